@@ -101,16 +101,20 @@ public class SessionController {
                 request.getFileSize(),
                 request.getSignature(),
                 request.getFileHash(),
-                request.getSenderEphemeralKey()).thenAccept(session -> {
+                request.getSenderEphemeralKey(),
+                request.getOffer(),
+                request.getOfferSignature(),
+                request.getKeyRotation()).thenAccept(session -> {
                     long expiresIn = session.getExpiresAt() - System.currentTimeMillis();
-                    result.setResult(ResponseEntity.ok(
-                            new ServeResponse(
-                                    "matched",
-                                    session.getSessionId(),
-                                    session.getSocketPort(),
-                                    "Receiver accepted, Proceeding to socket transfer.",
-                                    expiresIn,
-                                    session.getReceiverEphemeralKey())));
+                    ServeResponse response = new ServeResponse(
+                            "matched",
+                            session.getSessionId(),
+                            session.getSocketPort(),
+                            "Receiver accepted, Proceeding to socket transfer.",
+                            expiresIn,
+                            session.getReceiverEphemeralKey());
+                    response.setMaxStreams(socketSessionRegistry.getMaxStreams());
+                    result.setResult(ResponseEntity.ok(response));
                 }).exceptionally(ex -> {
                     // Timeout or error
                     log.error("Serve failed: {}", ex.getMessage());
@@ -155,21 +159,25 @@ public class SessionController {
                 .thenAccept(session -> {
                     // Alice initiated! Return to Bob
                     long expiresIn = session.getExpiresAt() - System.currentTimeMillis();
-                    result.setResult(ResponseEntity.ok(
-                            new ListenResponse(
-                                    "matched",
-                                    session.getSessionId(),
-                                    session.getSenderFp(),
-                                    session.getFilename(),
-                                    session.getFileSize(),
-                                    session.getSignature(),
-                                    session.getFileHash(),
-                                    session.getSocketPort(),
-                                    "Incoming transfer from " + session
-                                            .getSenderFp()
-                                            .substring(0, 8),
-                                    session.getSenderEphemeralKey(),
-                                    session.getReceiverEphemeralKey())));
+                    ListenResponse response = new ListenResponse(
+                            "matched",
+                            session.getSessionId(),
+                            session.getSenderFp(),
+                            session.getFilename(),
+                            session.getFileSize(),
+                            session.getSignature(),
+                            session.getFileHash(),
+                            session.getSocketPort(),
+                            "Incoming transfer from " + session
+                                    .getSenderFp()
+                                    .substring(0, 8),
+                            session.getSenderEphemeralKey(),
+                            session.getReceiverEphemeralKey());
+                    response.setMaxStreams(socketSessionRegistry.getMaxStreams());
+                    response.setOffer(session.getOffer());
+                    response.setOfferSignature(session.getOfferSignature());
+                    response.setKeyRotation(session.getKeyRotation());
+                    result.setResult(ResponseEntity.ok(response));
                 }).exceptionally(ex -> {
                     log.error("Listen failed: {}", ex.getMessage());
                     result.setResult(ResponseEntity.status(408).body(
@@ -12,6 +12,10 @@ public class ListenResponse {
     private String message;
     private String senderEphemeralKey; // X25519 public key from sender (hex-encoded)
     private String receiverEphemeralKey; // X25519 public key from receiver (hex-encoded)
+    private int maxStreams; // Data connections allowed per session
+    private String offer; // Signed transfer offer from sender (hex-encoded)
+    private String offerSignature; // Sender's signature over the offer (hex-encoded)
+    private String keyRotation; // Sender's signed key rotation statements (JSON)
 
     // Constructors
     public ListenResponse() {
@@ -79,6 +83,22 @@ public class ListenResponse {
         return receiverEphemeralKey;
     }
 
+    public int getMaxStreams() {
+        return maxStreams;
+    }
+
+    public String getOffer() {
+        return offer;
+    }
+
+    public String getOfferSignature() {
+        return offerSignature;
+    }
+
+    public String getKeyRotation() {
+        return keyRotation;
+    }
+
     // Setters
     public void setStatus(String status) {
         this.status = status;
@@ -123,4 +143,20 @@ public class ListenResponse {
     public void setReceiverEphemeralKey(String receiverEphemeralKey) {
         this.receiverEphemeralKey = receiverEphemeralKey;
     }
+
+    public void setMaxStreams(int maxStreams) {
+        this.maxStreams = maxStreams;
+    }
+
+    public void setOffer(String offer) {
+        this.offer = offer;
+    }
+
+    public void setOfferSignature(String offerSignature) {
+        this.offerSignature = offerSignature;
+    }
+
+    public void setKeyRotation(String keyRotation) {
+        this.keyRotation = keyRotation;
+    }
 }
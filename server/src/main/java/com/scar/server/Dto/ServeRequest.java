@@ -8,6 +8,9 @@ public class ServeRequest {
     private String signature;
     private String fileHash;
     private String senderEphemeralKey; // X25519 public key (hex-encoded, 64 chars)
+    private String offer; // Signed transfer offer (hex-encoded, opaque to the relay)
+    private String offerSignature; // Sender's signature over the offer (hex-encoded)
+    private String keyRotation; // Sender's signed key rotation statements (JSON, opaque to the relay)
 
     // Constructors
     public ServeRequest() {
@@ -53,6 +56,18 @@ public class ServeRequest {
         return senderEphemeralKey;
     }
 
+    public String getOffer() {
+        return offer;
+    }
+
+    public String getOfferSignature() {
+        return offerSignature;
+    }
+
+    public String getKeyRotation() {
+        return keyRotation;
+    }
+
     // Setters
     public void setSenderFp(String senderFp) {
         this.senderFp = senderFp;
@@ -81,4 +96,16 @@ public class ServeRequest {
     public void setSenderEphemeralKey(String senderEphemeralKey) {
         this.senderEphemeralKey = senderEphemeralKey;
     }
+
+    public void setOffer(String offer) {
+        this.offer = offer;
+    }
+
+    public void setOfferSignature(String offerSignature) {
+        this.offerSignature = offerSignature;
+    }
+
+    public void setKeyRotation(String keyRotation) {
+        this.keyRotation = keyRotation;
+    }
 }
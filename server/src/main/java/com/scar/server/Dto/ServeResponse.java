@@ -7,6 +7,7 @@ public class ServeResponse {
     private String message;
     private long expiresIn;
     private String receiverEphemeralKey; // X25519 public key from receiver
+    private int maxStreams; // Data connections allowed per session
 
     // Constructors
     public ServeResponse() {
@@ -47,6 +48,10 @@ public class ServeResponse {
         return receiverEphemeralKey;
     }
 
+    public int getMaxStreams() {
+        return maxStreams;
+    }
+
     // Setters
     public void setStatus(String status) {
         this.status = status;
@@ -71,4 +76,8 @@ public class ServeResponse {
     public void setReceiverEphemeralKey(String receiverEphemeralKey) {
         this.receiverEphemeralKey = receiverEphemeralKey;
     }
+
+    public void setMaxStreams(int maxStreams) {
+        this.maxStreams = maxStreams;
+    }
 }
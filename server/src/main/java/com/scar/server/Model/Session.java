@@ -15,6 +15,9 @@ public class Session {
     private static final long SESSION_TIMEOUT_MS = 300_000; // 5 minutes
     private String senderEphemeralKey; // X25519 public key from sender
     private String receiverEphemeralKey; // X25519 public key from receiver
+    private String offer; // Signed transfer offer, passed through to the receiver
+    private String offerSignature;
+    private String keyRotation; // Sender's key rotation statements, passed through to the receiver
 
     public Session() {
     }
@@ -81,6 +84,30 @@ public class Session {
         this.receiverEphemeralKey = receiverEphemeralKey;
     }
 
+    public String getOffer() {
+        return offer;
+    }
+
+    public void setOffer(String offer) {
+        this.offer = offer;
+    }
+
+    public String getOfferSignature() {
+        return offerSignature;
+    }
+
+    public void setOfferSignature(String offerSignature) {
+        this.offerSignature = offerSignature;
+    }
+
+    public String getKeyRotation() {
+        return keyRotation;
+    }
+
+    public void setKeyRotation(String keyRotation) {
+        this.keyRotation = keyRotation;
+    }
+
     public String getStatus() {
         return status;
     }
@@ -27,7 +27,8 @@ public class SessionService {
 
     public CompletableFuture<Session> initiateAndWait(
             String senderFp, String receiverFp, String filename,
-            long fileSize, String signature, String fileHash, String senderEphemeralKey) {
+            long fileSize, String signature, String fileHash, String senderEphemeralKey,
+            String offer, String offerSignature, String keyRotation) {
 
         // Validate inputs
         if (senderFp == null || senderFp.isEmpty() ||
@@ -44,6 +45,11 @@ public class SessionService {
         String sessionId = UUID.randomUUID().toString();
         Session session = new Session(sessionId, senderFp, receiverFp, filename, fileSize, signature, fileHash,
                 senderEphemeralKey);
+        // Optional: older senders don't send an offer
+        session.setOffer(offer);
+        session.setOfferSignature(offerSignature);
+        // Optional: only sent after the sender rotated its key
+        session.setKeyRotation(keyRotation);
 
         sessions.put(sessionId, session);
 
@@ -20,7 +20,8 @@ import static com.scar.server.Controller.SessionController.*;
  * Handles file transfer socket connections
  * Protocol:
  * 1. Client connects
- * 2. Client sends "session_id:role\n" (raw text)
+ * 2. Client sends "session_id:role\n" (raw text), or "session_id:role:N\n" for
+ *    extra data stream N of a parallel transfer
  * 3. Server validates session, waits for partner
  * 4. When both connected, forward all raw binary data bidirectionally
  */
@@ -30,6 +31,7 @@ public class FileTransferHandler extends ChannelInboundHandlerAdapter {
     private final SocketSessionRegistry registry;
     private final SessionService sessionService;
     private String sessionId;
+    private String transferKey; // sessionId, or "sessionId#N" for extra data stream N
     private String role; // "sender" or "receiver"
     private volatile boolean paired = false;
     private volatile boolean readyAckReceived = false;
@@ -105,10 +107,10 @@ public class FileTransferHandler extends ChannelInboundHandlerAdapter {
                     sessionId.substring(0, 8), reset);
 
             // Mark ACK in the transfer object
-            SocketSessionRegistry.ActiveTransfer transfer = registry.getActiveTransfer(sessionId);
+            SocketSessionRegistry.ActiveTransfer transfer = registry.getActiveTransfer(transferKey);
             if (transfer != null) {
                 transfer.markAcked(role);
-                registry.checkBothAcked(sessionId);
+                registry.checkBothAcked(transferKey);
             }
 
             // If there's remaining data in buffer, it's file data - save it for after paired=true
@@ -141,8 +143,8 @@ public class FileTransferHandler extends ChannelInboundHandlerAdapter {
         buf.readBytes(handshakeData);
         String message = new String(handshakeData).trim(); // Trim newline and whitespace
 
-        // Expected format: "session_id:role" where role is "sender" or "receiver"
-        String[] parts = message.split(":", 2);
+        // Expected format: "session_id:role[:stream]" where role is "sender" or "receiver"
+        String[] parts = message.split(":", 3);
         if (parts.length < 2) {
             log.error("Invalid handshake format: {}", message);
             ctx.close();
@@ -152,6 +154,22 @@ public class FileTransferHandler extends ChannelInboundHandlerAdapter {
         this.sessionId = parts[0].trim();
         this.role = parts[1].trim();
 
+        // Extra data streams are paired separately from the primary connection
+        int stream = 0;
+        if (parts.length == 3) {
+            try {
+                stream = Integer.parseInt(parts[2].trim());
+            } catch (NumberFormatException e) {
+                stream = -1;
+            }
+            if (stream < 0 || stream >= registry.getMaxStreams()) {
+                log.error("Invalid stream index: {}", parts[2]);
+                ctx.close();
+                return;
+            }
+        }
+        this.transferKey = stream == 0 ? sessionId : sessionId + "#" + stream;
+
         log.info("Handshake: session={}{}{}, role={}{}{}", yellow, sessionId.substring(0, 8), reset, green, role,
                 green);
 
@@ -172,14 +190,14 @@ public class FileTransferHandler extends ChannelInboundHandlerAdapter {
         // Register connection - returns partner's pending connection if pairing
         // complete
         SocketSessionRegistry.PendingConnection partner = registry.registerConnection(
-                sessionId, ctx.channel(), role, session, this);
+                transferKey, ctx.channel(), role, session, this);
 
         if (partner != null) {
             log.info("Transfer ready! Session: {}{}{} | Both parties connected, sending READY signals",
                     yellow, sessionId.substring(0, 8), reset);
 
             // Get the active transfer and store both handlers
-            SocketSessionRegistry.ActiveTransfer transfer = registry.getActiveTransfer(sessionId);
+            SocketSessionRegistry.ActiveTransfer transfer = registry.getActiveTransfer(transferKey);
             if (transfer != null) {
                 transfer.setBothHandlers(role, this);
                 transfer.setBothHandlers(partner.role, partner.handler);
@@ -211,7 +229,7 @@ public class FileTransferHandler extends ChannelInboundHandlerAdapter {
     }
 
     private void forwardData(ChannelHandlerContext ctx, ByteBuf buf) {
-        SocketSessionRegistry.ActiveTransfer transfer = registry.getActiveTransfer(sessionId);
+        SocketSessionRegistry.ActiveTransfer transfer = registry.getActiveTransfer(transferKey);
         if (transfer == null) {
             log.error("No active transfer for session: {}{}{}", yellow, sessionId, reset);
             buf.release();
@@ -252,12 +270,12 @@ public class FileTransferHandler extends ChannelInboundHandlerAdapter {
 
     @Override
     public void channelInactive(ChannelHandlerContext ctx) {
-        if (sessionId != null) {
+        if (transferKey != null) {
             log.info("Channel disconnected: {} | Session: {}{}{}",
                     ctx.channel().remoteAddress(),
                     yellow, sessionId.substring(0, 8), reset);
 
-            SocketSessionRegistry.ActiveTransfer transfer = registry.getActiveTransfer(sessionId);
+            SocketSessionRegistry.ActiveTransfer transfer = registry.getActiveTransfer(transferKey);
             if (transfer != null) {
                 log.info("Transfer complete: ({}) gigabytes | Session: {}{}{}",
                         transfer.bytesTransferred / (1024 * 1024 * 1024),
@@ -4,6 +4,7 @@ import com.scar.server.Model.Session;
 import io.netty.channel.Channel;
 import org.slf4j.Logger;
 import org.slf4j.LoggerFactory;
+import org.springframework.beans.factory.annotation.Value;
 import org.springframework.stereotype.Component;
 
 import java.util.Map;
@@ -18,6 +19,12 @@ import static com.scar.server.Controller.SessionController.*;
 public class SocketSessionRegistry {
     private static final Logger log = LoggerFactory.getLogger(SocketSessionRegistry.class);
 
+    // Data connections a client may open per session (stream 0 is the primary one)
+    @Value("${rshare.socket.max-streams:8}")
+    private int maxStreams;
+
+    // Keys are the session ID for stream 0 and "sessionId#N" for extra data streams
+
     // Map: sessionId -> PendingConnection (waiting for pair)
     private final Map<String, PendingConnection> pendingConnections = new ConcurrentHashMap<>();
 
@@ -124,6 +131,10 @@ public class SocketSessionRegistry {
         }
     }
 
+    public int getMaxStreams() {
+        return maxStreams;
+    }
+
     // Statistics getters
     public int getActiveSessionCount() {
         return activeTransfers.size();
@@ -0,0 +1,135 @@
+use super::{AgentRequest, AgentResponse, socket_path};
+use crate::config::{AGENT_MAX_MESSAGE_LEN, AGENT_RESPONSE_TIMEOUT_MILLIS};
+use crate::crypto::signing;
+use crate::utils::error::{Error, Result};
+use ed25519_dalek::{Signature, VerifyingKey};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Connection details of a running agent and the key it holds
+///
+/// Each request opens its own short connection, so a client can be cloned freely
+/// (the Noise handshake keeps one per DH object).
+#[derive(Debug, Clone)]
+pub struct AgentClient {
+    socket: PathBuf,
+    public_key: VerifyingKey,
+    expires_in: u64,
+}
+
+impl AgentClient {
+    /// The agent on the default socket, if one is running
+    pub fn find() -> Option<Self> {
+        AgentClient::connect(&socket_path().ok()?).ok()
+    }
+
+    /// Ask the agent at `socket` which key it holds
+    pub fn connect(socket: &Path) -> Result<Self> {
+        match request_to(socket, &AgentRequest::Identity)? {
+            AgentResponse::Identity {
+                public_key,
+                expires_in,
+            } => Ok(AgentClient {
+                socket: socket.to_path_buf(),
+                public_key: signing::parse_verifying_key(&public_key)?,
+                expires_in,
+            }),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub fn public_key(&self) -> VerifyingKey {
+        self.public_key
+    }
+
+    pub fn socket(&self) -> &Path {
+        &self.socket
+    }
+
+    /// Seconds the key had left when the client connected
+    pub fn expires_in(&self) -> u64 {
+        self.expires_in
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Result<Signature> {
+        let request = AgentRequest::Sign {
+            data: hex::encode(data),
+        };
+        match request_to(&self.socket, &request)? {
+            AgentResponse::Signature { signature } => {
+                let signature = Signature::from_slice(&decode(&signature)?)
+                    .map_err(|_e| Error::CryptoError("Agent sent an invalid signature".into()))?;
+
+                // An agent that swapped keys in the meantime must not go unnoticed
+                signing::verify_bytes(&self.public_key, data, &signature)?;
+                Ok(signature)
+            }
+            response => Err(unexpected(response)),
+        }
+    }
+
+    pub fn x25519(&self, public_key: &[u8]) -> Result<[u8; 32]> {
+        let request = AgentRequest::X25519 {
+            public_key: hex::encode(public_key),
+        };
+        match request_to(&self.socket, &request)? {
+            AgentResponse::SharedSecret { secret } => decode(&secret)?
+                .try_into()
+                .map_err(|_e| Error::CryptoError("Agent sent an invalid shared secret".into())),
+            response => Err(unexpected(response)),
+        }
+    }
+
+    /// Make the agent forget the key and exit
+    pub fn stop(&self) -> Result<()> {
+        match request_to(&self.socket, &AgentRequest::Stop)? {
+            AgentResponse::Stopping => Ok(()),
+            response => Err(unexpected(response)),
+        }
+    }
+}
+
+/// Send one request and wait for the answer
+fn request(socket: &Path, request: &AgentRequest) -> Result<AgentResponse> {
+    let stream = UnixStream::connect(socket)
+        .map_err(|_e| Error::SessionError(format!("No agent at {}", socket.display())))?;
+    let timeout = Some(Duration::from_millis(AGENT_RESPONSE_TIMEOUT_MILLIS));
+    stream.set_read_timeout(timeout)?;
+    stream.set_write_timeout(timeout)?;
+
+    let mut line = serde_json::to_string(request)?;
+    line.push('\n');
+    (&stream).write_all(line.as_bytes())?;
+
+    let mut response = String::new();
+    BufReader::new(&stream)
+        .take(AGENT_MAX_MESSAGE_LEN as u64)
+        .read_line(&mut response)?;
+
+    serde_json::from_str(response.trim_end())
+        .map_err(|_e| Error::SessionError("Invalid response from the agent".to_string()))
+}
+
+/// Like `request`, but an agent-side refusal becomes an error
+fn request_to(socket: &Path, message: &AgentRequest) -> Result<AgentResponse> {
+    match request(socket, message)? {
+        AgentResponse::Error { reason } => {
+            Err(Error::CryptoError(format!("Agent refused: {}", reason)))
+        }
+        response => Ok(response),
+    }
+}
+
+fn decode(hex_str: &str) -> Result<Vec<u8>> {
+    hex::decode(hex_str)
+        .map_err(|_e| Error::SessionError("Invalid response from the agent".to_string()))
+}
+
+fn unexpected(response: AgentResponse) -> Error {
+    Error::SessionError(format!(
+        "Unexpected response from the agent: {:?}",
+        response
+    ))
+}
@@ -0,0 +1,170 @@
+use super::{AgentRequest, AgentResponse};
+use crate::config::AGENT_MAX_MESSAGE_LEN;
+use crate::crypto::identity::Identity;
+use crate::utils::error::{Error, Result};
+use ed25519_dalek::SigningKey;
+use std::fs;
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::mpsc;
+use tokio::time::Instant;
+
+/// Agent bound to its socket, holding the unlocked identity key
+pub struct Agent {
+    listener: UnixListener,
+    socket: PathBuf,
+    /// Only connections from this user are answered (the socket's owner)
+    owner: u32,
+    identity: Arc<Identity>,
+}
+
+impl Agent {
+    /// Listen on `socket` with owner-only permissions, refusing to replace a live agent
+    pub fn bind(socket: &Path, signing_key: SigningKey) -> Result<Self> {
+        if let Some(dir) = socket.parent()
+            && !dir.exists()
+        {
+            fs::create_dir_all(dir)
+                .map_err(|_e| Error::FileError("Failed to create agent directory".to_string()))?;
+            fs::set_permissions(dir, fs::Permissions::from_mode(0o700)).map_err(|_e| {
+                Error::FileError("Failed to set agent directory permissions".to_string())
+            })?;
+        }
+
+        if socket.exists() {
+            if std::os::unix::net::UnixStream::connect(socket).is_ok() {
+                return Err(Error::SessionError(format!(
+                    "An agent is already running at {}",
+                    socket.display()
+                )));
+            }
+            // Left behind by an agent that did not shut down cleanly
+            fs::remove_file(socket)?;
+        }
+
+        let listener = UnixListener::bind(socket)
+            .map_err(|e| Error::FileError(format!("Failed to bind agent socket: {}", e)))?;
+        fs::set_permissions(socket, fs::Permissions::from_mode(0o600))
+            .map_err(|_e| Error::FileError("Failed to set agent socket permissions".to_string()))?;
+        let owner = fs::metadata(socket)?.uid();
+
+        Ok(Agent {
+            listener,
+            socket: socket.to_path_buf(),
+            owner,
+            identity: Arc::new(Identity::Local(signing_key)),
+        })
+    }
+
+    /// Answer requests until `timeout` runs out, a stop request or Ctrl+C,
+    /// then drop the key and remove the socket
+    pub async fn run(self, timeout: Duration) -> Result<()> {
+        let deadline = Instant::now() + timeout;
+        let (stop_tx, mut stop_rx) = mpsc::channel::<()>(1);
+
+        let result = loop {
+            tokio::select! {
+                accepted = self.listener.accept() => {
+                    let stream = match accepted {
+                        Ok((stream, _addr)) => stream,
+                        Err(e) => break Err(e.into()),
+                    };
+                    let same_user = stream
+                        .peer_cred()
+                        .is_ok_and(|cred| cred.uid() == self.owner);
+                    if !same_user {
+                        continue;
+                    }
+                    tokio::spawn(serve_connection(
+                        stream,
+                        self.identity.clone(),
+                        deadline,
+                        stop_tx.clone(),
+                    ));
+                }
+                _ = tokio::time::sleep_until(deadline) => break Ok(()),
+                _ = stop_rx.recv() => break Ok(()),
+                _ = tokio::signal::ctrl_c() => break Ok(()),
+            }
+        };
+
+        let _ = fs::remove_file(&self.socket);
+        result
+    }
+}
+
+/// Answer the requests of one client, one JSON line each
+async fn serve_connection(
+    stream: UnixStream,
+    identity: Arc<Identity>,
+    deadline: Instant,
+    stop: mpsc::Sender<()>,
+) -> Result<()> {
+    let (reader, mut writer) = stream.into_split();
+    let mut reader = BufReader::new(reader);
+
+    loop {
+        let mut line = String::new();
+        let read = (&mut reader)
+            .take(AGENT_MAX_MESSAGE_LEN as u64)
+            .read_line(&mut line)
+            .await?;
+        if read == 0 {
+            return Ok(());
+        }
+
+        let request = serde_json::from_str::<AgentRequest>(line.trim_end());
+        let stopping = matches!(request, Ok(AgentRequest::Stop));
+        let response = match request {
+            Ok(request) => handle(&identity, request, deadline),
+            Err(_e) => AgentResponse::Error {
+                reason: "invalid request".to_string(),
+            },
+        };
+
+        let mut reply = serde_json::to_string(&response)?;
+        reply.push('\n');
+        writer.write_all(reply.as_bytes()).await?;
+
+        if stopping {
+            let _ = stop.send(()).await;
+            return Ok(());
+        }
+    }
+}
+
+fn handle(identity: &Identity, request: AgentRequest, deadline: Instant) -> AgentResponse {
+    let refused = |reason: &str| AgentResponse::Error {
+        reason: reason.to_string(),
+    };
+
+    match request {
+        AgentRequest::Identity => AgentResponse::Identity {
+            public_key: hex::encode(identity.verifying_key().to_bytes()),
+            expires_in: deadline.saturating_duration_since(Instant::now()).as_secs(),
+        },
+        AgentRequest::Sign { data } => match hex::decode(data) {
+            Ok(data) => match identity.sign(&data) {
+                Ok(signature) => AgentResponse::Signature {
+                    signature: hex::encode(signature.to_bytes()),
+                },
+                Err(_e) => refused("signing failed"),
+            },
+            Err(_e) => refused("invalid data"),
+        },
+        AgentRequest::X25519 { public_key } => match hex::decode(public_key) {
+            Ok(public_key) => match identity.x25519(&public_key) {
+                Ok(secret) => AgentResponse::SharedSecret {
+                    secret: hex::encode(secret),
+                },
+                Err(_e) => refused("invalid public key"),
+            },
+            Err(_e) => refused("invalid public key"),
+        },
+        AgentRequest::Stop => AgentResponse::Stopping,
+    }
+}
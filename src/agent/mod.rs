@@ -0,0 +1,60 @@
+//! `rs agent`: keeps the unlocked identity key in one process and signs for the others
+//!
+//! Requests and responses are single JSON lines on a Unix socket that only the owner
+//! can reach. The agent answers with signatures and X25519 shared secrets, the private
+//! key never crosses the socket.
+
+mod client;
+mod daemon;
+
+pub use client::*;
+pub use daemon::*;
+
+use crate::config::{AGENT_DIR, AGENT_SOCKET_ENV, AGENT_SOCKET_FILE};
+use crate::dirs::profile;
+use crate::utils::error::Result;
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum AgentRequest {
+    /// Which key the agent holds and for how long
+    Identity,
+    /// Ed25519 signature over hex-encoded `data`
+    Sign { data: String },
+    /// X25519 between the identity key and a hex-encoded peer key (Noise static DH)
+    X25519 { public_key: String },
+    /// Forget the key and exit
+    Stop,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "result", rename_all = "snake_case")]
+pub enum AgentResponse {
+    Identity {
+        /// Hex-encoded Ed25519 public key
+        public_key: String,
+        /// Seconds until the agent forgets the key
+        expires_in: u64,
+    },
+    Signature {
+        signature: String,
+    },
+    SharedSecret {
+        secret: String,
+    },
+    Stopping,
+    Error {
+        reason: String,
+    },
+}
+
+/// Agent socket: `$RSHARE_AGENT_SOCK`, or `agent/agent.sock` in the profile directory
+pub fn socket_path() -> Result<PathBuf> {
+    if let Ok(path) = std::env::var(AGENT_SOCKET_ENV) {
+        return Ok(PathBuf::from(path));
+    }
+
+    Ok(profile::base_dir()?.join(AGENT_DIR).join(AGENT_SOCKET_FILE))
+}
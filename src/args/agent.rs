@@ -0,0 +1,143 @@
+use crate::agent::{self, Agent, AgentClient};
+use crate::config::{AGENT_READY_SIGNAL, AGENT_SOCKET_ENV, KEY_FINGERPRINT_DISPLAY_LEN};
+use crate::dirs::{config, keys};
+use crate::utils::error::{Error, Result};
+use colored::Colorize;
+use ed25519_dalek::SigningKey;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Unlock the identity key once and keep it in a background agent
+///
+/// The passphrase is asked here, then a detached copy of `rs` receives the key over
+/// a pipe and serves it on the agent socket until the timeout.
+pub async fn start(timeout_secs: u64, foreground: bool, key_from_stdin: bool) -> Result<()> {
+    let socket = agent::socket_path()?;
+    let timeout = Duration::from_secs(timeout_secs);
+
+    // Detached agent spawned below: nothing may go to stdout after the ready line
+    if key_from_stdin {
+        let mut secret = [0u8; 32];
+        std::io::stdin().read_exact(&mut secret)?;
+        let agent = Agent::bind(&socket, SigningKey::from_bytes(&secret))?;
+        println!("{}", AGENT_READY_SIGNAL);
+        return agent.run(timeout).await;
+    }
+
+    if let Some(running) = AgentClient::find() {
+        return Err(Error::SessionError(format!(
+            "An agent is already running at {}",
+            running.socket().display()
+        )));
+    }
+
+    let config = config::load_config()?;
+    let (signing_key, verifying_key) = keys::load_keys_from(&config.path.keys_path)?;
+    keys::validate_keypair(&signing_key, &verifying_key)?;
+    let fingerprint = hex::encode(&verifying_key.to_bytes()[..KEY_FINGERPRINT_DISPLAY_LEN]);
+
+    if foreground {
+        let agent = Agent::bind(&socket, signing_key)?;
+        println!(
+            "{} Agent holding key {}...",
+            "✓".bright_green(),
+            fingerprint
+        );
+        println!("   Socket:  {}", socket.display());
+        println!("   Expires: in {}", format_duration(timeout_secs));
+        println!("\n Ctrl+C or {} to stop", "rs agent stop".bright_cyan());
+        return agent.run(timeout).await;
+    }
+
+    let mut child = Command::new(std::env::current_exe()?)
+        .args(["agent", "start", "--key-from-stdin", "--timeout"])
+        .arg(timeout_secs.to_string())
+        // Same socket as this process, whatever the profile or environment says
+        .env(AGENT_SOCKET_ENV, &socket)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        // Own process group, so Ctrl+C in this terminal does not reach it
+        .process_group(0)
+        .spawn()?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin.write_all(&signing_key.to_bytes())?;
+    }
+
+    let mut ready = String::new();
+    if let Some(stdout) = child.stdout.take() {
+        BufReader::new(stdout).read_line(&mut ready)?;
+    }
+    if ready.trim_end() != AGENT_READY_SIGNAL {
+        let mut reason = String::new();
+        if let Some(mut stderr) = child.stderr.take() {
+            let _ = stderr.read_to_string(&mut reason);
+        }
+        let _ = child.wait();
+        return Err(Error::SessionError(format!(
+            "Agent failed to start: {}",
+            reason.trim()
+        )));
+    }
+
+    println!("{} Agent started (pid {})", "✓".bright_green(), child.id());
+    println!("   Key:     {}...", fingerprint);
+    println!("   Socket:  {}", socket.display());
+    println!("   Expires: in {}", format_duration(timeout_secs));
+
+    Ok(())
+}
+
+/// Make the agent forget the key and exit
+pub async fn stop() -> Result<()> {
+    match AgentClient::find() {
+        Some(agent) => {
+            agent.stop()?;
+            println!("{} Agent stopped, key forgotten", "✓".bright_green());
+        }
+        None => println!("{} No agent running", "✗".bright_yellow()),
+    }
+    Ok(())
+}
+
+/// Show whether an agent is running and which key it holds
+pub async fn status() -> Result<()> {
+    let Some(agent) = AgentClient::find() else {
+        println!("{} No agent running", "✗".bright_yellow());
+        println!("   Socket: {}", agent::socket_path()?.display());
+        return Ok(());
+    };
+
+    let public_key = agent.public_key();
+    println!("{} Agent running", "✓".bright_green());
+    println!(
+        "   Key:     {}...",
+        hex::encode(&public_key.to_bytes()[..KEY_FINGERPRINT_DISPLAY_LEN])
+    );
+    println!("   Socket:  {}", agent.socket().display());
+    println!("   Expires: in {}", format_duration(agent.expires_in()));
+
+    // serve and listen only use the agent when it holds the configured key
+    if let Ok(config) = config::load_config()
+        && keys::load_public_key_from(&config.path.keys_path).is_ok_and(|key| key != public_key)
+    {
+        println!(
+            "{} Not the key in {}, serve and listen will not use it",
+            "✗".bright_yellow(),
+            config.path.keys_path.display()
+        );
+    }
+
+    Ok(())
+}
+
+fn format_duration(secs: u64) -> String {
+    match (secs / 3600, secs % 3600 / 60, secs % 60) {
+        (0, 0, s) => format!("{}s", s),
+        (0, m, s) => format!("{}m {}s", m, s),
+        (h, m, _) => format!("{}h {}m", h, m),
+    }
+}
@@ -1,15 +1,25 @@
-use crate::config::KEY_FINGERPRINT_DISPLAY_LEN;
+use crate::config::{KEY_FINGERPRINT_DISPLAY_LEN, PASSPHRASE_ENV, PASSPHRASE_FD_ENV};
+use crate::crypto::seed;
 use crate::dirs::config::Config;
 use crate::dirs::{config, contacts, keys};
 use crate::utils::error::Result;
+use crate::utils::passphrase;
 use colored::Colorize;
 use std::path::PathBuf;
 
-pub async fn run(key_path: Option<PathBuf>, force: bool) -> Result<()> {
+pub async fn run(
+    key_path: Option<PathBuf>,
+    force: bool,
+    protect: bool,
+    from_seed: bool,
+) -> Result<()> {
     println!("{}", "Initializing rshare...\n".bright_cyan().bold());
 
     // Determine keys path (CLI arg or default)
-    let keys_path = key_path.unwrap_or_else(|| keys::get_default_keys_dir().unwrap());
+    let keys_path = match key_path {
+        Some(key_path) => key_path,
+        None => keys::get_default_keys_dir()?,
+    };
 
     // Check if config exists
     let config_path = config::get_config_path()?;
@@ -25,10 +35,23 @@ pub async fn run(key_path: Option<PathBuf>, force: bool) -> Result<()> {
         {
             println!("{} Found keys and downloads", "✓".bright_green());
 
+            // Protected keys are checked by their AEAD tag whenever they are unlocked
+            let keys_loaded = if keys::is_encrypted_at(&loaded_config.path.keys_path)? {
+                println!("{} Keys are passphrase-protected", "✓".bright_green());
+                keys::load_public_key_from(&loaded_config.path.keys_path)
+                    .map(|public_key| (None, public_key))
+            } else {
+                keys::load_keys_from(&loaded_config.path.keys_path)
+                    .map(|(private_key, public_key)| (Some(private_key), public_key))
+            };
+
             // Load and validate keys
-            match keys::load_keys_from(&loaded_config.path.keys_path) {
+            match keys_loaded {
                 Ok((private_key, public_key)) => {
-                    if keys::validate_keypair(&private_key, &public_key).is_ok() {
+                    let valid = private_key.is_none_or(|private_key| {
+                        keys::validate_keypair(&private_key, &public_key).is_ok()
+                    });
+                    if valid {
                         println!("{} Keys are valid", "✓".bright_green());
 
                         if !force {
@@ -39,6 +62,12 @@ pub async fn run(key_path: Option<PathBuf>, force: bool) -> Result<()> {
                             );
                             println!("   Keys: {}", loaded_config.path.keys_path.display());
                             println!("   Config: {}", config_path.display());
+                            if from_seed {
+                                println!(
+                                    "\n Use {} to replace them with the key from the seed phrase",
+                                    "--force".bright_cyan()
+                                );
+                            }
                             return Ok(());
                         } else {
                             println!("\n{}", "  Forcing regeneration...".bright_yellow());
@@ -58,14 +87,46 @@ pub async fn run(key_path: Option<PathBuf>, force: bool) -> Result<()> {
         println!("{} No config found", "✗".bright_yellow());
     }
 
-    // Generate new keys
-    println!("\n{}", " Generating new keypair".bright_cyan());
-    let (private_key, public_key) = keys::generate_keys()?;
+    // Ask before generating, so a mistyped phrase or passphrase leaves nothing behind
+    let restored = if from_seed {
+        Some(seed::from_phrase(&passphrase::read_seed_phrase()?)?)
+    } else {
+        None
+    };
+    let new_passphrase = if protect {
+        Some(passphrase::read_new_passphrase(
+            PASSPHRASE_ENV,
+            PASSPHRASE_FD_ENV,
+        )?)
+    } else {
+        None
+    };
+
+    let (private_key, public_key) = match restored {
+        Some(private_key) => {
+            println!("\n{}", " Restoring keypair from seed phrase".bright_cyan());
+            let public_key = private_key.verifying_key();
+            (private_key, public_key)
+        }
+        None => {
+            println!("\n{}", " Generating new keypair".bright_cyan());
+            keys::generate_keys()?
+        }
+    };
     keys::validate_keypair(&private_key, &public_key)?;
 
     // Save keys
-    println!("{}", " Saving keys".bright_cyan());
-    keys::save_keys_to(&private_key, &public_key, keys_path.clone())?;
+    if new_passphrase.is_some() {
+        println!("{}", " Saving keys (passphrase-protected)".bright_cyan());
+    } else {
+        println!("{}", " Saving keys".bright_cyan());
+    }
+    keys::save_keys_to(
+        &private_key,
+        &public_key,
+        keys_path.clone(),
+        new_passphrase.as_deref(),
+    )?;
 
     // Add self to trust
     println!("{}", " Adding self to trust".bright_cyan());
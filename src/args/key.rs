@@ -0,0 +1,328 @@
+#[cfg(unix)]
+use crate::agent::AgentClient;
+use crate::config::{
+    KEY_EXPORT_COMMENT, KEY_FINGERPRINT_DISPLAY_LEN, NEW_PASSPHRASE_ENV, NEW_PASSPHRASE_FD_ENV,
+    PASSPHRASE_ENV, PASSPHRASE_FD_ENV,
+};
+use crate::crypto::key_format::{self, KeyFormat};
+use crate::crypto::rotation::KeyRotation;
+use crate::crypto::seed;
+use crate::dirs::config::Config;
+use crate::dirs::{config, contacts, keys};
+use crate::server::offer;
+use crate::utils::error::{Error, Result};
+use crate::utils::passphrase;
+use colored::Colorize;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Change, set or remove the passphrase protecting the private key
+pub async fn passwd(remove: bool) -> Result<()> {
+    let config = config::load_config()?;
+    let keys_path = &config.path.keys_path;
+
+    let protected = keys::is_encrypted_at(keys_path)?;
+    if remove && !protected {
+        println!(
+            "{} Private key is not passphrase-protected",
+            "✓".bright_green()
+        );
+        return Ok(());
+    }
+
+    // Asks for the current passphrase if there is one
+    let (private_key, public_key) = keys::load_keys_from(keys_path)?;
+    keys::validate_keypair(&private_key, &public_key)?;
+
+    if remove {
+        keys::save_keys_to(&private_key, &public_key, keys_path.clone(), None)?;
+        println!("{} Passphrase removed", "✓".bright_yellow());
+        println!("   The private key is protected by file permissions only");
+        return Ok(());
+    }
+
+    let new_passphrase =
+        passphrase::read_new_passphrase(NEW_PASSPHRASE_ENV, NEW_PASSPHRASE_FD_ENV)?;
+    keys::save_keys_to(
+        &private_key,
+        &public_key,
+        keys_path.clone(),
+        Some(&new_passphrase),
+    )?;
+
+    if protected {
+        println!("{} Passphrase changed", "✓".bright_green());
+    } else {
+        println!(
+            "{} Private key is now passphrase-protected",
+            "✓".bright_green()
+        );
+    }
+    println!("   Keys: {}", keys_path.display());
+
+    Ok(())
+}
+
+/// Print the private key as a seed phrase, to write down and restore with `init --from-seed`
+pub async fn export_seed() -> Result<()> {
+    let config = config::load_config()?;
+    let keys_path = &config.path.keys_path;
+
+    // Asks for the passphrase if there is one, the agent never hands out the key
+    let (private_key, public_key) = keys::load_keys_from(keys_path)?;
+    keys::validate_keypair(&private_key, &public_key)?;
+    let phrase = seed::to_phrase(&private_key)?;
+
+    println!(
+        "{}",
+        " Seed phrase (anyone holding these words holds your identity):\n"
+            .bright_yellow()
+            .bold()
+    );
+    let words: Vec<&str> = phrase.split(' ').collect();
+    for (row, words) in words.chunks(4).enumerate() {
+        let line: Vec<String> = words
+            .iter()
+            .enumerate()
+            .map(|(i, word)| format!("{:>2}. {:<10}", row * 4 + i + 1, word))
+            .collect();
+        println!("   {}", line.join(" ").trim_end());
+    }
+
+    println!(
+        "\n   Public: {}",
+        hex::encode(public_key.to_bytes()).bright_white()
+    );
+    println!(
+        "\n Restore on a new machine with {}",
+        "rs init --from-seed".bright_cyan()
+    );
+    println!(
+        " After {}, export the seed phrase again.",
+        "rs key rotate".bright_cyan()
+    );
+
+    Ok(())
+}
+
+/// Export the public or private key in a format other tools read
+pub async fn export(format: KeyFormat, private: bool, output: Option<PathBuf>) -> Result<()> {
+    let config = config::load_config()?;
+    let keys_path = &config.path.keys_path;
+
+    let exported = if private {
+        let (private_key, public_key) = keys::load_keys_from(keys_path)?;
+        keys::validate_keypair(&private_key, &public_key)?;
+        key_format::export_private(&private_key, format, KEY_EXPORT_COMMENT)?
+    } else {
+        let public_key = keys::load_public_key_from(keys_path)?;
+        key_format::export_public(&public_key, format, KEY_EXPORT_COMMENT)?
+    };
+    let exported = format!("{}\n", exported.trim_end());
+
+    let Some(path) = output else {
+        print!("{}", exported);
+        if private {
+            eprintln!(
+                "{} The private key above is not encrypted",
+                "✗".bright_yellow()
+            );
+        }
+        return Ok(());
+    };
+
+    write_new_file(&path, exported.as_bytes(), private)?;
+    if private {
+        println!(
+            "{} Private key exported to {}",
+            "✓".bright_green(),
+            path.display()
+        );
+        println!("   The file is not encrypted, keep it somewhere safe");
+    } else {
+        println!(
+            "{} Public key exported to {}",
+            "✓".bright_green(),
+            path.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Make an existing Ed25519 private key our identity
+///
+/// Works on a fresh machine too, in which case the config is created as `init` does.
+pub async fn import(file: PathBuf, force: bool, protect: bool) -> Result<()> {
+    let mut text = String::new();
+    if file == Path::new("-") {
+        std::io::stdin().read_to_string(&mut text)?;
+    } else {
+        text = fs::read_to_string(&file)
+            .map_err(|_e| Error::FileError(format!("Cannot read {}", file.display())))?;
+    }
+    let private_key = key_format::parse_private(&text)?;
+    let public_key = private_key.verifying_key();
+    keys::validate_keypair(&private_key, &public_key)?;
+
+    let config_path = config::get_config_path()?;
+    let existing_config = if config::exists_config_at(&config_path) {
+        Some(config::load_config()?)
+    } else {
+        None
+    };
+    let keys_path = match &existing_config {
+        Some(config) => config.path.keys_path.clone(),
+        None => keys::get_default_keys_dir()?,
+    };
+
+    if keys::keys_exist_at(&keys_path) && !force {
+        if keys::load_public_key_from(&keys_path).is_ok_and(|current| current == public_key) {
+            println!("{} This key is already your identity", "✓".bright_green());
+            return Ok(());
+        }
+        return Err(Error::InvalidInput(format!(
+            "Keys already exist at {}, use --force to replace them (back them up first with `rs key export-seed`)",
+            keys_path.display()
+        )));
+    }
+
+    let new_passphrase = if protect {
+        Some(passphrase::read_new_passphrase(
+            PASSPHRASE_ENV,
+            PASSPHRASE_FD_ENV,
+        )?)
+    } else {
+        None
+    };
+    keys::save_keys_to(
+        &private_key,
+        &public_key,
+        keys_path.clone(),
+        new_passphrase.as_deref(),
+    )?;
+
+    let mut contact_list = contacts::load_contacts()?;
+    if contact_list.get("self").is_some() {
+        contact_list.remove("self")?;
+    }
+    contact_list.add("self".to_string(), hex::encode(public_key.to_bytes()))?;
+    contacts::save_contacts(&contact_list)?;
+
+    if existing_config.is_none() {
+        let new_config = Config::create_config(keys_path.clone());
+        config::save_download_path(&new_config)?;
+        config::save_config(&new_config)?;
+    }
+
+    println!("{} Key imported", "✓".bright_green());
+    println!(
+        "   Public: {}",
+        hex::encode(public_key.to_bytes()).bright_white()
+    );
+    println!("   Keys:   {}", keys_path.display());
+
+    #[cfg(unix)]
+    if AgentClient::find().is_some_and(|agent| agent.public_key() != public_key) {
+        println!(
+            "\n{} The running agent holds another key, restart it with {}",
+            "✗".bright_yellow(),
+            "rs agent stop && rs agent start".bright_cyan()
+        );
+    }
+
+    Ok(())
+}
+
+/// Write an exported key, never over an existing file (owner-only for private keys)
+fn write_new_file(path: &Path, contents: &[u8], private: bool) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(if private { 0o600 } else { 0o644 });
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+
+    let mut file = options.open(path).map_err(|e| {
+        if e.kind() == std::io::ErrorKind::AlreadyExists {
+            Error::FileError(format!("{} already exists", path.display()))
+        } else {
+            Error::FileError(format!("Cannot write {}", path.display()))
+        }
+    })?;
+    file.write_all(contents)?;
+    Ok(())
+}
+
+/// Replace the key pair, leaving a rotation statement signed by the old and new key
+///
+/// The statement goes along with every transfer we send, so contacts who trust the
+/// old key move to the new one the next time they receive from us.
+pub async fn rotate() -> Result<()> {
+    let config = config::load_config()?;
+    let keys_path = &config.path.keys_path;
+
+    // The new key keeps the old one's passphrase, asked once
+    let passphrase = if keys::is_encrypted_at(keys_path)? {
+        Some(passphrase::read_passphrase(&format!(
+            "Passphrase for {}: ",
+            keys_path.display()
+        ))?)
+    } else {
+        None
+    };
+    let (old_private, old_public) = match &passphrase {
+        Some(passphrase) => keys::load_keys_with_passphrase(keys_path, passphrase)?,
+        None => keys::load_keys_from(keys_path)?,
+    };
+    keys::validate_keypair(&old_private, &old_public)?;
+
+    let (new_private, new_public) = keys::generate_keys()?;
+    keys::validate_keypair(&new_private, &new_public)?;
+    let rotation = KeyRotation::new(&old_private, &new_private, offer::now())?;
+
+    // Statement first: a new key without it would leave contacts stuck on the old one
+    let mut rotations = keys::load_rotations_from(keys_path)?;
+    rotations.push(rotation.clone());
+    keys::save_rotations_to(keys_path, &rotations)?;
+    keys::save_keys_to(
+        &new_private,
+        &new_public,
+        keys_path.clone(),
+        passphrase.as_deref(),
+    )?;
+
+    let mut contact_list = contacts::load_contacts()?;
+    if contact_list.get("self").is_some() {
+        contact_list.apply_rotation("self", std::slice::from_ref(&rotation))?;
+        contacts::save_contacts(&contact_list)?;
+    }
+
+    println!("{} Key rotated", "✓".bright_green());
+    println!(
+        "   Old key: {}...",
+        hex::encode(&old_public.to_bytes()[..KEY_FINGERPRINT_DISPLAY_LEN])
+    );
+    println!(
+        "   New key: {}",
+        hex::encode(new_public.to_bytes()).bright_white()
+    );
+    println!("   Keys:    {}", keys_path.display());
+    println!("\n Contacts who trust the old key switch over the next time they receive from you.");
+    println!(" Until then, they need your new key to send to you.");
+
+    #[cfg(unix)]
+    if AgentClient::find().is_some_and(|agent| agent.public_key() == old_public) {
+        println!(
+            "\n{} The running agent holds the old key, restart it with {}",
+            "✗".bright_yellow(),
+            "rs agent stop && rs agent start".bright_cyan()
+        );
+    }
+
+    Ok(())
+}
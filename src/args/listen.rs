@@ -1,36 +1,76 @@
 use crate::config::constants::*;
-use crate::crypto::{encryption, key_exchange, signing};
-use crate::dirs::config::Config;
-use crate::dirs::{config, contacts, keys};
-use crate::server::RelayClient;
+use crate::crypto::encryption::{CipherSuite, StreamDecryptor};
+use crate::crypto::identity::Identity;
+use crate::crypto::key_schedule::SessionKeys;
+use crate::crypto::noise::{self, NoiseHandshake};
+use crate::crypto::{key_exchange, rotation, signing};
+use crate::dirs::config::{Config, ConflictPolicy};
+use crate::dirs::{config, contacts, keys, offers};
+use crate::server::offer::{self, Offer, SignedOffer};
+use crate::server::protocol::{self, HashTrailer, Hello, NoiseInit, NoiseReply};
+use crate::server::{RelayClient, TransferRole, TransferSession};
+use crate::utils::checkpoint::Checkpoint;
+use crate::utils::compression;
+use crate::utils::download;
 use crate::utils::error::{Error, Result};
 use crate::utils::hash;
+use crate::utils::manifest::{Manifest, resolve_entry_path};
+use crate::utils::merkle::{self, MerkleTree};
+use crate::utils::sanitize::sanitize_filename;
 use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle};
+use ed25519_dalek::VerifyingKey;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 #[allow(unused_imports)]
 use memmap2::MmapMut;
-#[allow(unused_imports)]
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
 use std::fs::OpenOptions;
-use std::path::PathBuf;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 use tokio::fs::File;
-use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::io::{AsyncSeekExt, AsyncWrite, AsyncWriteExt, BufWriter};
+
+/// Set with `--stdout`, when stdout carries the file data
+static STATUS_TO_STDERR: AtomicBool = AtomicBool::new(false);
+
+/// Print status output, on stderr when the file itself goes to stdout
+macro_rules! status {
+    ($($arg:tt)*) => {
+        if STATUS_TO_STDERR.load(Ordering::Relaxed) {
+            eprintln!($($arg)*)
+        } else {
+            println!($($arg)*)
+        }
+    };
+}
 
 /// Listen for incoming file transfers
+#[allow(clippy::too_many_arguments)]
 pub async fn run(
     path: Option<PathBuf>,
     from: String,
     _quiet: bool,
     relay: Option<String>,
+    resume: bool,
+    stdout: bool,
+    on_conflict: Option<ConflictPolicy>,
+    streams: u32,
+    pq: bool,
+    cipher: Option<CipherSuite>,
 ) -> Result<()> {
-    println!("{}", "Listening...\n".bright_green().bold());
+    STATUS_TO_STDERR.store(stdout, Ordering::Relaxed);
+    status!("{}", "Listening...\n".bright_green().bold());
 
     // Load config and keys
     let config = config::load_config()?;
-    let (_signing_key, verifying_key) = keys::load_keys_from(&config.path.keys_path)?;
+    let identity = keys::load_identity(&config.path.keys_path)?;
+    let verifying_key = identity.verifying_key();
     let my_fingerprint = hex::encode(verifying_key.to_bytes());
 
-    // Determine download path
+    // Determine download path and what to do with names already taken
     let download_path = path.unwrap_or_else(|| config.path.download_path.clone());
+    let policy = on_conflict.unwrap_or(config.path.on_conflict);
     std::fs::create_dir_all(&download_path)?;
 
     //println!("{} Ready to receive files", "✓".bright_green());
@@ -53,6 +93,14 @@ pub async fn run(
             from
         ))
     })?;
+    if !expected_sender.is_verified() && expected_sender.public_key != my_fingerprint {
+        status!(
+            "{} '{}' is not verified, compare codes with {}",
+            "✗".bright_yellow().bold(),
+            from,
+            format!("rs trust verify {}", from).bright_cyan()
+        );
+    }
 
     //println!();
 
@@ -66,7 +114,7 @@ pub async fn run(
         server_config.socket_port,
     );
 
-    println!("{}", "Checking server health...".white());
+    status!("{}", "Checking server health...".white());
     relay_client.health_check().await?;
 
     // Generate ephemeral X25519 keypair for this transfer
@@ -86,13 +134,13 @@ pub async fn run(
     //println!();
 
     // Join transfer session (blocks until sender connects)
-    println!();
-    println!("{}", "Waiting for sender to connect...".yellow());
+    status!();
+    status!("{}", "Waiting for sender to connect...".yellow());
     let mut session = relay_client
-        .listen(my_fingerprint.clone(), receiver_ephemeral_hex)
+        .listen(my_fingerprint.clone(), receiver_ephemeral_hex.clone())
         .await?;
 
-    println!("  Session: {}", session.session_id().bright_green());
+    status!("  Session: {}", session.session_id().bright_green());
     //println!();
 
     // Extract metadata from HTTP response
@@ -116,14 +164,31 @@ pub async fn run(
         .clone()
         .ok_or_else(|| Error::SessionError("No file hash in session".to_string()))?;
 
-    // Verify sender is the expected contact
-    if expected_sender.public_key != sender_fp {
-        return Err(Error::SessionError(format!(
+    // Verify sender is the expected contact, or moved to its key by signed rotations
+    let mismatch = || {
+        Error::SessionError(format!(
             "Sender fingerprint mismatch! Expected {}, got {}",
             &expected_sender.public_key[..16],
             &sender_fp[..16]
-        )));
-    }
+        ))
+    };
+    let key_rotations = if expected_sender.public_key == sender_fp {
+        Vec::new()
+    } else if session.key_rotations.is_empty() {
+        return Err(mismatch());
+    } else {
+        match rotation::follow(
+            &session.key_rotations,
+            &expected_sender.public_key,
+            &sender_fp,
+        ) {
+            Ok(path) => path,
+            Err(e) => {
+                status!("{} Key rotation REJECTED: {}", "✗".bright_red().bold(), e);
+                return Err(mismatch());
+            }
+        }
+    };
 
     // Decode sender's public key for signature verification
     let sender_key_bytes = hex::decode(&sender_fp)
@@ -149,189 +214,602 @@ pub async fn run(
 
     // Verify signature
     if signing::verify_signature(&sender_key, &metadata_msg, &signature).is_err() {
-        println!();
-        println!("{} SIGNATURE VERIFICATION FAILED!", "✗".bright_red().bold());
-        println!("   Sender claims: {}...", &sender_fp[..16].bright_red());
+        status!();
+        status!("{} SIGNATURE VERIFICATION FAILED!", "✗".bright_red().bold());
+        status!("   Sender claims: {}...", &sender_fp[..16].bright_red());
         if let Some(expected) = expected_sender.public_key.get(..16) {
-            println!("   Expected from: {}...", expected.bright_yellow());
+            status!("   Expected from: {}...", expected.bright_yellow());
         }
-        println!();
-        println!("{} Transfer REJECTED.", "✗".bright_red().bold());
+        status!();
+        status!("{} Transfer REJECTED.", "✗".bright_red().bold());
 
         // Send error signal to sender
-        let _ = session.write_all(b"ERROR:signature_failed\n").await;
-        let _ = session.flush().await;
+        let _ = protocol::send_error(&mut session, "signature_failed").await;
 
         return Err(Error::InvalidInput("Signature verification failed".into()));
     }
 
+    let sender_ephemeral_hex = session
+        .sender_ephemeral_key
+        .clone()
+        .ok_or_else(|| Error::CryptoError("Sender ephemeral key not found".into()))?;
+
+    // The signed offer ties the metadata to us, this session and a validity window
+    let now = offer::now();
+    let accepted_offer = match check_offer(
+        session.offer.as_ref(),
+        &sender_key,
+        &my_fingerprint,
+        &sender_ephemeral_hex,
+        &filename,
+        filesize,
+        &file_hash_from_sender,
+        now,
+    ) {
+        Ok(offer) => offer,
+        Err(e) => {
+            status!();
+            status!("{} Transfer offer REJECTED: {}", "✗".bright_red().bold(), e);
+
+            let _ = protocol::send_error(&mut session, "offer_rejected").await;
+
+            return Err(e);
+        }
+    };
+
+    // An offer is accepted once, a replayed one is refused even while still valid
+    let mut seen_offers = offers::load_seen_offers()?;
+    let offer_id = accepted_offer.offer_id_hex();
+    if seen_offers.contains(&offer_id) {
+        status!();
+        status!(
+            "{} Transfer offer REJECTED: already used (replay)",
+            "✗".bright_red().bold()
+        );
+
+        let _ = protocol::send_error(&mut session, "offer_replayed").await;
+
+        return Err(Error::SessionError("Offer was already used".into()));
+    }
+    if let Err(e) = seen_offers.insert(
+        offer_id,
+        accepted_offer.expires_at + OFFER_CLOCK_SKEW_SECS,
+        now,
+    ) {
+        status!();
+        status!("{} Transfer offer REJECTED: {}", "✗".bright_red().bold(), e);
+
+        let _ = protocol::send_error(&mut session, "offer_rejected").await;
+
+        return Err(e);
+    }
+    offers::save_seen_offers(&seen_offers)?;
+
+    // The offer proved the sender holds the new key, the contact follows it from now on
+    if !key_rotations.is_empty() {
+        let mut contact_list = contacts::load_contacts()?;
+        contact_list.apply_rotation(&from, &key_rotations)?;
+        contacts::save_contacts(&contact_list)?;
+
+        status!();
+        status!(
+            "{} '{}' rotated their key, contact updated",
+            "✓".bright_green(),
+            from
+        );
+        status!("   Old key: {}...", &expected_sender.public_key[..16]);
+        status!("   New key: {}...", &sender_fp[..16]);
+    }
+
     //println!("{} Signature verified", "✓".bright_green());
     //println!(
     //    "   Expected hash: {}...",
     //    &file_hash_from_sender[..16].bright_cyan().dimmed()
     //);
 
-    // Derive encryption key from ephemeral keys
-    let sender_ephemeral_hex = session
-        .sender_ephemeral_key
-        .as_ref()
-        .ok_or_else(|| Error::CryptoError("Sender ephemeral key not found".into()))?;
+    // The name is chosen by the peer, it must stay a single file inside the download path
+    // (the signature and trailer still cover the name exactly as sent)
+    let safe_filename = match sanitize_filename(&filename) {
+        Ok(safe) => safe,
+        Err(e) => {
+            status!();
+            status!("{} Unusable filename from sender!", "✗".bright_red().bold());
 
-    //println!("{}", " Deriving encryption key...".bright_cyan());
-    let aes_key = key_exchange::perform_key_exchange(
-        ephemeral_keypair.secret,
-        sender_ephemeral_hex,
-        session.session_id(),
+            let _ = protocol::send_error(&mut session, "invalid_filename").await;
+
+            return Err(e);
+        }
+    };
+    if !safe_filename.changes.is_empty() {
+        status!();
+        status!(
+            "{} Unsafe filename from sender: {:?}",
+            "✗".bright_yellow().bold(),
+            filename
+        );
+        for change in &safe_filename.changes {
+            status!("   - {}", change);
+        }
+        status!("   Saving as: {}", safe_filename.name.bright_yellow());
+    }
+
+    // Multi-file transfers sign the manifest hash instead of a file hash
+    let manifest_hash = file_hash_from_sender.strip_prefix(MANIFEST_HASH_PREFIX);
+    let display_hash = manifest_hash.unwrap_or(&file_hash_from_sender);
+    let file_path = download_path.join(&safe_filename.name);
+    let part_path = download::part_path(&file_path);
+
+    // The sender may hash while sending, the real hash then arrives in a signed trailer
+    let trailer_mode = file_hash_from_sender == HASH_TRAILER_MARKER;
+
+    // Piped input has no size yet either, it is signed in the trailer together with the hash
+    let pipe_mode = file_hash_from_sender == PIPE_HASH_MARKER;
+
+    // Single files are signed by the Merkle root of their blocks
+    let merkle_root = file_hash_from_sender.strip_prefix(MERKLE_HASH_PREFIX);
+    if manifest_hash.is_none() && !trailer_mode && !pipe_mode && merkle_root.is_none() {
+        let _ = protocol::send_error(&mut session, "unsupported_hash").await;
+
+        return Err(Error::SessionError(
+            "Unsupported file hash format (outdated sender?)".to_string(),
+        ));
+    }
+    let display_hash = merkle_root.unwrap_or(display_hash);
+
+    // A directory tree cannot be written to a single output stream
+    if stdout && manifest_hash.is_some() {
+        let _ = protocol::send_error(&mut session, "stdout_single_file_only").await;
+
+        return Err(Error::InvalidInput(
+            "--stdout only accepts a single file, sender offered several".to_string(),
+        ));
+    }
+
+    // No need to download a single file that would be dropped anyway
+    if !stdout && manifest_hash.is_none() && policy == ConflictPolicy::Skip && file_path.exists() {
+        let _ = protocol::send_error(&mut session, "file_exists").await;
+
+        status!(
+            "{} {} already exists, transfer skipped",
+            "✗".bright_yellow().bold(),
+            file_path.display()
+        );
+        return Ok(());
+    }
+
+    // Pick up an earlier interrupted download of the same file from the same sender
+    let mut checkpoint = match (resume && !trailer_mode && !pipe_mode, manifest_hash) {
+        (true, None) => Some(
+            Checkpoint::load(&file_path)?
+                .filter(|c| c.matches(filesize, &file_hash_from_sender, &sender_fp))
+                .unwrap_or_else(|| {
+                    Checkpoint::new(
+                        session.session_id(),
+                        &safe_filename.name,
+                        filesize,
+                        &file_hash_from_sender,
+                        &sender_fp,
+                    )
+                }),
+        ),
+        _ => None,
+    };
+    // Blocks are verified whole, so resume from the last complete block
+    let requested_offset = match checkpoint.as_ref().map(|c| c.resume_offset(&file_path)) {
+        Some(offset) if offset < filesize => offset - offset % FILE_CHUNK_SIZE as u64,
+        Some(offset) => offset,
+        None => 0,
+    };
+
+    // Older relays pair a single connection per session
+    let wanted_streams = streams.min(session.max_streams);
+    if wanted_streams < streams {
+        status!(
+            "{} Relay does not support {} streams, using {}",
+            "✗".bright_yellow().bold(),
+            streams,
+            wanted_streams
+        );
+    }
+
+    // Vouch for our ephemeral key with the identity key, bound to this session and
+    // to the handshakes we offer
+    let mut hello = Hello::new(requested_offset, wanted_streams, String::new());
+    hello.key_signature = key_exchange::sign_ephemeral_key(
+        &identity,
+        &protocol::ephemeral_key_message(
+            TransferRole::Receiver,
+            session.session_id(),
+            &sender_fp,
+            &my_fingerprint,
+            &receiver_ephemeral_hex,
+            &hello.handshakes,
+        ),
     )?;
+
+    // Negotiate transfer parameters before any data flows
+    protocol::send_hello(&mut session, &hello).await?;
+    let accept = protocol::recv_accept(&mut session).await?;
+
+    // The sender picks the session setup: a Noise handshake, or the signed ephemeral keys
+    let noise_handshake = match accept.handshake.as_deref() {
+        None => false,
+        Some(HANDSHAKE_NOISE_IK) => true,
+        Some(handshake) => {
+            return Err(Error::SessionError(format!(
+                "Sender chose unsupported handshake: {}",
+                handshake
+            )));
+        }
+    };
+
+    // The sender's ephemeral key came through the relay, only use it if the
+    // sender's identity key vouches for it
+    let sender_key_message = protocol::ephemeral_key_message(
+        TransferRole::Sender,
+        session.session_id(),
+        &sender_fp,
+        &my_fingerprint,
+        &sender_ephemeral_hex,
+        accept.handshake.as_slice(),
+    );
+    if !noise_handshake
+        && let Err(e) = key_exchange::verify_ephemeral_key(
+            &sender_key,
+            &sender_key_message,
+            &accept.key_signature,
+        )
+    {
+        status!();
+        status!(
+            "{} SENDER KEY VERIFICATION FAILED!",
+            "✗".bright_red().bold()
+        );
+        status!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+        let _ = protocol::send_error(&mut session, "key_signature_failed").await;
+
+        return Err(e);
+    }
+
+    // Cipher suites are negotiated in the Noise handshake, the legacy setup is fixed
+    if let Some(cipher) = cipher
+        && !noise_handshake
+        && cipher != CipherSuite::Aes256Gcm
+    {
+        status!();
+        status!(
+            "{} Sender cannot use the {} cipher suite (--cipher)",
+            "✗".bright_red().bold(),
+            cipher
+        );
+        status!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+        let _ = protocol::send_error(&mut session, "cipher_unsupported").await;
+
+        return Err(Error::CryptoError(format!(
+            "Sender only supports {}, refusing to continue without {}",
+            CipherSuite::Aes256Gcm,
+            cipher
+        )));
+    }
+
+    // Derive session keys from the handshake, or from the ephemeral keys
+    //println!("{}", " Deriving encryption key...".bright_cyan());
+    let keys = if noise_handshake {
+        let prologue = protocol::noise_prologue(session.session_id(), &hello, &accept)?;
+        noise_initiate(&mut session, &identity, &sender_key, &prologue, pq, cipher).await?
+    } else {
+        // Senders without Noise predate the key schedule as well
+        SessionKeys::legacy(key_exchange::perform_key_exchange(
+            ephemeral_keypair.secret,
+            &sender_ephemeral_hex,
+            session.session_id(),
+        )?)
+    };
+    // From here on DONE and ERROR are authenticated (not with older senders)
+    if let Some(control_key) = keys.control.clone() {
+        session.set_control_key(control_key);
+    }
+
+    // Asked for a hybrid exchange: never fall back to a classical-only session
+    if pq && !keys.post_quantum {
+        status!();
+        status!(
+            "{} Sender does not support the hybrid post-quantum exchange (--pq)",
+            "✗".bright_red().bold()
+        );
+        status!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+        let _ = protocol::send_error(&mut session, "pq_unsupported").await;
+
+        return Err(Error::CryptoError(
+            "Sender is classical-only, refusing to continue without ML-KEM".to_string(),
+        ));
+    }
     //println!("{}  Encryption key derived", "✓".bright_green());
 
+    if accept.streams == 0 || accept.streams > wanted_streams {
+        return Err(Error::SessionError(format!(
+            "Sender tried to open {} streams, we offered {}",
+            accept.streams, wanted_streams
+        )));
+    }
+
+    if accept.resume_offset > requested_offset {
+        return Err(Error::SessionError(format!(
+            "Sender tried to resume at {} bytes, only {} bytes are on disk",
+            accept.resume_offset, requested_offset
+        )));
+    }
+
+    // We advertised the codecs we decode, the sender may only pick one of those
+    let compressed = match accept.compression.as_deref() {
+        None => false,
+        Some(COMPRESSION_ZSTD) => true,
+        Some(codec) => {
+            return Err(Error::SessionError(format!(
+                "Sender chose unsupported compression: {}",
+                codec
+            )));
+        }
+    };
+
     //println!("{} Incoming file transfer", "✓".bright_green());
-    println!();
-    println!(
+    status!();
+    status!(
         " File: {} | Hash {}...",
-        filename.bright_yellow(),
-        &file_hash_from_sender[..16].bright_cyan().dimmed()
+        safe_filename.name.bright_yellow(),
+        hash::display_hash(display_hash).bright_cyan().dimmed()
     );
-    println!(
-        " Size: {} bytes ({:.2} MB)",
-        filesize,
-        filesize as f64 / (1024.0 * 1024.0)
-    );
-    println!(" From:   {}", from.bright_white().bold());
-    println!();
-    println!(
+    if pipe_mode {
+        status!(" Size: unknown (sender is streaming from stdin)");
+    } else {
+        status!(
+            " Size: {} bytes ({:.2} MB)",
+            filesize,
+            filesize as f64 / (1024.0 * 1024.0)
+        );
+    }
+    status!(" From:   {}", from.bright_white().bold());
+    if accept.resume_offset > 0 {
+        status!(
+            " Resume: {} bytes already received",
+            accept.resume_offset.to_string().bright_cyan()
+        );
+    }
+    if compressed {
+        status!(" Compression: {}", COMPRESSION_ZSTD.bright_cyan());
+    }
+    if accept.streams > 1 {
+        status!(" Streams: {}", accept.streams.to_string().bright_cyan());
+    }
+    if noise_handshake {
+        status!(" Handshake: {}", NOISE_PARAMS.bright_cyan());
+    }
+    if keys.post_quantum {
+        status!(
+            " Key exchange: X25519 + {} (hybrid)",
+            KEM_ALGORITHM.bright_cyan()
+        );
+    }
+    status!(" Cipher: {}", keys.suite.as_str().bright_cyan());
+    if let Some(code) = keys.confirmation_code() {
+        status!(" Key check: {}", code.bright_cyan());
+    }
+    status!();
+    status!(
         "{} Receiving and decrypting file...",
         "⇙".bright_magenta().bold()
     );
 
     // Receive encrypted file data with progress bar
-    let file_path = download_path.join(&filename);
-    let file_writer = File::create(&file_path).await?;
-    let mut file_writer = BufWriter::with_capacity(BUFFER_SIZE, file_writer);
-    //let file = OpenOptions::new()
-    //    .read(true)
-    //    .write(true)
-    //    .create(true)
-    //    .open(&file_path)?;
-    //file.set_len(filesize)?;
-    //let mut mmap = unsafe { MmapMut::map_mut(&file)? };
-
+    // Compressed transfers also show how many bytes actually went over the wire
     let pb = ProgressBar::new(filesize);
     pb.set_style(
         ProgressStyle::default_bar()
-            .template(PROGRESS_BAR_TEMPLATE)
+            .template(match (pipe_mode, compressed) {
+                (true, _) => PROGRESS_SPINNER_TEMPLATE,
+                (false, true) => PROGRESS_BAR_WIRE_TEMPLATE,
+                (false, false) => PROGRESS_BAR_TEMPLATE,
+            })
             .unwrap()
             .progress_chars(PROGRESS_BAR_CHARS)
             .tick_chars(DEFAULT_SPINNER_STYLE),
     );
 
-    let mut total_received = 0u64;
-    //let mut offset = 0;
+    let mut progress = Progress {
+        pb,
+        received: accept.resume_offset,
+        total: filesize,
+        wire: 0,
+        compressed,
+    };
+    progress.pb.set_position(progress.received);
 
-    // Read encrypted chunks: [4B size][encrypted data]
-    while total_received < filesize {
-        // Read 4-byte size prefix
-        let mut size_buffer = [0u8; 4];
-        if let Err(_e) = session.read_exact(&mut size_buffer).await {
-            println!();
-            println!(
-                "{} Connection closed early! Received {}/{} bytes ({:.1}%)",
-                "✗".bright_red().bold(),
-                total_received,
-                filesize,
-                (total_received as f64 / filesize as f64) * 100.0
-            );
+    // Join the sender's extra data streams, frames are striped across all of them
+    relay_client
+        .open_streams(&mut session, accept.streams)
+        .await?;
 
-            // Clean up partial file immediately
-            //drop(mmap);
-            //std::fs::remove_file(&file_path)?;
-            drop(file_writer);
-            tokio::fs::remove_file(&file_path).await?;
-            println!("{} Partial file deleted", "✓".bright_red());
+    // Chunks must arrive in order and end with the sender's final chunk
+    let mut stream =
+        StreamDecryptor::new(keys.suite, &keys.sender_to_receiver, session.session_id());
 
-            return Err(Error::SessionError(
-                "Transfer interrupted - connection closed before chunk size read".to_string(),
-            ));
-        }
+    if let Some(manifest_hash) = manifest_hash {
+        receive_tree(
+            &mut session,
+            &mut stream,
+            &download_path,
+            manifest_hash,
+            &mut progress,
+            policy,
+        )
+        .await?;
 
-        let chunk_size = u32::from_be_bytes(size_buffer) as usize;
+        // Send completion confirmation to sender
+        protocol::send_control(&mut session, DONE_MESSAGE).await?;
 
-        // Read encrypted chunk
-        let mut encrypted_buffer = vec![0u8; chunk_size];
-        if let Err(_e) = session.read_exact(&mut encrypted_buffer).await {
-            println!();
-            println!(
-                "{} Connection closed early! Received {}/{} bytes ({:.1}%)",
-                "✗".bright_red().bold(),
-                total_received,
-                filesize,
-                (total_received as f64 / filesize as f64) * 100.0
-            );
+        progress.print_wire_summary(0);
+        status!();
+        status!("{} Files received successfully!", "✓".bright_green().bold());
 
-            // Clean up partial file immediately
-            //drop(mmap);
-            //std::fs::remove_file(&file_path)?;
-            drop(file_writer);
-            tokio::fs::remove_file(&file_path).await?;
-            println!("{} Partial file deleted", "✓".bright_red());
+        return Ok(());
+    }
 
-            return Err(Error::SessionError(
-                "Transfer interrupted - connection closed while reading chunk".to_string(),
-            ));
+    // Leaves come first and must add up to the signed root, then each block is checked
+    // against its leaf as it arrives
+    let mut integrity = match merkle_root {
+        Some(root) => {
+            let encrypted_leaves = session.read_frame().await?;
+            let (leaf_bytes, _last) = stream.decrypt_next(&encrypted_leaves)?;
+            let tree = MerkleTree::from_bytes(&leaf_bytes)?;
+
+            if tree.root_hex() != root || tree.len() as u64 != merkle::block_count(filesize) {
+                status!();
+                status!("{} MERKLE TREE CHECK FAILED!", "✗".bright_red().bold());
+
+                let _ = protocol::send_error(&mut session, "merkle_mismatch").await;
+
+                return Err(Error::SessionError(
+                    "Merkle leaves do not match the signed root".to_string(),
+                ));
+            }
+
+            Integrity::Leaves(tree)
         }
+        None => Integrity::Trailer(MerkleTree::new()),
+    };
 
-        // Decrypt the chunk
-        let plaintext = encryption::decrypt_chunk(&aes_key, &encrypted_buffer)?;
+    // Piped input and stdout output are written as they come, without resume support
+    let known_size = (!pipe_mode).then_some(filesize);
+    let trailer = if stdout {
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, tokio::io::stdout());
+        receive_stream(
+            &mut session,
+            &mut stream,
+            &mut writer,
+            known_size,
+            &mut progress,
+            &mut integrity,
+        )
+        .await?
+    } else if pipe_mode {
+        let mut writer = BufWriter::with_capacity(BUFFER_SIZE, File::create(&part_path).await?);
+        let received = receive_stream(
+            &mut session,
+            &mut stream,
+            &mut writer,
+            known_size,
+            &mut progress,
+            &mut integrity,
+        )
+        .await;
 
-        // Write decrypted data to file
-        //let len = plaintext.len();
-        //mmap[offset..offset + len].copy_from_slice(&plaintext);
-        file_writer.write_all(&plaintext).await?;
-        total_received += plaintext.len() as u64;
-        //offset += len;
-        //total_received += len as u64;
+        match received {
+            Ok(trailer) => trailer,
+            Err(e) => {
+                drop(writer);
+                tokio::fs::remove_file(&part_path).await?;
+                status!("{} Partial file deleted", "✓".bright_red());
+                return Err(e);
+            }
+        }
+    } else {
+        receive_file(
+            &mut session,
+            &mut stream,
+            &file_path,
+            filesize,
+            accept.resume_offset,
+            &mut progress,
+            checkpoint.as_mut(),
+            &mut integrity,
+        )
+        .await?;
+        finish_stream(&mut session, &mut stream).await?
+    };
+    let computed_hash = integrity.finish();
+    progress.pb.finish_with_message("Download complete!");
+    let total_received = progress.received;
+
+    // Verify file integrity against the SHA256 hash computed while receiving
+    status!();
+    status!();
+    status!("{}", "Verifying file hash...".yellow());
 
-        pb.set_position(total_received);
+    // The partial download is complete (or unusable), the checkpoint is no longer needed
+    if !stdout {
+        Checkpoint::remove(&file_path)?;
     }
 
-    //mmap.flush()?;
-    file_writer.flush().await?;
-    pb.finish_with_message("Download complete!");
+    // Expected hash comes from the signed metadata, or from the signed trailer
+    // (which for piped input also covers the number of bytes sent)
+    let expected_hash = if trailer_mode || pipe_mode {
+        let signed_size = if pipe_mode { total_received } else { filesize };
+        match verify_trailer(
+            &trailer,
+            &sender_key,
+            session.session_id(),
+            &filename,
+            signed_size,
+        ) {
+            Ok(hash) => hash,
+            Err(e) => {
+                status!();
+                status!("{} HASH TRAILER REJECTED!", "✗".bright_red().bold());
+
+                discard_output(&part_path, &safe_filename.name, stdout).await?;
 
-    // Verify file integrity by computing SHA256 hash
-    println!();
-    println!();
-    println!("{}", "Verifying file hash...".yellow());
+                let _ = protocol::send_error(&mut session, "trailer_invalid").await;
 
-    // Use the extracted file_io utility
-    let computed_hash = hash::compute_file_hash(&file_path).await?;
+                return Err(e);
+            }
+        }
+    } else if trailer.is_empty() {
+        file_hash_from_sender.clone()
+    } else {
+        status!();
+        status!("{} UNEXPECTED DATA FROM SENDER!", "✗".bright_red().bold());
+
+        discard_output(&part_path, &safe_filename.name, stdout).await?;
+
+        let _ = protocol::send_error(&mut session, "unexpected_data").await;
+
+        return Err(Error::SessionError(
+            "Sender sent more data than announced".to_string(),
+        ));
+    };
 
     // Compare with expected hash from signature
-    if computed_hash != file_hash_from_sender {
-        println!();
-        println!("{} FILE INTEGRITY CHECK FAILED!", "✗".bright_red().bold());
-        println!(
+    if computed_hash != expected_hash {
+        status!();
+        status!("{} FILE INTEGRITY CHECK FAILED!", "✗".bright_red().bold());
+        status!(
             "   Expected: {}...",
-            &file_hash_from_sender[..16].bright_yellow()
+            hash::display_hash(&expected_hash).bright_yellow()
+        );
+        status!(
+            "   Got:      {}...",
+            hash::display_hash(computed_hash.trim_start_matches(MERKLE_HASH_PREFIX)).bright_red()
         );
-        println!("   Got:      {}...", &computed_hash[..16].bright_red());
-        println!();
+        status!();
 
         // Delete corrupted file
         //std::fs::remove_file(&file_path)?;
-        tokio::fs::remove_file(&file_path).await?;
-        println!("{} Corrupted file deleted: {}", "✓".bright_red(), filename);
+        discard_output(&part_path, &safe_filename.name, stdout).await?;
 
         // Send error signal to sender
-        let _ = session.write_all(b"ERROR:hash_mismatch\n").await;
-        let _ = session.flush().await;
+        let _ = protocol::send_error(&mut session, "hash_mismatch").await;
 
         return Err(Error::FileError("File integrity check failed".to_string()));
     }
 
-    println!(
+    status!(
         "  File hash verified | Hash {}...",
-        &computed_hash[..16].bright_cyan().dimmed()
+        hash::display_hash(computed_hash.trim_start_matches(MERKLE_HASH_PREFIX))
+            .bright_cyan()
+            .dimmed()
     );
     //println!(
     //    "   Hash: {}...",
@@ -340,14 +818,19 @@ pub async fn run(
     //         .dimmed()
     //);
 
+    // Only a verified file gets its final name
+    if !stdout {
+        place_download(&part_path, &file_path, policy)?;
+    }
+
     // Send completion confirmation to sender
     //println!();
     //println!(" Sending completion signal to sender...");
-    session.write_all(DONE_SIGNAL).await?;
-    session.flush().await?;
+    protocol::send_control(&mut session, DONE_MESSAGE).await?;
 
-    println!();
-    println!("{} File received successfully!", "✓".bright_green().bold());
+    progress.print_wire_summary(accept.resume_offset);
+    status!();
+    status!("{} File received successfully!", "✓".bright_green().bold());
 
     //println!("   Saved to: {}", file_path.display());
     //println!(
@@ -356,8 +839,8 @@ pub async fn run(
     //    total_received as f64 / (1024.0 * 1024.0)
     //);
 
-    if total_received < filesize {
-        println!(
+    if !pipe_mode && total_received < filesize {
+        status!(
             "   {} Expected {} bytes, got {} bytes",
             "✗".bright_yellow().bold(),
             filesize,
@@ -367,3 +850,717 @@ pub async fn run(
 
     Ok(())
 }
+
+/// Verify the sender's signed offer and check it matches the session metadata
+#[allow(clippy::too_many_arguments)]
+fn check_offer(
+    signed: Option<&SignedOffer>,
+    sender_key: &VerifyingKey,
+    my_fingerprint: &str,
+    sender_ephemeral_hex: &str,
+    filename: &str,
+    filesize: u64,
+    file_hash: &str,
+    now: u64,
+) -> Result<Offer> {
+    let signed = signed.ok_or_else(|| {
+        Error::SessionError("No signed offer (outdated sender or relay)".to_string())
+    })?;
+    let offer = signed.verify(sender_key)?;
+
+    if offer.sender_fp != hex::encode(sender_key.to_bytes())
+        || offer.filename != filename
+        || offer.file_size != filesize
+        || offer.file_hash != file_hash
+    {
+        return Err(Error::SessionError(
+            "Offer does not match the session metadata".to_string(),
+        ));
+    }
+
+    offer.validate(my_fingerprint, sender_ephemeral_hex, now)?;
+    Ok(offer)
+}
+
+/// Run the Noise handshake towards the expected sender and return the session keys
+///
+/// Only the sender's identity key can answer it, and its reply carries the key schedule
+/// and cipher suite it picked from the ones we offer and, for a hybrid exchange, the
+/// ciphertext for our ML-KEM key (which we ask for with `pq`). The session keys come from
+/// the finished handshake (and the KEM secret).
+async fn noise_initiate(
+    session: &mut TransferSession,
+    identity: &Identity,
+    sender_key: &VerifyingKey,
+    prologue: &[u8],
+    pq: bool,
+    cipher: Option<CipherSuite>,
+) -> Result<SessionKeys> {
+    let mut handshake = NoiseHandshake::initiator(identity, sender_key, prologue)?;
+
+    let kem_keypair = key_exchange::KemKeyPair::generate();
+    let init = NoiseInit::new(
+        kem_keypair.public_key_hex(),
+        pq,
+        &CipherSuite::preferred(cipher),
+    );
+    let first = handshake.write_message(&init.to_bytes()?)?;
+    protocol::send_handshake(session, &first).await?;
+
+    let reply = protocol::recv_handshake(session).await?;
+    let payload = match handshake.read_message(&reply) {
+        Ok(payload) if handshake.is_finished() => payload,
+        result => {
+            status!();
+            status!("{} NOISE HANDSHAKE FAILED!", "✗".bright_red().bold());
+            status!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+            let _ = protocol::send_error(session, "handshake_failed").await;
+
+            return Err(result.err().unwrap_or_else(|| {
+                Error::CryptoError("Noise handshake did not complete".to_string())
+            }));
+        }
+    };
+
+    let reply = NoiseReply::from_bytes(&payload)?;
+    if !init.supports_key_schedule(reply.key_schedule) {
+        return Err(Error::SessionError(format!(
+            "Sender chose unsupported key schedule: v{}",
+            reply.key_schedule
+        )));
+    }
+    if !init.cipher_suites().contains(&reply.cipher_suite) {
+        return Err(Error::SessionError(format!(
+            "Sender chose unsupported cipher suite: {}",
+            reply.cipher_suite
+        )));
+    }
+
+    let kem_secret = match &reply.kem_ciphertext {
+        Some(ciphertext) => Some(kem_keypair.decapsulate(ciphertext)?),
+        None => None,
+    };
+
+    let split_secret = handshake.split_secret()?;
+    noise::derive_session_keys(
+        &split_secret,
+        kem_secret.as_ref(),
+        handshake.handshake_hash(),
+        reply.key_schedule,
+        reply.cipher_suite,
+    )
+}
+
+/// Move a verified part file to its final name, applying the conflict policy
+fn place_download(part_path: &Path, file_path: &Path, policy: ConflictPolicy) -> Result<()> {
+    match download::resolve_conflict(file_path, policy)? {
+        Some(target) => {
+            download::commit(part_path, &target)?;
+            if target != file_path {
+                status!(
+                    "  {} {} exists, saved as {}",
+                    "↻".bright_cyan(),
+                    file_path.display(),
+                    target.display().to_string().bright_yellow()
+                );
+            }
+        }
+        None => {
+            std::fs::remove_file(part_path)?;
+            status!(
+                "  {} {} exists, skipped",
+                "✗".bright_yellow().bold(),
+                file_path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Delete a file that failed verification, or warn that stdout already got it
+async fn discard_output(file_path: &Path, filename: &str, stdout: bool) -> Result<()> {
+    if stdout {
+        status!(
+            "{} Data already written to stdout is UNVERIFIED, discard it",
+            "✗".bright_red().bold()
+        );
+    } else {
+        tokio::fs::remove_file(file_path).await?;
+        status!("{} Corrupted file deleted: {}", "✓".bright_red(), filename);
+    }
+    Ok(())
+}
+
+/// How received file data is checked
+enum Integrity {
+    /// Flat SHA256 over the whole file (manifest entries)
+    Sha256(Sha256),
+    /// Signed Merkle leaves, every block is checked as it arrives
+    Leaves(MerkleTree),
+    /// Leaves collected while receiving, the root follows in a signed trailer
+    Trailer(MerkleTree),
+}
+
+impl Integrity {
+    /// Check (or record) block `index` of the file
+    fn update(&mut self, index: u64, block: &[u8]) -> bool {
+        match self {
+            Integrity::Sha256(hasher) => {
+                hasher.update(block);
+                true
+            }
+            Integrity::Leaves(tree) => tree.verify_block(index, block),
+            Integrity::Trailer(tree) => {
+                tree.push(block);
+                true
+            }
+        }
+    }
+
+    /// Digest in the form the sender signed it
+    fn finish(self) -> String {
+        match self {
+            Integrity::Sha256(hasher) => hex::encode(hasher.finalize()),
+            Integrity::Leaves(tree) | Integrity::Trailer(tree) => {
+                format!("{}{}", MERKLE_HASH_PREFIX, tree.root_hex())
+            }
+        }
+    }
+}
+
+/// Progress across all files of one transfer
+struct Progress {
+    pb: ProgressBar,
+    received: u64,
+    total: u64,
+    /// Encrypted bytes read from the socket in this session
+    wire: u64,
+    /// Data chunks are compressed and carry a compression flag
+    compressed: bool,
+}
+
+impl Progress {
+    fn advance(&mut self, raw: usize, wire: usize) {
+        self.received += raw as u64;
+        self.wire += wire as u64;
+        self.pb.set_position(self.received);
+
+        if self.compressed {
+            self.pb.set_prefix(format!(
+                "raw {} / wire {}",
+                HumanBytes(self.received),
+                HumanBytes(self.wire)
+            ));
+        }
+    }
+
+    /// Show raw vs wire bytes for this session (skipping a resumed prefix)
+    fn print_wire_summary(&self, resumed: u64) {
+        if self.compressed {
+            status!(
+                " Compressed: {} raw, {} on the wire",
+                HumanBytes(self.received - resumed),
+                HumanBytes(self.wire)
+            );
+        }
+    }
+}
+
+/// Receive a multi-file transfer: verify the manifest, then rebuild the tree under `download_path`
+async fn receive_tree(
+    session: &mut TransferSession,
+    stream: &mut StreamDecryptor,
+    download_path: &Path,
+    manifest_hash: &str,
+    progress: &mut Progress,
+    policy: ConflictPolicy,
+) -> Result<()> {
+    // The manifest is the first frame and must match the signed hash
+    let encrypted_manifest = session.read_frame().await?;
+    let (manifest_bytes, _last) = stream.decrypt_next(&encrypted_manifest)?;
+
+    if Manifest::digest(&manifest_bytes) != manifest_hash {
+        status!();
+        status!(
+            "{} MANIFEST INTEGRITY CHECK FAILED!",
+            "✗".bright_red().bold()
+        );
+
+        let _ = protocol::send_error(session, "manifest_mismatch").await;
+
+        return Err(Error::SessionError(
+            "Manifest integrity check failed".to_string(),
+        ));
+    }
+
+    let manifest = Manifest::from_bytes(&manifest_bytes)?;
+    if manifest.total_size() != progress.total {
+        return Err(Error::SessionError(format!(
+            "Manifest size mismatch! Expected {} bytes, manifest lists {}",
+            progress.total,
+            manifest.total_size()
+        )));
+    }
+
+    // Resolve every path before writing anything, names are sanitized like a single file
+    let mut targets = Vec::with_capacity(manifest.entries.len());
+    let mut seen = HashSet::new();
+    for entry in &manifest.entries {
+        let resolved = match resolve_entry_path(download_path, &entry.path) {
+            Ok(resolved) if seen.insert(resolved.path.clone()) => resolved,
+            result => {
+                status!();
+                status!("{} Unusable path from sender!", "✗".bright_red().bold());
+
+                let _ = protocol::send_error(session, "invalid_filename").await;
+
+                return Err(result.err().unwrap_or_else(|| {
+                    Error::SessionError(format!(
+                        "Manifest paths collide once sanitized: {:?}",
+                        entry.path
+                    ))
+                }));
+            }
+        };
+
+        if !resolved.changes.is_empty() {
+            status!();
+            status!(
+                "{} Unsafe path from sender: {:?}",
+                "✗".bright_yellow().bold(),
+                entry.path
+            );
+            for change in &resolved.changes {
+                status!("   - {}", change);
+            }
+            let saved = resolved
+                .path
+                .strip_prefix(download_path)
+                .unwrap_or(&resolved.path);
+            status!(
+                "   Saving as: {}",
+                saved.display().to_string().bright_yellow()
+            );
+        }
+        targets.push(resolved.path);
+    }
+
+    let mut hashes = Vec::with_capacity(targets.len());
+    for (entry, file_path) in manifest.entries.iter().zip(&targets) {
+        if let Some(parent) = file_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let mut integrity = Integrity::Sha256(Sha256::new());
+        receive_file(
+            session,
+            stream,
+            file_path,
+            entry.size,
+            0,
+            progress,
+            None,
+            &mut integrity,
+        )
+        .await?;
+        hashes.push(integrity.finish());
+    }
+
+    if !finish_stream(session, stream).await?.is_empty() {
+        return Err(Error::SessionError(
+            "Sender sent more data than announced".to_string(),
+        ));
+    }
+
+    progress.pb.finish_with_message("Download complete!");
+
+    // Verify every entry against the signed manifest
+    status!();
+    status!();
+    status!("{}", "Verifying file hashes...".yellow());
+
+    let mut failed = Vec::new();
+    for ((entry, file_path), computed_hash) in manifest.entries.iter().zip(&targets).zip(hashes) {
+        if computed_hash == entry.hash {
+            status!("  {} {}", "✓".bright_green(), entry.path);
+            place_download(&download::part_path(file_path), file_path, policy)?;
+        } else {
+            status!("  {} {}", "✗".bright_red().bold(), entry.path.bright_red());
+
+            // Delete corrupted file
+            tokio::fs::remove_file(download::part_path(file_path)).await?;
+            failed.push(entry.path.clone());
+        }
+    }
+
+    if !failed.is_empty() {
+        status!();
+        status!("{} FILE INTEGRITY CHECK FAILED!", "✗".bright_red().bold());
+        status!(
+            "{} Corrupted files deleted: {}",
+            "✓".bright_red(),
+            failed.len()
+        );
+
+        // Send error signal to sender
+        let _ = protocol::send_error(session, "hash_mismatch").await;
+
+        return Err(Error::FileError(format!(
+            "File integrity check failed for {} of {} files",
+            failed.len(),
+            manifest.entries.len()
+        )));
+    }
+
+    status!(
+        "  {} files verified under {}",
+        manifest.entries.len(),
+        download_path.display()
+    );
+
+    Ok(())
+}
+
+/// Receive and decrypt `file_path` from `offset` up to `size` bytes into its part file
+///
+/// Every chunk is one `FILE_CHUNK_SIZE` block and goes through `integrity` as it is
+/// written. A resumed prefix is first checked against the signed Merkle leaves.
+/// Without a checkpoint the partial file is deleted if the connection drops. With a
+/// checkpoint it is kept and the checkpoint records how far we got.
+#[allow(clippy::too_many_arguments)]
+async fn receive_file(
+    session: &mut TransferSession,
+    stream: &mut StreamDecryptor,
+    file_path: &Path,
+    size: u64,
+    offset: u64,
+    progress: &mut Progress,
+    mut checkpoint: Option<&mut Checkpoint>,
+    integrity: &mut Integrity,
+) -> Result<()> {
+    // Data goes to the part file, the caller moves it into place once verified
+    let part_path = download::part_path(file_path);
+
+    if offset > 0 {
+        // Data on disk is only trusted if it matches the signed tree
+        let intact = match integrity {
+            Integrity::Leaves(tree) => tree.verify_file_prefix(&part_path, offset).await?,
+            _ => false,
+        };
+
+        if !intact {
+            status!();
+            status!(
+                "{} Partial download does not match the signed file!",
+                "✗".bright_red().bold()
+            );
+
+            tokio::fs::remove_file(&part_path).await?;
+            Checkpoint::remove(file_path)?;
+            status!("{} Partial file deleted", "✓".bright_red());
+
+            let _ = protocol::send_error(session, "resume_mismatch").await;
+
+            return Err(Error::FileError(
+                "Partial download is corrupt, run listen again to start over".to_string(),
+            ));
+        }
+    }
+
+    let file_writer = if offset > 0 {
+        // Drop anything past the verified offset and append from there
+        let file = OpenOptions::new().write(true).open(&part_path)?;
+        file.set_len(offset)?;
+        let mut file = File::from_std(file);
+        file.seek(SeekFrom::End(0)).await?;
+        file
+    } else {
+        File::create(&part_path).await?
+    };
+    let mut file_writer = BufWriter::with_capacity(BUFFER_SIZE, file_writer);
+    //let file = OpenOptions::new()
+    //    .read(true)
+    //    .write(true)
+    //    .create(true)
+    //    .open(&file_path)?;
+    //file.set_len(filesize)?;
+    //let mut mmap = unsafe { MmapMut::map_mut(&file)? };
+
+    let mut file_received = offset;
+    let mut chunks = 0u64;
+    //let mut offset = 0;
+
+    // Read encrypted chunks: [4B size][encrypted data]
+    while file_received < size {
+        let encrypted_buffer = match session.read_frame().await {
+            Ok(frame) => frame,
+            Err(_e) => {
+                status!();
+                status!(
+                    "{} Connection closed early! Received {}/{} bytes ({:.1}%)",
+                    "✗".bright_red().bold(),
+                    progress.received,
+                    progress.total,
+                    (progress.received as f64 / progress.total as f64) * 100.0
+                );
+
+                if let Some(checkpoint) = checkpoint {
+                    // Keep what we have so the next listen can resume
+                    file_writer.flush().await?;
+                    checkpoint.offset = file_received;
+                    checkpoint.save(file_path)?;
+                    status!(
+                        "{} Partial file kept, run {} again to resume",
+                        "↻".bright_cyan(),
+                        "rs listen --resume".bright_cyan()
+                    );
+                } else {
+                    // Clean up partial file immediately
+                    //drop(mmap);
+                    //std::fs::remove_file(&file_path)?;
+                    drop(file_writer);
+                    tokio::fs::remove_file(&part_path).await?;
+                    status!("{} Partial file deleted", "✓".bright_red());
+                }
+
+                return Err(Error::SessionError(
+                    "Transfer interrupted - connection closed early".to_string(),
+                ));
+            }
+        };
+
+        // Decrypt the chunk and check its position in the stream
+        let (plaintext, last) = match stream.decrypt_next(&encrypted_buffer) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                status!();
+                status!(
+                    "{} CHUNK AUTHENTICATION FAILED at chunk {}!",
+                    "✗".bright_red().bold(),
+                    stream.index()
+                );
+
+                // Everything before this chunk was authenticated, keep it for resume
+                if let Some(checkpoint) = checkpoint {
+                    file_writer.flush().await?;
+                    checkpoint.offset = file_received;
+                    checkpoint.save(file_path)?;
+                } else {
+                    drop(file_writer);
+                    tokio::fs::remove_file(&part_path).await?;
+                }
+
+                let _ = protocol::send_error(session, "chunk_auth_failed").await;
+
+                return Err(e);
+            }
+        };
+
+        // Undo compression before anything is checked or written
+        let plaintext = if progress.compressed && !last {
+            match compression::decompress_chunk(&plaintext, FILE_CHUNK_SIZE) {
+                Ok(plaintext) => plaintext,
+                Err(e) => {
+                    drop(file_writer);
+                    tokio::fs::remove_file(&part_path).await?;
+                    return Err(e);
+                }
+            }
+        } else {
+            plaintext
+        };
+
+        // A chunk may never spill over into the next file, and the stream may not end early
+        if last || file_received + plaintext.len() as u64 > size {
+            drop(file_writer);
+            tokio::fs::remove_file(&part_path).await?;
+            return Err(Error::SessionError(format!(
+                "Stream does not match the announced size of {}",
+                file_path.display()
+            )));
+        }
+
+        // Check the block before it touches the disk
+        let block_index = file_received / FILE_CHUNK_SIZE as u64;
+        if !integrity.update(block_index, &plaintext) {
+            status!();
+            status!(
+                "{} BLOCK {} FAILED THE MERKLE CHECK!",
+                "✗".bright_red().bold(),
+                block_index
+            );
+
+            // Earlier blocks were verified, keep them for resume
+            if let Some(checkpoint) = checkpoint {
+                file_writer.flush().await?;
+                checkpoint.offset = file_received;
+                checkpoint.save(file_path)?;
+            } else {
+                drop(file_writer);
+                tokio::fs::remove_file(&part_path).await?;
+            }
+
+            let _ = protocol::send_error(session, "block_mismatch").await;
+
+            return Err(Error::FileError(format!(
+                "Block {} does not match the signed Merkle tree",
+                block_index
+            )));
+        }
+
+        // Write decrypted data to file
+        //let len = plaintext.len();
+        //mmap[offset..offset + len].copy_from_slice(&plaintext);
+        file_writer.write_all(&plaintext).await?;
+        file_received += plaintext.len() as u64;
+        progress.advance(plaintext.len(), encrypted_buffer.len());
+        //offset += len;
+        //total_received += len as u64;
+
+        // Periodically persist how far we got
+        chunks += 1;
+        if let Some(checkpoint) = checkpoint.as_deref_mut()
+            && chunks.is_multiple_of(CHECKPOINT_INTERVAL_CHUNKS)
+        {
+            file_writer.flush().await?;
+            checkpoint.offset = file_received;
+            checkpoint.save(file_path)?;
+        }
+    }
+
+    //mmap.flush()?;
+    file_writer.flush().await?;
+
+    Ok(())
+}
+
+/// Receive and decrypt chunks into `writer` until the sender's final chunk
+///
+/// Used when the data does not go to a resumable file: piped input of unknown length,
+/// or output to stdout. With a known `size` the stream must match it exactly. Returns
+/// the contents of the final chunk (empty, or a hash trailer).
+async fn receive_stream<W: AsyncWrite + Unpin>(
+    session: &mut TransferSession,
+    stream: &mut StreamDecryptor,
+    writer: &mut W,
+    size: Option<u64>,
+    progress: &mut Progress,
+    integrity: &mut Integrity,
+) -> Result<Vec<u8>> {
+    loop {
+        let encrypted_buffer = session.read_frame().await.map_err(|_e| {
+            Error::SessionError("Transfer interrupted - connection closed early".to_string())
+        })?;
+
+        // Decrypt the chunk and check its position in the stream
+        let (plaintext, last) = match stream.decrypt_next(&encrypted_buffer) {
+            Ok(chunk) => chunk,
+            Err(e) => {
+                status!();
+                status!(
+                    "{} CHUNK AUTHENTICATION FAILED at chunk {}!",
+                    "✗".bright_red().bold(),
+                    stream.index()
+                );
+
+                let _ = protocol::send_error(session, "chunk_auth_failed").await;
+
+                return Err(e);
+            }
+        };
+
+        // The final chunk ends the stream, anything announced must have arrived by now
+        if last {
+            writer.flush().await?;
+            if let Some(size) = size
+                && progress.received != size
+            {
+                return Err(Error::SessionError(format!(
+                    "Stream ended after {} of {} bytes",
+                    progress.received, size
+                )));
+            }
+            return Ok(plaintext);
+        }
+
+        // Undo compression before anything is checked or written
+        let plaintext = if progress.compressed {
+            compression::decompress_chunk(&plaintext, FILE_CHUNK_SIZE)?
+        } else {
+            plaintext
+        };
+
+        if let Some(size) = size
+            && progress.received + plaintext.len() as u64 > size
+        {
+            return Err(Error::SessionError(
+                "Sender sent more data than announced".to_string(),
+            ));
+        }
+
+        // Check the block before it is written
+        let block_index = progress.received / FILE_CHUNK_SIZE as u64;
+        if !integrity.update(block_index, &plaintext) {
+            status!();
+            status!(
+                "{} BLOCK {} FAILED THE MERKLE CHECK!",
+                "✗".bright_red().bold(),
+                block_index
+            );
+
+            let _ = protocol::send_error(session, "block_mismatch").await;
+
+            return Err(Error::FileError(format!(
+                "Block {} does not match the signed Merkle tree",
+                block_index
+            )));
+        }
+
+        writer.write_all(&plaintext).await?;
+        progress.advance(plaintext.len(), encrypted_buffer.len());
+    }
+}
+
+/// Read the sender's final chunk and return its contents (empty, or a hash trailer)
+async fn finish_stream(
+    session: &mut TransferSession,
+    stream: &mut StreamDecryptor,
+) -> Result<Vec<u8>> {
+    let frame = session.read_frame().await.map_err(|_e| {
+        Error::SessionError("Transfer truncated - end of stream marker missing".to_string())
+    })?;
+    let (trailing, last) = stream.decrypt_next(&frame)?;
+
+    if !last {
+        return Err(Error::SessionError(
+            "Sender sent more data than announced".to_string(),
+        ));
+    }
+
+    Ok(trailing)
+}
+
+/// Check the sender's signature on a hash trailer and return the signed hash
+fn verify_trailer(
+    trailer: &[u8],
+    sender_key: &VerifyingKey,
+    session_id: &str,
+    filename: &str,
+    filesize: u64,
+) -> Result<String> {
+    let trailer = HashTrailer::from_bytes(trailer)?;
+
+    let message = protocol::trailer_message(session_id, filename, filesize, &trailer.hash);
+    let signature_bytes = hex::decode(&trailer.signature)
+        .map_err(|_e| Error::SessionError("Invalid trailer signature hex".to_string()))?;
+    let signature = ed25519_dalek::Signature::from_bytes(
+        signature_bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_e| Error::SessionError("Invalid trailer signature length".to_string()))?,
+    );
+
+    signing::verify_signature(sender_key, &message, &signature)?;
+    Ok(trailer.hash)
+}
@@ -0,0 +1,76 @@
+use crate::crypto::card::{self, IdentityCard};
+use crate::crypto::signing;
+use crate::dirs::{config, keys, profile};
+use crate::server::offer;
+use crate::utils::error::{Error, Result};
+use colored::Colorize;
+use qrcode::QrCode;
+use qrcode::render::unicode::Dense1x2;
+
+/// Show our public key, fingerprint and relay, with a signed identity card to share
+pub async fn run(name: Option<String>, relay: Option<String>, no_qr: bool) -> Result<()> {
+    let config = config::load_config()?;
+    let keys_path = &config.path.keys_path;
+    let public_key = keys::load_public_key_from(keys_path)?;
+
+    // A relay named on the command line must exist, the default one is optional
+    let relay = match relay {
+        Some(relay) => Some(config.select_server(Some(relay))?),
+        None => config.select_server(None).ok(),
+    };
+    let name = name.unwrap_or_else(default_name);
+
+    println!("{}", " Your identity:\n".bright_cyan().bold());
+    println!(
+        "   Public key:  {}",
+        hex::encode(public_key.to_bytes()).bright_white()
+    );
+    let fingerprint = signing::format_fingerprint(&public_key);
+    let (first, second) = fingerprint.split_at(fingerprint.len() / 2);
+    println!("   Fingerprint: {}", first.trim().bright_yellow());
+    println!("                {}", second.trim().bright_yellow());
+    match &relay {
+        Some(relay) => println!(
+            "   Relay:       {} ({}:{}, socket {})",
+            relay.server_name, relay.server_ip, relay.http_port, relay.socket_port
+        ),
+        None => println!("   Relay:       {}", "none configured".dimmed()),
+    }
+
+    // Signing may ask for the passphrase, unless the agent holds the key
+    let identity = keys::load_identity(keys_path)?;
+    let card = IdentityCard::new(public_key, &name, relay, offer::now())?;
+    let card = card.sign(&identity)?;
+
+    println!("\n{}", " Identity card:\n".bright_cyan().bold());
+    println!("{}", card);
+
+    if !no_qr {
+        let code = QrCode::new(card.as_bytes())
+            .map_err(|e| Error::InvalidInput(format!("Card does not fit a QR code: {}", e)))?;
+        let image = code
+            .render::<Dense1x2>()
+            .dark_color(Dense1x2::Light)
+            .light_color(Dense1x2::Dark)
+            .quiet_zone(true)
+            .build();
+        println!("\n{}", image);
+    }
+
+    println!(
+        "\n Others add you with {}",
+        "rs trust add --key <card>".bright_cyan()
+    );
+
+    Ok(())
+}
+
+/// Name for the card when none is given: the user name, or the profile's name
+pub fn default_name() -> String {
+    std::env::var("USER")
+        .or_else(|_e| std::env::var("USERNAME"))
+        .ok()
+        .filter(|name| card::validate_name(name).is_ok())
+        .or_else(|| profile::active().ok())
+        .unwrap_or_else(|| "rshare".to_string())
+}
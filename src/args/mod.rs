@@ -1,6 +1,11 @@
+#[cfg(unix)]
+pub mod agent;
 pub mod health;
 pub mod init;
+pub mod key;
 pub mod listen;
+pub mod me;
+pub mod profile;
 pub mod relays;
 pub mod serve;
 pub mod trust;
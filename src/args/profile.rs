@@ -0,0 +1,95 @@
+use crate::args::init;
+use crate::config::{CONFIG_FILE, DEFAULT_PROFILE, PROFILE_ENV};
+use crate::dirs::{config, keys, profile};
+use crate::utils::error::Result;
+use colored::Colorize;
+
+/// Create a profile with its own keys, contacts, relays and download path
+pub async fn create(name: String, passphrase: bool) -> Result<()> {
+    profile::create(&name)?;
+
+    // Initialize inside the new profile, and leave nothing behind if that fails
+    profile::select(&name)?;
+    if let Err(e) = init::run(None, false, passphrase, false).await {
+        let _ = profile::discard(&name);
+        return Err(e);
+    }
+
+    println!("\n{} Profile '{}' created", "✓".bright_green(), name);
+    println!(
+        "   Use it with {} or {}, or make it the default with {}",
+        format!("--profile {}", name).bright_cyan(),
+        format!("{}={}", PROFILE_ENV, name).bright_cyan(),
+        format!("rs profile use {}", name).bright_cyan()
+    );
+
+    Ok(())
+}
+
+/// List profiles, marking the active one
+pub async fn list() -> Result<()> {
+    let active = profile::active()?;
+
+    println!("{}", " Profiles:\n".bright_cyan().bold());
+
+    for name in profile::list()? {
+        if name == active {
+            println!(
+                "{} {}",
+                format!("  • {}", name).bright_white().bold(),
+                "(active)".bright_green()
+            );
+        } else {
+            println!("{}", format!("  • {}", name).bright_white().bold());
+        }
+
+        let dir = profile::dir_of(&name)?;
+        match config::load_config_from(&dir.join(CONFIG_FILE)) {
+            Ok(config) => {
+                if let Ok(fingerprint) =
+                    keys::get_public_key_fingerprint_from(&config.path.keys_path)
+                {
+                    println!("    Key:       {}...", fingerprint.bright_yellow());
+                }
+                println!("    Directory: {}", dir.display());
+                println!("    Downloads: {}", config.path.download_path.display());
+            }
+            Err(_) => {
+                println!("    Directory: {}", dir.display());
+                println!(
+                    "    Not initialized, run {}",
+                    format!("rs --profile {} init", name).bright_cyan()
+                );
+            }
+        }
+
+        println!();
+    }
+
+    Ok(())
+}
+
+/// Make a profile the default for commands without `--profile`
+pub async fn switch(name: String) -> Result<()> {
+    profile::set_default(&name)?;
+
+    if name == DEFAULT_PROFILE {
+        println!("{} Using the default profile", "✓".bright_green());
+    } else {
+        println!("{} Using profile '{}'", "✓".bright_green(), name);
+    }
+
+    if let Ok(env_profile) = std::env::var(PROFILE_ENV)
+        && !env_profile.is_empty()
+        && env_profile != name
+    {
+        println!(
+            "{} {} is set to '{}' and takes precedence in this shell",
+            "✗".bright_yellow(),
+            PROFILE_ENV,
+            env_profile
+        );
+    }
+
+    Ok(())
+}
@@ -1,39 +1,114 @@
 use crate::config::constants::*;
-use crate::crypto::{encryption, key_exchange, signing};
+use crate::crypto::encryption::{CipherSuite, StreamEncryptor};
+use crate::crypto::identity::Identity;
+use crate::crypto::key_schedule::SessionKeys;
+use crate::crypto::noise::{self, NoiseHandshake};
+use crate::crypto::{key_exchange, signing};
 #[allow(unused_imports)]
 //use std::fs::File;
 use crate::dirs::config::Config;
 use crate::dirs::{config, contacts, keys};
-use crate::server::RelayClient;
+use crate::server::offer::Offer;
+use crate::server::protocol::{self, Accept, HashTrailer, NoiseInit, NoiseReply};
+use crate::server::{RelayClient, TransferRole, TransferSession};
+use crate::utils::compression;
 use crate::utils::error::{Error, Result};
 use crate::utils::hash::{self, validate_file_path};
+use crate::utils::manifest::Manifest;
+use crate::utils::merkle::MerkleTree;
 use colored::Colorize;
-use indicatif::{ProgressBar, ProgressStyle};
+use ed25519_dalek::VerifyingKey;
+use indicatif::{HumanBytes, ProgressBar, ProgressStyle};
 #[allow(unused_imports)]
 use memmap2::Mmap;
-use std::path::PathBuf;
+use std::io::SeekFrom;
+use std::path::{Path, PathBuf};
 use tokio::fs::File;
-use tokio::io::{AsyncReadExt, BufReader};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncSeekExt, BufReader};
+
+/// What is being sent in this session
+enum Payload {
+    /// A single regular file, sent as-is
+    File(PathBuf),
+    /// Several files or directory trees, preceded by the manifest
+    Tree { manifest: Manifest, bytes: Vec<u8> },
+    /// Data piped into stdin (`--file -`), length unknown until the end
+    Stdin,
+}
 
-/// Serve (send) a file to a trusted contact
-pub async fn run(file: PathBuf, to: String, _quiet: bool, relay: Option<String>) -> Result<()> {
+/// Serve (send) one file, several files or a directory tree to a trusted contact
+#[allow(clippy::too_many_arguments)]
+pub async fn run(
+    files: Vec<PathBuf>,
+    to: String,
+    _quiet: bool,
+    relay: Option<String>,
+    stream_hash: bool,
+    compress: bool,
+    streams: u32,
+    pq: bool,
+    cipher: Option<CipherSuite>,
+) -> Result<()> {
     println!("{}", "Serving...\n".bright_blue().bold());
 
-    // Validate file exists
-    validate_file_path(&file).await?;
+    // A single regular file is sent as-is, anything else goes through a manifest
+    let payload = match files.as_slice() {
+        [file] if file == Path::new("-") => Payload::Stdin,
+        _ if files.iter().any(|file| file == Path::new("-")) => {
+            return Err(Error::InvalidInput(
+                "stdin (-) cannot be combined with other files".into(),
+            ));
+        }
+        [file] if file.is_file() => Payload::File(file.clone()),
+        _ if stream_hash => {
+            return Err(Error::InvalidInput(
+                "--stream-hash only applies to a single file".into(),
+            ));
+        }
+        _ => {
+            println!("{}", "Building manifest...".white());
+            let manifest = Manifest::build(&files).await?;
+            let bytes = manifest.to_bytes()?;
+            Payload::Tree { manifest, bytes }
+        }
+    };
+
+    let (filename, filesize) = match &payload {
+        Payload::File(file) => {
+            // Validate file exists
+            validate_file_path(file).await?;
 
-    let filename = file
-        .file_name()
-        .ok_or_else(|| Error::InvalidInput("Invalid filename".into()))?
-        .to_string_lossy()
-        .to_string();
+            let filename = file
+                .file_name()
+                .ok_or_else(|| Error::InvalidInput("Invalid filename".into()))?
+                .to_string_lossy()
+                .to_string();
 
-    let metadata = std::fs::metadata(&file)?;
-    let filesize = metadata.len();
+            let metadata = std::fs::metadata(file)?;
+            (filename, metadata.len())
+        }
+        Payload::Tree { manifest, .. } => {
+            // Label shown to the receiver: the directory name or a file count
+            let label = match files.len() {
+                1 => manifest.entries[0]
+                    .path
+                    .split('/')
+                    .next()
+                    .unwrap_or_default()
+                    .to_string(),
+                _ => format!("{} files", manifest.entries.len()),
+            };
+
+            (label, manifest.total_size())
+        }
+        // The real size is only known at the end, it is signed in the trailer
+        Payload::Stdin => (STDIN_FILENAME.to_string(), 0),
+    };
 
     // Load config and keys
     let config = config::load_config()?;
-    let (signing_key, verifying_key) = keys::load_keys_from(&config.path.keys_path)?;
+    let identity = keys::load_identity(&config.path.keys_path)?;
+    let verifying_key = identity.verifying_key();
     let my_fingerprint = hex::encode(verifying_key.to_bytes());
 
     // Load contacts and find recipient
@@ -41,10 +116,37 @@ pub async fn run(file: PathBuf, to: String, _quiet: bool, relay: Option<String>)
     let recipient = contact_list
         .get(&to)
         .ok_or_else(|| Error::InvalidInput(format!("Contact >'{}'< not found", to)))?;
+    if !recipient.is_verified() && recipient.public_key != my_fingerprint {
+        println!(
+            "{} '{}' is not verified, compare codes with {}",
+            "✗".bright_yellow().bold(),
+            to,
+            format!("rs trust verify {}", to).bright_cyan()
+        );
+    }
 
     // Compute file hash for integrity verification
+    // Single files sign the Merkle root over FILE_CHUNK_SIZE blocks, so every chunk
+    // can be checked on arrival. Multi-file transfers sign the manifest hash, which
+    // covers every file hash.
     //println!("{}", " Computing file hash...".bright_cyan());
-    let file_hash_hex = hash::compute_file_hash(&file).await?;
+    let merkle = match &payload {
+        Payload::File(file) if !stream_hash => Some(MerkleTree::from_file(file).await?),
+        _ => None,
+    };
+
+    // With --stream-hash the file is hashed while sending and the root follows in a signed trailer
+    let file_hash_hex = match (&payload, &merkle) {
+        (_, Some(tree)) => format!("{}{}", MERKLE_HASH_PREFIX, tree.root_hex()),
+        (Payload::File(_), None) => HASH_TRAILER_MARKER.to_string(),
+        (Payload::Stdin, None) => PIPE_HASH_MARKER.to_string(),
+        (Payload::Tree { bytes, .. }, None) => {
+            format!("{}{}", MANIFEST_HASH_PREFIX, Manifest::digest(bytes))
+        }
+    };
+    let display_hash = file_hash_hex
+        .trim_start_matches(MANIFEST_HASH_PREFIX)
+        .trim_start_matches(MERKLE_HASH_PREFIX);
     //println!(
     //    "{}  Hash: {}...",
     //    "✓".bright_green(),
@@ -58,15 +160,20 @@ pub async fn run(file: PathBuf, to: String, _quiet: bool, relay: Option<String>)
     println!(
         " File: {} | Hash {}",
         filename.bright_yellow(),
-        file_hash_hex[..KEY_FINGERPRINT_DISPLAY_LEN]
-            .bright_cyan()
-            .dimmed()
-    );
-    println!(
-        " Size: {} bytes ({:.2} MB)",
-        filesize,
-        filesize as f64 / (1024.0 * 1024.0)
+        hash::display_hash(display_hash).bright_cyan().dimmed()
     );
+    if let Payload::Tree { manifest, .. } = &payload {
+        println!(" Files: {}", manifest.entries.len());
+    }
+    if let Payload::Stdin = &payload {
+        println!(" Size: unknown (reading from stdin)");
+    } else {
+        println!(
+            " Size: {} bytes ({:.2} MB)",
+            filesize,
+            filesize as f64 / (1024.0 * 1024.0)
+        );
+    }
     println!(" To:   {}", to.bright_white().bold());
     //println!(
     //    "   Key:  {}...",
@@ -90,7 +197,7 @@ pub async fn run(file: PathBuf, to: String, _quiet: bool, relay: Option<String>)
 
     // Create transfer metadata and signature (includes file hash)
     let metadata_msg = format!("{}|{}|{}", filename, filesize, file_hash_hex);
-    let metadata_signature = signing::sign_data(&signing_key, &metadata_msg)?;
+    let metadata_signature = signing::sign_data(&identity, &metadata_msg)?;
     let signature_hex = hex::encode(metadata_signature.to_bytes());
 
     // Generate ephemeral X25519 keypair for this transfer
@@ -109,6 +216,21 @@ pub async fn run(file: PathBuf, to: String, _quiet: bool, relay: Option<String>)
     //);
     //println!();
 
+    // Signed offer: binds the metadata to the recipient, this session's ephemeral
+    // key and a validity window, so it cannot be replayed or redirected
+    let offer = Offer::new(
+        &my_fingerprint,
+        &recipient.public_key,
+        &sender_ephemeral_hex,
+        &filename,
+        filesize,
+        &file_hash_hex,
+    )
+    .sign(&identity)?;
+
+    // After `rs key rotate`, contacts still trusting an older key follow these
+    let key_rotations = keys::recent_rotations_from(&config.path.keys_path)?;
+
     // Initiate transfer session (blocks until receiver connects)
     // Metadata is sent via HTTP API
     println!();
@@ -121,27 +243,227 @@ pub async fn run(file: PathBuf, to: String, _quiet: bool, relay: Option<String>)
             filesize,
             signature_hex,
             file_hash_hex,
-            sender_ephemeral_hex,
+            sender_ephemeral_hex.clone(),
+            offer,
+            &key_rotations,
         )
         .await?;
 
     println!("  Session: {}", session.session_id().bright_blue());
     println!();
 
-    // Derive encryption key from ephemeral keys
     let receiver_ephemeral_hex = session
         .receiver_ephemeral_key
-        .as_ref()
+        .clone()
         .ok_or_else(|| Error::SessionError("Receiver key not found".to_string()))?;
 
-    //println!("{}", " Deriving encryption key...".bright_cyan());
-    let aes_key = key_exchange::perform_key_exchange(
-        ephemeral_keypair.secret,
-        receiver_ephemeral_hex,
+    // Negotiate transfer parameters before any data flows
+    let hello = protocol::recv_hello(&mut session).await?;
+    let recipient_key = signing::parse_verifying_key(&recipient.public_key)?;
+
+    // Receivers that support it set up the session with a Noise handshake between the
+    // identity keys, older ones with the signed ephemeral keys
+    let handshake = hello
+        .supports_handshake(HANDSHAKE_NOISE_IK)
+        .then(|| HANDSHAKE_NOISE_IK.to_string());
+
+    // The hybrid exchange rides on the Noise handshake, older receivers cannot do it
+    if pq && handshake.is_none() {
+        println!();
+        println!(
+            "{} Receiver does not support the hybrid post-quantum exchange (--pq)",
+            "✗".bright_red().bold()
+        );
+        println!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+        let _ = protocol::send_error(&mut session, "pq_unsupported").await;
+
+        return Err(Error::CryptoError(
+            "Receiver is classical-only, refusing to continue without ML-KEM".to_string(),
+        ));
+    }
+
+    // Cipher suites are negotiated in the Noise handshake, the legacy setup is fixed
+    if let Some(cipher) = cipher
+        && handshake.is_none()
+        && cipher != CipherSuite::Aes256Gcm
+    {
+        println!();
+        println!(
+            "{} Receiver cannot use the {} cipher suite (--cipher)",
+            "✗".bright_red().bold(),
+            cipher
+        );
+        println!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+        let _ = protocol::send_error(&mut session, "cipher_unsupported").await;
+
+        return Err(Error::CryptoError(format!(
+            "Receiver only supports {}, refusing to continue without {}",
+            CipherSuite::Aes256Gcm,
+            cipher
+        )));
+    }
+
+    // The receiver's ephemeral key came through the relay, only use it if the
+    // recipient's identity key vouches for it (and for the handshakes it offered)
+    let receiver_key_message = protocol::ephemeral_key_message(
+        TransferRole::Receiver,
         session.session_id(),
-    )?;
+        &my_fingerprint,
+        &recipient.public_key,
+        &receiver_ephemeral_hex,
+        &hello.handshakes,
+    );
+    if handshake.is_none()
+        && let Err(e) = key_exchange::verify_ephemeral_key(
+            &recipient_key,
+            &receiver_key_message,
+            &hello.key_signature,
+        )
+    {
+        println!();
+        println!(
+            "{} RECEIVER KEY VERIFICATION FAILED!",
+            "✗".bright_red().bold()
+        );
+        println!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+        let _ = protocol::send_error(&mut session, "key_signature_failed").await;
+
+        return Err(e);
+    }
+
+    // Only single files with a known Merkle tree can be resumed, on a block boundary
+    // and never past the end of the file
+    let resume_offset = match &merkle {
+        Some(_)
+            if hello.resume_offset == filesize
+                || (hello.resume_offset < filesize
+                    && hello.resume_offset.is_multiple_of(FILE_CHUNK_SIZE as u64)) =>
+        {
+            hello.resume_offset
+        }
+        _ => 0,
+    };
+
+    // Compress only if asked to and the receiver can decode it (older receivers cannot)
+    let compression = (compress && hello.supports_compression(COMPRESSION_ZSTD))
+        .then(|| COMPRESSION_ZSTD.to_string());
+    if compress && compression.is_none() {
+        println!(
+            "{} Receiver does not support compression, sending uncompressed",
+            "✗".bright_yellow().bold()
+        );
+    }
+
+    // Use as many streams as both sides and the relay allow (older relays: one)
+    if streams > session.max_streams {
+        println!(
+            "{} Relay does not support {} streams, using {}",
+            "✗".bright_yellow().bold(),
+            streams,
+            session.max_streams
+        );
+    }
+    let streams = streams.min(session.max_streams).min(hello.streams.max(1));
+
+    // Without Noise, vouch for our own ephemeral key in the same way
+    let key_signature = match handshake {
+        Some(_) => String::new(),
+        None => key_exchange::sign_ephemeral_key(
+            &identity,
+            &protocol::ephemeral_key_message(
+                TransferRole::Sender,
+                session.session_id(),
+                &my_fingerprint,
+                &recipient.public_key,
+                &sender_ephemeral_hex,
+                handshake.as_slice(),
+            ),
+        )?,
+    };
+    let accept = Accept::new(
+        resume_offset,
+        compression.clone(),
+        streams,
+        key_signature,
+        handshake.clone(),
+    );
+    protocol::send_accept(&mut session, &accept).await?;
+
+    // Derive session keys from the handshake, or from the ephemeral keys
+    //println!("{}", " Deriving encryption key...".bright_cyan());
+    let keys = match &handshake {
+        Some(_) => {
+            let prologue = protocol::noise_prologue(session.session_id(), &hello, &accept)?;
+            noise_respond(
+                &mut session,
+                &identity,
+                &recipient_key,
+                &prologue,
+                pq,
+                cipher,
+            )
+            .await?
+        }
+        // Receivers without Noise predate the key schedule as well
+        None => SessionKeys::legacy(key_exchange::perform_key_exchange(
+            ephemeral_keypair.secret,
+            &receiver_ephemeral_hex,
+            session.session_id(),
+        )?),
+    };
+    // The receiver's DONE or ERROR must carry a valid tag (not with older receivers)
+    if let Some(control_key) = keys.control.clone() {
+        session.set_control_key(control_key);
+    }
     //println!("{}  Encryption key derived", "✓".bright_green());
     //println!();
+    relay_client.open_streams(&mut session, streams).await?;
+
+    if resume_offset > 0 {
+        println!(
+            "{} Resuming from {} bytes ({:.1}%)",
+            "↻".bright_cyan().bold(),
+            resume_offset,
+            (resume_offset as f64 / filesize as f64) * 100.0
+        );
+    }
+
+    if handshake.is_some() {
+        println!(
+            "{} Session set up with {}",
+            "⇗".bright_cyan().bold(),
+            NOISE_PARAMS
+        );
+    }
+
+    if keys.post_quantum {
+        println!(
+            "{} Hybrid post-quantum key exchange: X25519 + {}",
+            "⇗".bright_cyan().bold(),
+            KEM_ALGORITHM
+        );
+    }
+
+    println!("{} Cipher: {}", "⇗".bright_cyan().bold(), keys.suite);
+
+    if let Some(code) = keys.confirmation_code() {
+        println!(
+            "{} Key check: {}",
+            "⇗".bright_cyan().bold(),
+            code.bright_cyan()
+        );
+    }
+
+    if streams > 1 {
+        println!(
+            "{} Striping chunks over {} streams",
+            "⇗".bright_cyan().bold(),
+            streams
+        );
+    }
 
     // Socket now ready for encrypted binary file transfer
     println!(
@@ -150,70 +472,155 @@ pub async fn run(file: PathBuf, to: String, _quiet: bool, relay: Option<String>)
     );
 
     // Send file data with progress bar (encrypt each chunk)
-    //let file_reader = File::open(&file)?;
-    let file_reader = File::open(&file).await?;
-    let mut buf_reader = BufReader::with_capacity(BUFFER_SIZE, file_reader);
-    //let mmap = unsafe { Mmap::map(&file_reader)? };
-
+    // Compressed transfers also show how many bytes actually went over the wire
     let pb = ProgressBar::new(filesize);
     pb.set_style(
         ProgressStyle::default_bar()
-            .template(PROGRESS_BAR_TEMPLATE)
+            .template(match (&payload, &compression) {
+                (Payload::Stdin, _) => PROGRESS_SPINNER_TEMPLATE,
+                (_, Some(_)) => PROGRESS_BAR_WIRE_TEMPLATE,
+                (_, None) => PROGRESS_BAR_TEMPLATE,
+            })
             .unwrap()
             .progress_chars(PROGRESS_BAR_CHARS)
             .tick_chars(DEFAULT_SPINNER_STYLE),
     );
 
-    let mut buffer = vec![0u8; FILE_CHUNK_SIZE];
-    let mut total_sent = 0u64;
+    let mut progress = Progress {
+        pb,
+        sent: resume_offset,
+        wire: 0,
+        compressed: compression.is_some(),
+    };
+    progress.pb.set_position(progress.sent);
+
+    // Every chunk is bound to its position in the session stream
+    let mut stream =
+        StreamEncryptor::new(keys.suite, &keys.sender_to_receiver, session.session_id());
+    let mut hasher = (stream_hash || matches!(payload, Payload::Stdin)).then(MerkleTree::new);
+
+    match &payload {
+        Payload::File(file) => {
+            // Leaves go first so the receiver can check each block against the signed root
+            if let Some(tree) = &merkle {
+                let encrypted_leaves = stream.encrypt_next(&tree.to_bytes(), false)?;
+                session.write_frame(&encrypted_leaves).await?;
+            }
 
-    loop {
-        let n = buf_reader.read(&mut buffer).await?;
-        if n == 0 {
-            break;
+            send_file(
+                &mut session,
+                &mut stream,
+                file,
+                filesize,
+                resume_offset,
+                &mut progress,
+                hasher.as_mut(),
+            )
+            .await?;
+        }
+        Payload::Tree { manifest, bytes } => {
+            // Manifest goes first so the receiver knows how to split the stream
+            let encrypted_manifest = stream.encrypt_next(bytes, false)?;
+            session.write_frame(&encrypted_manifest).await?;
+
+            for entry in &manifest.entries {
+                send_file(
+                    &mut session,
+                    &mut stream,
+                    &entry.source,
+                    entry.size,
+                    0,
+                    &mut progress,
+                    None,
+                )
+                .await?;
+            }
+        }
+        Payload::Stdin => {
+            let mut stdin = BufReader::with_capacity(BUFFER_SIZE, tokio::io::stdin());
+            send_data(
+                &mut session,
+                &mut stream,
+                &mut stdin,
+                &mut progress,
+                hasher.as_mut(),
+            )
+            .await?;
         }
-
-        //for chunk in mmap.chunks(FILE_CHUNK_SIZE) {
-        // Encrypt the chunk before sending
-        let encrypted_chunk = encryption::encrypt_chunk(&aes_key, &buffer[..n])?;
-        //let encrypted_chunk = encryption::encrypt_chunk(&aes_key, chunk)?;
-
-        // Send encrypted chunk size (4 bytes) followed by encrypted data
-        let chunk_size = encrypted_chunk.len() as u32;
-        session.write_all(&chunk_size.to_be_bytes()).await?;
-        session.write_all(&encrypted_chunk).await?;
-
-        total_sent += n as u64;
-        //total_sent += chunk.len() as u64;
-        pb.set_position(total_sent);
     }
 
+    // Final chunk marks the end, so truncation is detected by the receiver.
+    // It is empty, or carries the signed hash trailer when hashing while sending.
+    // For piped input the trailer also fixes the size, which only now is known.
+    let signed_size = match &payload {
+        Payload::Stdin => progress.sent,
+        _ => filesize,
+    };
+    let trailer = match hasher {
+        Some(tree) => {
+            let hash = format!("{}{}", MERKLE_HASH_PREFIX, tree.root_hex());
+            let message =
+                protocol::trailer_message(session.session_id(), &filename, signed_size, &hash);
+            let signature = signing::sign_data(&identity, &message)?;
+
+            HashTrailer {
+                hash,
+                signature: hex::encode(signature.to_bytes()),
+            }
+            .to_bytes()?
+        }
+        None => Vec::new(),
+    };
+    let end_of_stream = stream.encrypt_next(&trailer, true)?;
+    session.write_frame(&end_of_stream).await?;
+
     session.flush().await?;
-    pb.finish_with_message("Transfer complete!");
+    progress.pb.finish_with_message("Transfer complete!");
+
+    if progress.compressed {
+        println!();
+        println!(
+            " Compressed: {} raw, {} on the wire",
+            HumanBytes(progress.sent - resume_offset),
+            HumanBytes(progress.wire)
+        );
+    }
 
     println!();
     println!();
     println!("{}", "Waiting for receiver confirmation....".yellow());
 
-    // Wait for receiver's completion confirmation
-    let mut ack_buffer = vec![0u8; 10];
-    match session.read(&mut ack_buffer).await {
-        Ok(n) if n > 0 && &ack_buffer[..n] == DONE_SIGNAL => {
+    // Wait for receiver's completion confirmation (authenticated by newer receivers)
+    match protocol::recv_control(&mut session).await {
+        Ok(message) if message == DONE_MESSAGE => {
             println!("  Receiver confirmed receipt!");
         }
-        Ok(n) => {
-            println!(
-                //"{} Got {} bytes, expected DONE signal",
-                "{} Unexpected confirmation response: {} bytes",
-                "✗".bright_yellow().bold(),
-                n
-            );
-            if n > 0 {
+        Ok(message) => match message.strip_prefix(ERROR_SIGNAL_PREFIX) {
+            Some(reason) => {
+                println!(
+                    "{} Receiver rejected the file: {}",
+                    "✗".bright_red().bold(),
+                    reason
+                );
+                return Err(Error::SessionError(format!(
+                    "Receiver rejected the file: {}",
+                    reason
+                )));
+            }
+            None => {
                 println!(
-                    "   Received: {:?}",
-                    String::from_utf8_lossy(&ack_buffer[..n])
+                    "{} Unexpected confirmation response",
+                    "✗".bright_yellow().bold()
                 );
+                println!("   Received: {:?}", message);
             }
+        },
+        Err(e @ Error::CryptoError(_)) => {
+            println!(
+                "{} Confirmation failed authentication (tampered by the relay?)",
+                "✗".bright_red().bold()
+            );
+            return Err(e);
         }
         Err(_e) => {
             println!("{} Failed to read confirmation", "✗".bright_red().bold(),);
@@ -230,3 +637,217 @@ pub async fn run(file: PathBuf, to: String, _quiet: bool, relay: Option<String>)
 
     Ok(())
 }
+
+/// Progress across all files of one transfer
+struct Progress {
+    pb: ProgressBar,
+    /// Raw file bytes sent (including a resumed prefix)
+    sent: u64,
+    /// Encrypted bytes written to the socket in this session
+    wire: u64,
+    /// Data chunks are compressed and carry a compression flag
+    compressed: bool,
+}
+
+impl Progress {
+    fn advance(&mut self, raw: usize, wire: usize) {
+        self.sent += raw as u64;
+        self.wire += wire as u64;
+        self.pb.set_position(self.sent);
+
+        if self.compressed {
+            self.pb.set_prefix(format!(
+                "raw {} / wire {}",
+                HumanBytes(self.sent),
+                HumanBytes(self.wire)
+            ));
+        }
+    }
+}
+
+/// Answer the receiver's Noise handshake and return the session keys
+///
+/// The receiver's static key must belong to the recipient contact. The reply carries
+/// the key schedule and cipher suite picked from the ones the receiver offers and, if
+/// either side asks for a hybrid exchange (`pq`), a secret encapsulated to the receiver's
+/// ML-KEM key. The session keys come from the finished handshake (and that secret).
+async fn noise_respond(
+    session: &mut TransferSession,
+    identity: &Identity,
+    recipient_key: &VerifyingKey,
+    prologue: &[u8],
+    pq: bool,
+    cipher: Option<CipherSuite>,
+) -> Result<SessionKeys> {
+    let mut handshake = NoiseHandshake::responder(identity, prologue)?;
+
+    let first = protocol::recv_handshake(session).await?;
+    let init = match handshake.read_message(&first) {
+        Ok(payload) if handshake.remote_is(recipient_key) => NoiseInit::from_bytes(&payload),
+        Ok(_payload) => Err(Error::CryptoError(
+            "Handshake is not from the trusted contact".to_string(),
+        )),
+        Err(e) => Err(e),
+    };
+
+    let init = match init {
+        Ok(init) => init,
+        Err(e) => {
+            println!();
+            println!("{} NOISE HANDSHAKE FAILED!", "✗".bright_red().bold());
+            println!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+            let _ = protocol::send_error(session, "handshake_failed").await;
+
+            return Err(e);
+        }
+    };
+
+    // The session keys are expanded with a key schedule both sides speak
+    let Some(key_schedule) = init.key_schedule() else {
+        println!();
+        println!(
+            "{} Receiver does not support a session key schedule",
+            "✗".bright_red().bold()
+        );
+        println!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+        let _ = protocol::send_error(session, "key_schedule_unsupported").await;
+
+        return Err(Error::CryptoError(
+            "Receiver supports none of our key schedules".to_string(),
+        ));
+    };
+
+    // Only key schedule v2 binds the suite into the keys, earlier ones mean AES-256-GCM
+    let suite = if key_schedule >= 2 {
+        CipherSuite::negotiate(&CipherSuite::preferred(cipher), &init.cipher_suites())
+    } else {
+        Some(CipherSuite::Aes256Gcm)
+    };
+    let Some(suite) = suite else {
+        println!();
+        println!(
+            "{} No cipher suite in common with the receiver",
+            "✗".bright_red().bold()
+        );
+        println!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+        let _ = protocol::send_error(session, "cipher_unsupported").await;
+
+        return Err(Error::CryptoError(
+            "Receiver supports none of our cipher suites".to_string(),
+        ));
+    };
+
+    // Hybrid if either side asks for it, and then never without the KEM
+    let hybrid = pq || init.kem.as_ref().is_some_and(|offer| offer.required);
+    let kem = match init.kem_offer() {
+        Some(offer) if hybrid => Some(key_exchange::kem_encapsulate(&offer.public_key)?),
+        _ if hybrid => {
+            println!();
+            println!(
+                "{} Receiver does not support the hybrid post-quantum exchange (--pq)",
+                "✗".bright_red().bold()
+            );
+            println!("{} Transfer REJECTED.", "✗".bright_red().bold());
+
+            let _ = protocol::send_error(session, "pq_unsupported").await;
+
+            return Err(Error::CryptoError(
+                "Receiver is classical-only, refusing to continue without ML-KEM".to_string(),
+            ));
+        }
+        _ => None,
+    };
+
+    let reply = NoiseReply {
+        key_schedule,
+        cipher_suite: suite,
+        kem_ciphertext: kem.as_ref().map(|(ciphertext, _secret)| ciphertext.clone()),
+    };
+    let message = handshake.write_message(&reply.to_bytes())?;
+    protocol::send_handshake(session, &message).await?;
+
+    let split_secret = handshake.split_secret()?;
+    noise::derive_session_keys(
+        &split_secret,
+        kem.as_ref().map(|(_ciphertext, secret)| secret),
+        handshake.handshake_hash(),
+        key_schedule,
+        suite,
+    )
+}
+
+/// Encrypt and send a file from `offset` up to `size` as [4B size][encrypted chunk] frames
+async fn send_file(
+    session: &mut TransferSession,
+    stream: &mut StreamEncryptor,
+    file: &Path,
+    size: u64,
+    offset: u64,
+    progress: &mut Progress,
+    hasher: Option<&mut MerkleTree>,
+) -> Result<()> {
+    //let file_reader = File::open(&file)?;
+    let mut file_reader = File::open(file).await?;
+    file_reader.seek(SeekFrom::Start(offset)).await?;
+    let mut buf_reader = BufReader::with_capacity(BUFFER_SIZE, file_reader).take(size - offset);
+    //let mmap = unsafe { Mmap::map(&file_reader)? };
+
+    let file_sent = offset + send_data(session, stream, &mut buf_reader, progress, hasher).await?;
+
+    // The receiver splits the stream by the announced sizes, so they must match
+    if file_sent != size {
+        return Err(Error::FileError(format!(
+            "{} changed size during transfer",
+            file.display()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Encrypt and send everything `reader` yields, returning the number of bytes sent
+///
+/// Chunks are full `FILE_CHUNK_SIZE` blocks (except the last), so they line up with
+/// the Merkle leaves. When a tree is given, every block read is also added to it.
+async fn send_data<R: AsyncRead + Unpin>(
+    session: &mut TransferSession,
+    stream: &mut StreamEncryptor,
+    reader: &mut R,
+    progress: &mut Progress,
+    mut hasher: Option<&mut MerkleTree>,
+) -> Result<u64> {
+    let mut buffer = vec![0u8; FILE_CHUNK_SIZE];
+    let mut sent = 0u64;
+
+    loop {
+        let n = hash::read_block(reader, &mut buffer).await?;
+        if n == 0 {
+            break;
+        }
+
+        if let Some(hasher) = hasher.as_deref_mut() {
+            hasher.push(&buffer[..n]);
+        }
+
+        //for chunk in mmap.chunks(FILE_CHUNK_SIZE) {
+        // Compress (if negotiated) and encrypt the chunk before sending
+        let encrypted_chunk = if progress.compressed {
+            stream.encrypt_next(&compression::compress_chunk(&buffer[..n])?, false)?
+        } else {
+            stream.encrypt_next(&buffer[..n], false)?
+        };
+        //let encrypted_chunk = encryption::encrypt_chunk(&aes_key, chunk)?;
+
+        // Send encrypted chunk size (4 bytes) followed by encrypted data
+        session.write_frame(&encrypted_chunk).await?;
+
+        sent += n as u64;
+        progress.advance(n, encrypted_chunk.len());
+        //total_sent += chunk.len() as u64;
+    }
+
+    Ok(sent)
+}
@@ -1,9 +1,17 @@
-use crate::dirs::contacts;
-use crate::utils::error::Result;
+use crate::args::me;
+use crate::config::SAS_GROUPS;
+use crate::crypto::card::{IdentityCard, Invitation};
+use crate::crypto::{key_format, sas, signing};
+use crate::dirs::config::ServerConfig;
+use crate::dirs::contacts::{self, AuditEvent};
+use crate::dirs::{config, keys};
+use crate::server::offer;
+use crate::utils::error::{Error, Result};
 use colored::Colorize;
+use std::io::{IsTerminal, Write};
 
 /// Add a trusted contact
-pub async fn add(name: String, pubkey: String) -> Result<()> {
+pub async fn add(name: Option<String>, pubkey: String) -> Result<()> {
     let mut contacts = contacts::load_contacts()?;
 
     // CLAP HANDLES THIS
@@ -17,10 +25,33 @@ pub async fn add(name: String, pubkey: String) -> Result<()> {
         }
     };*/
 
-    contacts.add(name.clone(), pubkey)?;
+    // An identity card names its owner, a bare key needs --name
+    let card = if IdentityCard::is_card(&pubkey) {
+        Some(IdentityCard::parse(&pubkey)?)
+    } else {
+        None
+    };
+    let (name, public_key) = match &card {
+        Some(card) => (name.unwrap_or_else(|| card.name.clone()), card.public_key),
+        None => (
+            name.ok_or_else(|| {
+                Error::InvalidInput("Provide --name for a contact added by key".to_string())
+            })?,
+            key_format::parse_public(&pubkey)?,
+        ),
+    };
+
+    // Stored as hex whatever form it was given in
+    contacts.add(name.clone(), hex::encode(public_key.to_bytes()))?;
     contacts::save_contacts(&contacts)?;
 
     println!("{} Trust added: {}", "✓".bright_green(), name.clone());
+    if let Some(relay) = card.and_then(|card| card.relay) {
+        println!(
+            "   Their relay: {} ({}:{}, socket {})",
+            relay.server_name, relay.server_ip, relay.http_port, relay.socket_port
+        );
+    }
 
     Ok(())
 }
@@ -37,11 +68,38 @@ pub async fn list(verbose: bool) -> Result<()> {
     println!("{}", " Trusted Contacts:\n".bright_cyan().bold());
 
     for contact in contacts.list() {
-        println!("{}", format!("  • {}", contact.name).bright_white().bold());
+        // Our own key needs no comparing
+        if contact.name == "self" {
+            println!("{}", format!("  • {}", contact.name).bright_white().bold());
+        } else if contact.is_verified() {
+            println!(
+                "{} {}",
+                format!("  • {}", contact.name).bright_white().bold(),
+                "(verified)".bright_green()
+            );
+        } else {
+            println!(
+                "{} {}",
+                format!("  • {}", contact.name).bright_white().bold(),
+                "(unverified)".dimmed()
+            );
+        }
 
         if verbose {
             println!("    Key:   {}", &contact.public_key.bright_yellow());
             println!("    Added: {}", contact.added_at.dimmed());
+            if let Some(verified_at) = &contact.verified_at {
+                println!("    Verified: {}", verified_at.dimmed());
+            }
+
+            for entry in contacts.audit_of(&contact.name) {
+                let AuditEvent::KeyRotated { old_key, .. } = &entry.event;
+                println!(
+                    "    Rotated: {} (was {}...)",
+                    entry.at.dimmed(),
+                    old_key.get(..16).unwrap_or(old_key)
+                );
+            }
         }
 
         println!();
@@ -50,6 +108,209 @@ pub async fn list(verbose: bool) -> Result<()> {
     Ok(())
 }
 
+/// Show the short code for a contact's key and mark the contact verified once compared
+pub async fn verify(name: String, yes: bool) -> Result<()> {
+    let config = config::load_config()?;
+    let my_key = keys::load_public_key_from(&config.path.keys_path)?;
+
+    let mut contact_list = contacts::load_contacts()?;
+    let contact = contact_list
+        .get(&name)
+        .ok_or_else(|| Error::InvalidInput(format!("Contact '{}' not found", name)))?;
+    let their_key = signing::parse_verifying_key(&contact.public_key)?;
+    let code = sas::short_auth_string(&my_key, &their_key);
+
+    println!(
+        "{}",
+        format!(" Verification code with {}:\n", name)
+            .bright_cyan()
+            .bold()
+    );
+    // One row per key
+    let groups: Vec<&str> = code.split(' ').collect();
+    for row in groups.chunks(SAS_GROUPS) {
+        println!("   {}", row.join(" ").bright_white().bold());
+    }
+    println!();
+    println!(
+        " {} runs {} and reads their code to you,",
+        name,
+        "rs trust verify <your name>".bright_cyan()
+    );
+    println!(" in person or over a call, not through the channel you got the key from.");
+    if let Some(verified_at) = &contact.verified_at {
+        println!(
+            "\n{} Verified before, on {}",
+            "✓".bright_green(),
+            verified_at.dimmed()
+        );
+    }
+    println!();
+
+    if !yes && !confirm(&format!("Does {} read the same code?", name))? {
+        println!(
+            "{} Not verified. If the codes differ, the key you have for {} may not be theirs",
+            "✗".bright_yellow(),
+            name
+        );
+        return Ok(());
+    }
+
+    contact_list.mark_verified(&name)?;
+    contacts::save_contacts(&contact_list)?;
+    println!("{} {} is verified", "✓".bright_green(), name);
+
+    Ok(())
+}
+
+/// Print a signed invitation carrying our name, key and relay, valid for `days`
+pub async fn invite(name: Option<String>, relay: Option<String>, days: u64) -> Result<()> {
+    let config = config::load_config()?;
+    let keys_path = &config.path.keys_path;
+    let public_key = keys::load_public_key_from(keys_path)?;
+
+    // A relay named on the command line must exist, the default one is optional
+    let relay = match relay {
+        Some(relay) => Some(config.select_server(Some(relay))?),
+        None => config.select_server(None).ok(),
+    };
+    let name = name.unwrap_or_else(me::default_name);
+
+    let card = IdentityCard::new(public_key, &name, relay, offer::now())?;
+    let invitation = Invitation::new(card, days)?;
+
+    // Signing may ask for the passphrase, unless the agent holds the key
+    let identity = keys::load_identity(keys_path)?;
+    let token = invitation.sign(&identity)?;
+
+    let expires = chrono::DateTime::from_timestamp(invitation.expires_at as i64, 0)
+        .map(|at| at.format("%Y-%m-%d %H:%M UTC").to_string())
+        .unwrap_or_default();
+    println!(
+        "{}",
+        format!(" Invitation for {} (valid until {}):\n", name, expires)
+            .bright_cyan()
+            .bold()
+    );
+    println!("{}", token);
+    println!(
+        "\n They add you with {}",
+        "rs trust accept <token>".bright_cyan()
+    );
+
+    Ok(())
+}
+
+/// Add the contact and relay from an invitation, once its signature and expiry check out
+pub async fn accept(token: String, name: Option<String>) -> Result<()> {
+    // Cards never expire, so they only go through `trust add`, which adds no relay
+    if !Invitation::is_invitation(&token) {
+        let hint = if IdentityCard::is_card(&token) {
+            ", add identity cards with `rs trust add --key <card>`"
+        } else {
+            ""
+        };
+        return Err(Error::InvalidInput(format!(
+            "Not an invitation, expected rshare-invite:...{}",
+            hint
+        )));
+    }
+    let card = Invitation::parse(&token, offer::now())?.card;
+    let name = name.unwrap_or_else(|| card.name.clone());
+    let fingerprint = card.fingerprint();
+
+    let mut contact_list = contacts::load_contacts()?;
+    let known = contact_list
+        .list()
+        .into_iter()
+        .find(|contact| contact.public_key == fingerprint)
+        .map(|contact| contact.name.clone());
+    let name = match known {
+        Some(known) => {
+            println!("{} Already trusted as {}", "✓".bright_green(), known);
+            known
+        }
+        None => {
+            if contact_list.get(&name).is_some() {
+                return Err(Error::InvalidInput(format!(
+                    "Contact '{}' already exists with another key, pick a name with --name",
+                    name
+                )));
+            }
+            contact_list.add(name.clone(), fingerprint)?;
+            contacts::save_contacts(&contact_list)?;
+            println!("{} Trust added: {}", "✓".bright_green(), name);
+            name
+        }
+    };
+
+    if let Some(relay) = card.relay {
+        let mut config = config::load_config()?;
+        let existing = config.server.iter().find(|server| {
+            server.server_ip == relay.server_ip
+                && server.http_port == relay.http_port
+                && server.socket_port == relay.socket_port
+        });
+
+        match existing {
+            Some(server) => println!(
+                "{} Relay already configured as {}",
+                "✓".bright_green(),
+                server.server_name
+            ),
+            None => {
+                // Keep our own relay names, prefix theirs with the contact on a clash
+                let server_name = if config
+                    .server
+                    .iter()
+                    .any(|server| server.server_name == relay.server_name)
+                {
+                    format!("{}-{}", name, relay.server_name)
+                } else {
+                    relay.server_name.clone()
+                };
+                let server = ServerConfig {
+                    server_name,
+                    default: false,
+                    ..relay
+                };
+                config::add_server(&mut config, &server)?;
+                println!(
+                    "{} Relay added: {} ({}:{}, socket {})",
+                    "✓".bright_green(),
+                    server.server_name,
+                    server.server_ip,
+                    server.http_port,
+                    server.socket_port
+                );
+            }
+        }
+    }
+
+    println!(
+        "\n The invitation proves it was made with this key, not whose key it is.\n Compare codes with {}",
+        format!("rs trust verify {}", name).bright_cyan()
+    );
+
+    Ok(())
+}
+
+/// Yes/no question on the terminal, no by default
+fn confirm(question: &str) -> Result<bool> {
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::InvalidInput(
+            "stdin is not a terminal to confirm, pass --yes once the codes match".to_string(),
+        ));
+    }
+
+    print!("{} {} [y/N] ", "?".bright_yellow().bold(), question);
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
 /// Remove a trusted contact
 pub async fn remove(name: String) -> Result<()> {
     let mut contacts = contacts::load_contacts()?;
@@ -1,41 +1,87 @@
 use anyhow::Result;
 use clap::Parser;
-use rshare::args::{health, init, listen, relays, serve, trust};
-use rshare::cli::{Args, Commands, ServerAction, TrustAction};
+#[cfg(unix)]
+use rshare::args::agent;
+use rshare::args::{health, init, key, listen, me, profile, relays, serve, trust};
+#[cfg(unix)]
+use rshare::cli::AgentAction;
+use rshare::cli::{Args, Commands, KeyAction, ProfileAction, ServerAction, TrustAction};
+use rshare::dirs;
 use rshare::utils::message::show_welcome;
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
+    if let Some(name) = &args.profile {
+        dirs::profile::select(name)?;
+    }
 
     match args.command {
-        Some(Commands::Me { .. }) => {
-            // TODO: Implement "me" command
-            //trust::show_me(verbose).await?;
+        Some(Commands::Me { name, relay, no_qr }) => {
+            me::run(name, relay, no_qr).await?;
         }
 
         Some(Commands::Health { server }) => {
             health::run(server).await?;
         }
 
-        Some(Commands::Init { keys, force }) => {
-            init::run(keys, force).await?;
+        Some(Commands::Init {
+            keys,
+            force,
+            passphrase,
+            from_seed,
+        }) => {
+            init::run(keys, force, passphrase, from_seed).await?;
         }
         Some(Commands::Listen {
             path,
             from,
             quiet,
             relay,
+            resume,
+            stdout,
+            on_conflict,
+            streams,
+            pq,
+            cipher,
         }) => {
-            listen::run(path, from, quiet, relay).await?;
+            listen::run(
+                path,
+                from,
+                quiet,
+                relay,
+                resume,
+                stdout,
+                on_conflict,
+                streams,
+                pq,
+                cipher,
+            )
+            .await?;
         }
         Some(Commands::Serve {
             file,
             to,
             quiet,
             relay,
+            stream_hash,
+            compress,
+            streams,
+            pq,
+            cipher,
         }) => {
-            serve::run(file, to, quiet, relay).await?;
+            serve::run(
+                file,
+                to,
+                quiet,
+                relay,
+                stream_hash,
+                compress,
+                streams,
+                pq,
+                cipher,
+            )
+            .await?;
         }
         Some(Commands::Relay { action }) => match action {
             ServerAction::Add {
@@ -64,6 +110,70 @@ async fn main() -> Result<()> {
             TrustAction::Remove { name } => {
                 trust::remove(name).await?;
             }
+            TrustAction::Verify { name, yes } => {
+                trust::verify(name, yes).await?;
+            }
+            TrustAction::Invite { name, relay, days } => {
+                trust::invite(name, relay, days).await?;
+            }
+            TrustAction::Accept { token, name } => {
+                trust::accept(token, name).await?;
+            }
+        },
+
+        Some(Commands::Key { action }) => match action {
+            KeyAction::Passwd { remove } => {
+                key::passwd(remove).await?;
+            }
+            KeyAction::ExportSeed => {
+                key::export_seed().await?;
+            }
+            KeyAction::Export {
+                format,
+                private,
+                output,
+            } => {
+                key::export(format, private, output).await?;
+            }
+            KeyAction::Import {
+                file,
+                force,
+                passphrase,
+            } => {
+                key::import(file, force, passphrase).await?;
+            }
+            KeyAction::Rotate => {
+                key::rotate().await?;
+            }
+        },
+
+        Some(Commands::Profile { action }) => match action {
+            ProfileAction::Create { name, passphrase } => {
+                profile::create(name, passphrase).await?;
+            }
+            ProfileAction::List => {
+                profile::list().await?;
+            }
+            ProfileAction::Use { name } => {
+                profile::switch(name).await?;
+            }
+        },
+
+        #[cfg(unix)]
+        Some(Commands::Agent { action }) => match action {
+            AgentAction::Start {
+                timeout,
+                foreground,
+                key_from_stdin,
+            } => {
+                agent::start(timeout, foreground, key_from_stdin).await?;
+            }
+            AgentAction::Stop => {
+                agent::stop().await?;
+            }
+            AgentAction::Status => {
+                agent::status().await?;
+            }
         },
         _ => {
             show_welcome().await?;
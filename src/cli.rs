@@ -1,4 +1,10 @@
-use crate::config::APP_VERSION;
+use crate::config::{
+    AGENT_DEFAULT_TIMEOUT_SECS, APP_VERSION, INVITATION_LIFETIME_DAYS,
+    MAX_INVITATION_LIFETIME_DAYS, MAX_STREAMS,
+};
+use crate::crypto::encryption::CipherSuite;
+use crate::crypto::key_format::KeyFormat;
+use crate::dirs::config::ConflictPolicy;
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
 
@@ -11,16 +17,29 @@ use std::path::PathBuf;
     It allows users to easily share files over local or public networks with encryption and access controls."
 )]
 pub struct Args {
+    /// Profile to use (default: $RSHARE_PROFILE, then the one set with `profile use`)
+    #[arg(long, global = true)]
+    pub profile: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
+    /// Show your public key and a signed identity card others can add you with
     Me {
-        /// Show your public key
-        #[arg(short, long, default_value = "false")]
-        verbose: bool,
+        /// Name shown on the card (default: your user name)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Relay to put on the card (default: your default relay)
+        #[arg(short, long)]
+        relay: Option<String>,
+
+        /// Do not draw the card as a QR code
+        #[arg(long, default_value = "false")]
+        no_qr: bool,
     },
 
     Health {
@@ -31,13 +50,21 @@ pub enum Commands {
 
     /// Initialize and generate a public/private key
     Init {
-        /// Save keys to custom path, or default to ~/.rshare/keys/
+        /// Save keys to custom path, or default to keys/ in the profile (~/.rshare/keys/)
         #[arg(short, long)]
         keys: Option<PathBuf>,
 
         /// Overwrite existing keys and config file if they exist
         #[arg(short, long, default_value = "false")]
         force: bool,
+
+        /// Encrypt the private key with a passphrase (prompted, or $RSHARE_PASSPHRASE / $RSHARE_PASSPHRASE_FD)
+        #[arg(long, default_value = "false")]
+        passphrase: bool,
+
+        /// Restore the key from its seed phrase (prompted, or $RSHARE_SEED_PHRASE / $RSHARE_SEED_PHRASE_FD)
+        #[arg(long, default_value = "false")]
+        from_seed: bool,
     },
 
     Listen {
@@ -56,12 +83,36 @@ pub enum Commands {
         /// Enable progress bars
         #[arg(short, long, default_value = "false")]
         quiet: bool,
+
+        /// Keep partial downloads and resume them from the last checkpoint
+        #[arg(long, default_value = "false")]
+        resume: bool,
+
+        /// What to do when a received file already exists (default from config: rename)
+        #[arg(long, value_enum)]
+        on_conflict: Option<ConflictPolicy>,
+
+        /// Write the received file to stdout (status output goes to stderr)
+        #[arg(long, default_value = "false", conflicts_with_all = ["resume", "path"])]
+        stdout: bool,
+
+        /// Parallel relay connections to use, if the relay supports them
+        #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..=MAX_STREAMS as i64))]
+        streams: u32,
+
+        /// Require a hybrid post-quantum key exchange (X25519 + ML-KEM-768)
+        #[arg(long, default_value = "false")]
+        pq: bool,
+
+        /// Require a cipher suite (default: negotiated, AES-256-GCM with hardware AES, else ChaCha20-Poly1305)
+        #[arg(long, value_enum)]
+        cipher: Option<CipherSuite>,
     },
 
     Serve {
-        /// File(s) or directory to send
-        #[arg(short, long, required = true)]
-        file: PathBuf,
+        /// File(s) or directory to send, or - to read from stdin
+        #[arg(short, long, required = true, num_args = 1..)]
+        file: Vec<PathBuf>,
 
         /// Send to trusted contact by name
         #[arg(short, long, required = true)]
@@ -74,6 +125,26 @@ pub enum Commands {
         /// Enable progress bars
         #[arg(short, long, default_value = "false")]
         quiet: bool,
+
+        /// Hash while sending and deliver a signed hash trailer instead of pre-hashing
+        #[arg(long, default_value = "false")]
+        stream_hash: bool,
+
+        /// Compress chunks with zstd when the receiver supports it
+        #[arg(short, long, default_value = "false")]
+        compress: bool,
+
+        /// Parallel relay connections to use, if the relay supports them
+        #[arg(long, default_value = "1", value_parser = clap::value_parser!(u32).range(1..=MAX_STREAMS as i64))]
+        streams: u32,
+
+        /// Require a hybrid post-quantum key exchange (X25519 + ML-KEM-768)
+        #[arg(long, default_value = "false")]
+        pq: bool,
+
+        /// Require a cipher suite (default: negotiated, AES-256-GCM with hardware AES, else ChaCha20-Poly1305)
+        #[arg(long, value_enum)]
+        cipher: Option<CipherSuite>,
     },
 
     /// Manage relay servers
@@ -88,6 +159,24 @@ pub enum Commands {
         action: TrustAction,
     },
 
+    /// Manage your identity key
+    Key {
+        #[command(subcommand)]
+        action: KeyAction,
+    },
+
+    /// Keep the unlocked key in a background agent (Unix only)
+    Agent {
+        #[command(subcommand)]
+        action: AgentAction,
+    },
+
+    /// Manage separate identities, each with its own keys, contacts and relays
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+
     /// View transfer history
     History {
         /// Show last N transfers
@@ -136,11 +225,11 @@ pub enum ServerAction {
 pub enum TrustAction {
     /// Add a trusted contact
     Add {
-        /// Contact name
-        #[arg(short, long, required = true)]
-        name: String,
+        /// Contact name (default: the name on the identity card)
+        #[arg(short, long)]
+        name: Option<String>,
 
-        /// Public key (hex string)
+        /// Public key: hex, an `ssh-ed25519 AAAA...` line, a PEM public key or an identity card
         #[arg(short, long, required = true)]
         key: String,
     },
@@ -157,4 +246,130 @@ pub enum TrustAction {
         /// Contact name
         name: String,
     },
+
+    /// Compare a short code with a contact to check their key, then mark them verified
+    Verify {
+        /// Contact name
+        name: String,
+
+        /// Mark verified without asking (only after comparing the codes)
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Print a signed invitation others accept to add you and your relay in one step
+    Invite {
+        /// Name the invitation introduces you with (default: your user name)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Relay to put in the invitation (default: your default relay)
+        #[arg(short, long)]
+        relay: Option<String>,
+
+        /// Days the invitation stays valid
+        #[arg(short, long, default_value_t = INVITATION_LIFETIME_DAYS, value_parser = clap::value_parser!(u64).range(1..=MAX_INVITATION_LIFETIME_DAYS))]
+        days: u64,
+    },
+
+    /// Add a contact and their relay from an invitation
+    Accept {
+        /// Invitation token (rshare-invite:...)
+        token: String,
+
+        /// Contact name (default: the name in the invitation)
+        #[arg(short, long)]
+        name: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum KeyAction {
+    /// Change the passphrase of the private key, or set one
+    Passwd {
+        /// Store the private key without a passphrase
+        #[arg(long)]
+        remove: bool,
+    },
+
+    /// Print the seed phrase that restores the private key with `init --from-seed`
+    ExportSeed,
+
+    /// Export the public key (or the private key) for use with other tools
+    Export {
+        /// Output format
+        #[arg(short, long, value_enum, default_value = "openssh")]
+        format: KeyFormat,
+
+        /// Export the private key, unencrypted
+        #[arg(long)]
+        private: bool,
+
+        /// Write to a file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Use an existing Ed25519 private key (OpenSSH, PKCS#8 PEM or hex) as your identity
+    Import {
+        /// Key file, or "-" to read it from stdin
+        file: PathBuf,
+
+        /// Replace the current keys
+        #[arg(short, long)]
+        force: bool,
+
+        /// Encrypt the imported key with a passphrase (prompted, or $RSHARE_PASSPHRASE / $RSHARE_PASSPHRASE_FD)
+        #[arg(long)]
+        passphrase: bool,
+    },
+
+    /// Replace the key pair, contacts follow a statement signed by both keys
+    Rotate,
+}
+
+#[derive(Subcommand)]
+pub enum AgentAction {
+    /// Unlock the key and start the agent in the background
+    Start {
+        /// Forget the key and exit after this many seconds
+        #[arg(short, long, default_value_t = AGENT_DEFAULT_TIMEOUT_SECS)]
+        timeout: u64,
+
+        /// Stay in the foreground instead of detaching
+        #[arg(long)]
+        foreground: bool,
+
+        /// Read the unlocked key from stdin (used by the detaching `start`)
+        #[arg(long, hide = true)]
+        key_from_stdin: bool,
+    },
+
+    /// Make the agent forget the key and exit
+    Stop,
+
+    /// Show whether an agent is running and which key it holds
+    Status,
+}
+
+#[derive(Subcommand)]
+pub enum ProfileAction {
+    /// Create a profile and initialize its keys and config
+    Create {
+        /// Profile name (letters, digits, '-' and '_')
+        name: String,
+
+        /// Protect the new private key with a passphrase
+        #[arg(long)]
+        passphrase: bool,
+    },
+
+    /// List profiles, marking the active one
+    List,
+
+    /// Make a profile the default for later commands
+    Use {
+        /// Profile name ("default" for ~/.rshare)
+        name: String,
+    },
 }
@@ -6,6 +6,24 @@ pub const FILE_CHUNK_SIZE: usize = 2 * 1024 * 1024;
 /// Size of chunks when computing file hashes (4MB)
 pub const HASH_CHUNK_SIZE: usize = 4 * 1024 * 1024;
 
+/// Environment variable selecting the profile (`--profile` takes precedence)
+pub const PROFILE_ENV: &str = "RSHARE_PROFILE";
+
+/// Profile living directly in ~/.rshare, the only one before profiles existed
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// Directory under ~/.rshare holding the other profiles, one directory each
+pub const PROFILES_DIR: &str = "profiles";
+
+/// Config file in each profile directory
+pub const CONFIG_FILE: &str = "config.toml";
+
+/// File under ~/.rshare naming the profile chosen with `rs profile use`
+pub const ACTIVE_PROFILE_FILE: &str = "active_profile";
+
+/// Maximum size of a single length-prefixed frame on the socket (16MB)
+pub const MAX_FRAME_SIZE: usize = 16 * 1024 * 1024;
+
 /// Buffer size for network transfers (8MB)
 pub const BUFFER_SIZE: usize = 8 * 1024 * 1024;
 
@@ -51,12 +69,78 @@ pub const SHA256_HASH_LEN: usize = 32;
 /// Number of hex characters to display for key fingerprints
 pub const KEY_FINGERPRINT_DISPLAY_LEN: usize = 16;
 
+/// Magic at the start of a passphrase-protected private key file
+pub const KEY_FILE_MAGIC: &[u8] = b"rshare-private-key";
+
+/// Current version of the passphrase-protected key file format
+pub const KEY_FILE_VERSION: u8 = 1;
+
+/// Argon2id memory cost for new key files (KiB)
+pub const KEY_FILE_MEMORY_KIB: u32 = 64 * 1024;
+
+/// Argon2id passes for new key files
+pub const KEY_FILE_ITERATIONS: u32 = 3;
+
+/// Argon2id lanes for new key files
+pub const KEY_FILE_PARALLELISM: u32 = 1;
+
+/// Upper bounds accepted when reading a key file, so a crafted file can't exhaust memory
+pub const KEY_FILE_MAX_MEMORY_KIB: u32 = 1024 * 1024;
+pub const KEY_FILE_MAX_ITERATIONS: u32 = 64;
+pub const KEY_FILE_MAX_PARALLELISM: u32 = 16;
+
+/// Environment variable holding the passphrase of the private key
+pub const PASSPHRASE_ENV: &str = "RSHARE_PASSPHRASE";
+
+/// Environment variable naming a file descriptor to read the passphrase from
+pub const PASSPHRASE_FD_ENV: &str = "RSHARE_PASSPHRASE_FD";
+
+/// Same as above for the new passphrase of `rs key passwd`
+pub const NEW_PASSPHRASE_ENV: &str = "RSHARE_NEW_PASSPHRASE";
+pub const NEW_PASSPHRASE_FD_ENV: &str = "RSHARE_NEW_PASSPHRASE_FD";
+
+/// Comment on keys exported in OpenSSH format
+pub const KEY_EXPORT_COMMENT: &str = "rshare";
+
+/// Words in a seed phrase: 256 bits of secret key plus an 8-bit checksum (BIP39)
+pub const SEED_PHRASE_WORDS: usize = 24;
+
+/// Same as above for the seed phrase of `rs init --from-seed`
+pub const SEED_PHRASE_ENV: &str = "RSHARE_SEED_PHRASE";
+pub const SEED_PHRASE_FD_ENV: &str = "RSHARE_SEED_PHRASE_FD";
+
+/// Environment variable overriding the key agent socket path
+pub const AGENT_SOCKET_ENV: &str = "RSHARE_AGENT_SOCK";
+
+/// Key agent socket, inside an owner-only directory of the profile
+pub const AGENT_DIR: &str = "agent";
+pub const AGENT_SOCKET_FILE: &str = "agent.sock";
+
+/// How long the agent keeps the key unlocked by default (seconds)
+pub const AGENT_DEFAULT_TIMEOUT_SECS: u64 = 60 * 60;
+
+/// Maximum length of one agent request or response line
+pub const AGENT_MAX_MESSAGE_LEN: usize = 64 * 1024;
+
+/// Maximum time to wait for the agent to answer (milliseconds)
+pub const AGENT_RESPONSE_TIMEOUT_MILLIS: u64 = 5_000;
+
+/// Line the detached agent prints once its socket is listening
+pub const AGENT_READY_SIGNAL: &str = "READY";
+
 // UI/Display Constants
 
 /// Progress bar template
 pub const PROGRESS_BAR_TEMPLATE: &str =
     "{spinner:.green} |{bar:40.magenta/purple}| ([{percent}%] / [{bytes_per_sec}] / [{elapsed}])";
 
+/// Progress bar template for compressed transfers (raw and wire bytes in the prefix)
+pub const PROGRESS_BAR_WIRE_TEMPLATE: &str = "{spinner:.green} |{bar:40.magenta/purple}| ([{percent}%] / [{bytes_per_sec}] / [{elapsed}]) {prefix}";
+
+/// Progress template when the total size is unknown (piped input)
+pub const PROGRESS_SPINNER_TEMPLATE: &str =
+    "{spinner:.green} ([{bytes}] / [{bytes_per_sec}] / [{elapsed}]) {prefix}";
+
 /// Progress bar characters
 pub const PROGRESS_BAR_CHARS: &str = "░▒▓█";
 
@@ -71,8 +155,8 @@ pub const APP_VERSION: &str = "1.0.0-beta";
 /// Protocol delimiter for socket messages
 pub const PROTOCOL_DELIMITER: &str = "\n";
 
-/// DONE signal sent by receiver after successful transfer
-pub const DONE_SIGNAL: &[u8] = b"DONE\n";
+/// DONE control message sent by receiver after successful transfer
+pub const DONE_MESSAGE: &str = "DONE";
 
 /// READY signal sent by receiver when ready to receive
 pub const READY_SIGNAL: &[u8] = b"READY\n";
@@ -80,6 +164,166 @@ pub const READY_SIGNAL: &[u8] = b"READY\n";
 /// ACK signal sent by sender acknowledging READY
 pub const ACK_SIGNAL: &[u8] = b"ACK\n";
 
+/// Version of the socket protocol spoken after pairing
+pub const PROTOCOL_VERSION: u32 = 4;
+
+/// HELLO message prefix, sent by receiver with its transfer parameters
+pub const HELLO_SIGNAL_PREFIX: &str = "HELLO:";
+
+/// ACCEPT message prefix, sent by sender to confirm the parameters before data flows
+pub const ACCEPT_SIGNAL_PREFIX: &str = "ACCEPT:";
+
+/// Domain separation label of the signed ephemeral key message
+pub const EPHEMERAL_KEY_CONTEXT: &str = "rshare-ephemeral-key-v1";
+
+/// Handshake name advertised in HELLO and selected in ACCEPT for the Noise session setup
+pub const HANDSHAKE_NOISE_IK: &str = "noise-ik";
+
+/// Noise protocol used for the handshake between identity keys
+pub const NOISE_PARAMS: &str = "Noise_IK_25519_ChaChaPoly_SHA256";
+
+/// Domain separation label at the start of the Noise prologue
+pub const NOISE_PROLOGUE_LABEL: &str = "rshare-noise-v1";
+
+/// Maximum size of a Noise handshake message
+pub const NOISE_MAX_MESSAGE_LEN: usize = 65535;
+
+/// NOISE message prefix, carries one hex-encoded handshake message
+pub const NOISE_SIGNAL_PREFIX: &str = "NOISE:";
+
+/// Post-quantum KEM of the hybrid key exchange
+pub const KEM_ALGORITHM: &str = "ML-KEM-768";
+
+/// Key schedule versions this build can run, newest first
+pub const KEY_SCHEDULE_VERSIONS: &[u32] = &[2, 1];
+
+/// Cipher suite names advertised in the Noise handshake
+pub const CIPHER_AES_256_GCM: &str = "AES-256-GCM";
+pub const CIPHER_CHACHA20_POLY1305: &str = "ChaCha20-Poly1305";
+
+/// HKDF info prefix of the key schedule outputs (followed by version and purpose)
+pub const KEY_SCHEDULE_LABEL: &str = "rshare-key-schedule";
+
+/// Separator between a control message and its MAC tag
+pub const CONTROL_TAG_SEPARATOR: char = '|';
+
+/// Bytes of the HMAC kept in a control message tag
+pub const CONTROL_TAG_LEN: usize = 16;
+
+/// Bytes of the confirmation value shown to the users
+pub const CONFIRMATION_CODE_LEN: usize = 6;
+
+/// Maximum time to wait for the peer's HELLO/ACCEPT message (milliseconds)
+pub const NEGOTIATION_TIMEOUT_MILLIS: u64 = 10_000;
+
+/// Maximum length of a single control line on the socket
+pub const MAX_CONTROL_LINE_LEN: usize = 64 * 1024;
+
+/// Compression codec advertised in HELLO and selected in ACCEPT
+pub const COMPRESSION_ZSTD: &str = "zstd";
+
+/// zstd compression level for file chunks (favours speed)
+pub const ZSTD_LEVEL: i32 = 3;
+
+/// Maximum number of parallel data streams per session (`--streams`)
+pub const MAX_STREAMS: u32 = 8;
+
+/// Frames queued per session (sender) or per stream (receiver) while striping
+pub const STRIPE_QUEUE_FRAMES: usize = 4;
+
+/// Suffix of the temporary file a download is written to until it is verified
+pub const PART_SUFFIX: &str = ".rshare-part";
+
+/// Suffix of the sidecar checkpoint kept next to a partial download
+pub const CHECKPOINT_SUFFIX: &str = ".rshare-resume";
+
+/// Number of received chunks between checkpoint updates (32MB with 2MB chunks)
+pub const CHECKPOINT_INTERVAL_CHUNKS: u64 = 16;
+
+/// Prefix of the signed file hash for multi-file transfers (hash of the manifest)
+pub const MANIFEST_HASH_PREFIX: &str = "manifest:";
+
+/// Prefix of the signed file hash for single files (Merkle root over FILE_CHUNK_SIZE blocks)
+pub const MERKLE_HASH_PREFIX: &str = "merkle:";
+
+/// Signed file hash placeholder when the real hash follows in a signed trailer
+pub const HASH_TRAILER_MARKER: &str = "trailer";
+
+/// Signed file hash placeholder for piped input of unknown length
+/// (the size and hash follow in a signed trailer)
+pub const PIPE_HASH_MARKER: &str = "pipe";
+
+/// Name announced for data read from stdin
+pub const STDIN_FILENAME: &str = "stdin";
+
+/// Version of the signed transfer offer encoding
+pub const OFFER_VERSION: u8 = 1;
+
+/// Domain separation label at the start of an encoded offer
+pub const OFFER_MAGIC: &str = "rshare-offer";
+
+/// How long a new offer stays valid (seconds)
+pub const OFFER_LIFETIME_SECS: u64 = 10 * 60;
+
+/// Longest lifetime a receiver accepts in an offer (seconds)
+pub const MAX_OFFER_LIFETIME_SECS: u64 = 60 * 60;
+
+/// Clock difference tolerated between sender and receiver (seconds)
+pub const OFFER_CLOCK_SKEW_SECS: u64 = 5 * 60;
+
+/// Maximum number of offer ids remembered for replay protection
+pub const MAX_SEEN_OFFERS: usize = 1024;
+
+/// Version of the key rotation statement
+pub const KEY_ROTATION_VERSION: u32 = 1;
+
+/// Domain separation label of the message both keys sign in a rotation statement
+pub const KEY_ROTATION_CONTEXT: &str = "rshare-key-rotation";
+
+/// Rotation statements a sender presents, most recent ones (bounds the receiver's work)
+pub const MAX_KEY_ROTATIONS: usize = 16;
+
+/// File in the keys directory holding our own rotation statements
+pub const KEY_ROTATIONS_FILE: &str = "rotations.json";
+
+/// Domain separation label of the short authentication string hash
+pub const SAS_CONTEXT: &str = "rshare-sas";
+
+/// Short authentication string layout: groups of digits per key, read out to a contact
+pub const SAS_GROUPS: usize = 4;
+pub const SAS_GROUP_DIGITS: usize = 5;
+
+/// SHA-256 iterations behind each key's half of the code (slows down key grinding)
+pub const SAS_ITERATIONS: usize = 5200;
+
+/// Version of the signed identity card encoding
+pub const IDENTITY_CARD_VERSION: u8 = 1;
+
+/// Domain separation label at the start of an encoded identity card
+pub const IDENTITY_CARD_MAGIC: &str = "rshare-card";
+
+/// Prefix of the copy-pasteable identity card string
+pub const IDENTITY_CARD_PREFIX: &str = "rshare-card:";
+
+/// Longest display name carried in an identity card (bytes)
+pub const MAX_CARD_NAME_LEN: usize = 64;
+
+/// Version of the signed contact invitation encoding
+pub const INVITATION_VERSION: u8 = 1;
+
+/// Domain separation label at the start of an encoded invitation
+pub const INVITATION_MAGIC: &str = "rshare-invite";
+
+/// Prefix of the copy-pasteable invitation token
+pub const INVITATION_PREFIX: &str = "rshare-invite:";
+
+/// How long a new invitation stays valid by default, and at most (days)
+pub const INVITATION_LIFETIME_DAYS: u64 = 7;
+pub const MAX_INVITATION_LIFETIME_DAYS: u64 = 90;
+
+/// Clock difference tolerated when checking an invitation (seconds)
+pub const INVITATION_CLOCK_SKEW_SECS: u64 = 5 * 60;
+
 /// Error signal prefix
 pub const ERROR_SIGNAL_PREFIX: &str = "ERROR:";
 
@@ -91,5 +335,8 @@ pub const MAX_DONE_WAIT_MILLIS: u64 = 100;
 /// Maximum length for contact name
 pub const MAX_CONTACT_NAME_LEN: usize = 50;
 
+/// Maximum length for profile name
+pub const MAX_PROFILE_NAME_LEN: usize = 32;
+
 /// Maximum length for filename (for validation)
 pub const MAX_FILENAME_LEN: usize = 255;
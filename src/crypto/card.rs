@@ -0,0 +1,375 @@
+use crate::config::{
+    IDENTITY_CARD_MAGIC, IDENTITY_CARD_PREFIX, IDENTITY_CARD_VERSION, INVITATION_CLOCK_SKEW_SECS,
+    INVITATION_MAGIC, INVITATION_PREFIX, INVITATION_VERSION, MAX_CARD_NAME_LEN,
+    MAX_INVITATION_LIFETIME_DAYS,
+};
+use crate::crypto::signing;
+use crate::dirs::config::ServerConfig;
+use crate::utils::error::{Error, Result};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::{Signature, Signer, VerifyingKey};
+
+const SIGNATURE_LEN: usize = 64;
+const SECS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// Identity card a user hands out so others can add them as a contact in one step
+///
+/// Self-signed: the signature shows the card was made by the holder of `public_key`
+/// and that the name and relay were not changed on the way. It says nothing about
+/// who that holder is, which is still for the reader to check.
+///
+/// Canonical encoding, integers big-endian, strings as u32 length + UTF-8:
+/// `IDENTITY_CARD_MAGIC, version u8, issued_at u64, public_key [32], name,
+/// has_relay u8, [server_name, server_ip, http_port u16, socket_port u16]`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdentityCard {
+    pub version: u8,
+    /// Unix timestamp (seconds)
+    pub issued_at: u64,
+    pub public_key: VerifyingKey,
+    pub name: String,
+    /// Relay the owner can be reached through (`default` is not carried)
+    pub relay: Option<ServerConfig>,
+}
+
+impl IdentityCard {
+    pub fn new(
+        public_key: VerifyingKey,
+        name: &str,
+        relay: Option<ServerConfig>,
+        issued_at: u64,
+    ) -> Result<Self> {
+        validate_name(name)?;
+        Ok(IdentityCard {
+            version: IDENTITY_CARD_VERSION,
+            issued_at,
+            public_key,
+            name: name.to_string(),
+            relay,
+        })
+    }
+
+    /// Canonical encoding, the exact bytes that are signed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = IDENTITY_CARD_MAGIC.as_bytes().to_vec();
+        bytes.push(self.version);
+        bytes.extend_from_slice(&self.issued_at.to_be_bytes());
+        bytes.extend_from_slice(self.public_key.as_bytes());
+        put_string(&mut bytes, &self.name);
+
+        match &self.relay {
+            Some(relay) => {
+                bytes.push(1);
+                put_string(&mut bytes, &relay.server_name);
+                put_string(&mut bytes, &relay.server_ip);
+                bytes.extend_from_slice(&relay.http_port.to_be_bytes());
+                bytes.extend_from_slice(&relay.socket_port.to_be_bytes());
+            }
+            None => bytes.push(0),
+        }
+        bytes
+    }
+
+    /// Decode the canonical encoding, rejecting unknown versions and trailing bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+        let card = IdentityCard::read(&mut reader)?;
+
+        if !reader.bytes.is_empty() {
+            return Err(invalid("trailing bytes"));
+        }
+        Ok(card)
+    }
+
+    fn read(reader: &mut Reader) -> Result<Self> {
+        if reader.take(IDENTITY_CARD_MAGIC.len())? != IDENTITY_CARD_MAGIC.as_bytes() {
+            return Err(invalid("not an identity card"));
+        }
+        let version = reader.take(1)?[0];
+        if version != IDENTITY_CARD_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "Unsupported identity card version {} (we speak v{})",
+                version, IDENTITY_CARD_VERSION
+            )));
+        }
+
+        let issued_at = reader.u64()?;
+        let key_bytes: [u8; 32] = reader
+            .take(32)?
+            .try_into()
+            .map_err(|_e| invalid("public key"))?;
+        let public_key =
+            VerifyingKey::from_bytes(&key_bytes).map_err(|_e| invalid("public key"))?;
+        let name = reader.string()?;
+        validate_name(&name)?;
+
+        let relay = match reader.take(1)?[0] {
+            0 => None,
+            1 => Some(ServerConfig {
+                server_name: reader.string()?,
+                default: false,
+                server_ip: reader.string()?,
+                http_port: reader.u16()?,
+                socket_port: reader.u16()?,
+            }),
+            _ => return Err(invalid("relay flag")),
+        };
+
+        Ok(IdentityCard {
+            version,
+            issued_at,
+            public_key,
+            name,
+            relay,
+        })
+    }
+
+    /// Sign with the identity key and encode as `rshare-card:<base64url>`
+    ///
+    /// The signer must hold the key the card is about.
+    pub fn sign(&self, signer: &impl Signer<Signature>) -> Result<String> {
+        seal(
+            IDENTITY_CARD_PREFIX,
+            self.to_bytes(),
+            &self.public_key,
+            signer,
+        )
+    }
+
+    /// Decode a card string and check its signature against the key it carries
+    pub fn parse(text: &str) -> Result<Self> {
+        let (bytes, signature) = unseal(IDENTITY_CARD_PREFIX, text)?;
+        let card = IdentityCard::from_bytes(&bytes)?;
+        signing::verify_bytes(&card.public_key, &bytes, &signature)
+            .map_err(|_e| Error::InvalidInput("Identity card signature is invalid".to_string()))?;
+        Ok(card)
+    }
+
+    /// Whether `text` looks like a card string rather than a bare key
+    pub fn is_card(text: &str) -> bool {
+        text.trim().starts_with(IDENTITY_CARD_PREFIX)
+    }
+
+    pub fn fingerprint(&self) -> String {
+        hex::encode(self.public_key.to_bytes())
+    }
+}
+
+/// Identity card with an expiry, handed to someone to add us as a contact with our relay
+///
+/// Signed like the card, under its own label, so a card can't be passed off as an
+/// invitation or kept past its expiry.
+///
+/// Canonical encoding: `INVITATION_MAGIC, version u8, expires_at u64, card`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Invitation {
+    pub version: u8,
+    /// Unix timestamp (seconds)
+    pub expires_at: u64,
+    pub card: IdentityCard,
+}
+
+impl Invitation {
+    /// Invitation valid for `lifetime_days` from the card's issue time
+    pub fn new(card: IdentityCard, lifetime_days: u64) -> Result<Self> {
+        if lifetime_days == 0 || lifetime_days > MAX_INVITATION_LIFETIME_DAYS {
+            return Err(Error::InvalidInput(format!(
+                "Invitations are valid for 1-{} days",
+                MAX_INVITATION_LIFETIME_DAYS
+            )));
+        }
+
+        Ok(Invitation {
+            version: INVITATION_VERSION,
+            expires_at: card.issued_at + lifetime_days * SECS_PER_DAY,
+            card,
+        })
+    }
+
+    /// Canonical encoding, the exact bytes that are signed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = INVITATION_MAGIC.as_bytes().to_vec();
+        bytes.push(self.version);
+        bytes.extend_from_slice(&self.expires_at.to_be_bytes());
+        bytes.extend_from_slice(&self.card.to_bytes());
+        bytes
+    }
+
+    /// Decode the canonical encoding, rejecting unknown versions and trailing bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(INVITATION_MAGIC.len())? != INVITATION_MAGIC.as_bytes() {
+            return Err(invalid("not an invitation"));
+        }
+        let version = reader.take(1)?[0];
+        if version != INVITATION_VERSION {
+            return Err(Error::InvalidInput(format!(
+                "Unsupported invitation version {} (we speak v{})",
+                version, INVITATION_VERSION
+            )));
+        }
+
+        let expires_at = reader.u64()?;
+        let card = IdentityCard::read(&mut reader)?;
+        if !reader.bytes.is_empty() {
+            return Err(invalid("trailing bytes"));
+        }
+        Ok(Invitation {
+            version,
+            expires_at,
+            card,
+        })
+    }
+
+    /// Sign with the identity key and encode as `rshare-invite:<base64url>`
+    pub fn sign(&self, signer: &impl Signer<Signature>) -> Result<String> {
+        seal(
+            INVITATION_PREFIX,
+            self.to_bytes(),
+            &self.card.public_key,
+            signer,
+        )
+    }
+
+    /// Decode a token, check its signature and that it is still valid at `now`
+    pub fn parse(text: &str, now: u64) -> Result<Self> {
+        let (bytes, signature) = unseal(INVITATION_PREFIX, text)?;
+        let invitation = Invitation::from_bytes(&bytes)?;
+        signing::verify_bytes(&invitation.card.public_key, &bytes, &signature)
+            .map_err(|_e| Error::InvalidInput("Invitation signature is invalid".to_string()))?;
+
+        let issued_at = invitation.card.issued_at;
+        if invitation.expires_at <= issued_at
+            || invitation.expires_at - issued_at > MAX_INVITATION_LIFETIME_DAYS * SECS_PER_DAY
+        {
+            return Err(Error::InvalidInput(
+                "Invitation has an invalid validity window".to_string(),
+            ));
+        }
+        if issued_at > now + INVITATION_CLOCK_SKEW_SECS {
+            return Err(Error::InvalidInput(
+                "Invitation was issued in the future (check the clocks)".to_string(),
+            ));
+        }
+        if now > invitation.expires_at + INVITATION_CLOCK_SKEW_SECS {
+            return Err(Error::InvalidInput(
+                "Invitation has expired, ask for a new one".to_string(),
+            ));
+        }
+        Ok(invitation)
+    }
+
+    /// Whether `text` looks like an invitation token
+    pub fn is_invitation(text: &str) -> bool {
+        text.trim().starts_with(INVITATION_PREFIX)
+    }
+}
+
+/// Sign `bytes` and encode them followed by the signature as `<prefix><base64url>`
+///
+/// The signer must hold `public_key`, the key readers check the signature against.
+fn seal(
+    prefix: &str,
+    mut bytes: Vec<u8>,
+    public_key: &VerifyingKey,
+    signer: &impl Signer<Signature>,
+) -> Result<String> {
+    let signature = signing::sign_bytes(signer, &bytes)?;
+    signing::verify_bytes(public_key, &bytes, &signature).map_err(|_e| {
+        Error::CryptoError("Identity card must be signed by its own key".to_string())
+    })?;
+
+    bytes.extend_from_slice(&signature.to_bytes());
+    Ok(format!("{}{}", prefix, URL_SAFE_NO_PAD.encode(bytes)))
+}
+
+/// Split `<prefix><base64url>` into the signed bytes and the signature
+fn unseal(prefix: &str, text: &str) -> Result<(Vec<u8>, Signature)> {
+    let encoded = text
+        .trim()
+        .strip_prefix(prefix)
+        .ok_or_else(|| invalid("missing prefix"))?;
+    let mut bytes = URL_SAFE_NO_PAD
+        .decode(encoded)
+        .map_err(|_e| invalid("not base64url"))?;
+    if bytes.len() < SIGNATURE_LEN {
+        return Err(invalid("truncated"));
+    }
+
+    let signature_bytes = bytes.split_off(bytes.len() - SIGNATURE_LEN);
+    let signature = Signature::from_slice(&signature_bytes).map_err(|_e| invalid("signature"))?;
+    Ok((bytes, signature))
+}
+
+/// Card names become contact names and are printed as is: letters, digits, `-`, `_` and `.`
+///
+/// Rules out control, bidi and invisible characters from a remote card, and `self`,
+/// the contact holding our own key.
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_CARD_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'));
+
+    if !valid {
+        return Err(Error::InvalidInput(format!(
+            "Invalid card name '{}' (1-{} letters, digits, '-', '_' or '.')",
+            name.escape_default(),
+            MAX_CARD_NAME_LEN
+        )));
+    }
+    if name.eq_ignore_ascii_case("self") {
+        return Err(Error::InvalidInput(
+            "Card name 'self' is reserved for your own key".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+fn put_string(bytes: &mut Vec<u8>, text: &str) {
+    bytes.extend_from_slice(&(text.len() as u32).to_be_bytes());
+    bytes.extend_from_slice(text.as_bytes());
+}
+
+fn invalid(what: &str) -> Error {
+    Error::InvalidInput(format!("Malformed identity card: {}", what))
+}
+
+/// Cursor over an encoded card or invitation
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid("truncated"));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_be_bytes(
+            bytes.try_into().map_err(|_e| invalid("integer"))?,
+        ))
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_be_bytes(
+            bytes.try_into().map_err(|_e| invalid("integer"))?,
+        ))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = u32::from_be_bytes(self.take(4)?.try_into().map_err(|_e| invalid("length"))?);
+        let bytes = self.take(len as usize)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_e| invalid("text is not UTF-8"))
+    }
+}
@@ -1,10 +1,154 @@
+use crate::config::{CIPHER_AES_256_GCM, CIPHER_CHACHA20_POLY1305};
 use crate::utils::error::{Error, Result};
 use aes_gcm::aead::OsRng;
 use aes_gcm::aead::rand_core::RngCore;
 use aes_gcm::{
     Aes256Gcm, Nonce,
-    aead::{Aead, KeyInit},
+    aead::{Aead, KeyInit, Payload},
 };
+use chacha20poly1305::ChaCha20Poly1305;
+use sha2::{Digest, Sha256};
+
+/// Domain separation label for the chunk stream (nonce prefix and AAD)
+const STREAM_LABEL: &[u8] = b"rshare-stream-v1";
+
+/// Chunk flag: more chunks follow
+const FLAG_MORE: u8 = 0x00;
+
+/// Chunk flag: last chunk of the stream
+const FLAG_FINAL: u8 = 0x01;
+
+/// AEAD protecting the chunk stream, chosen per session
+///
+/// Both suites take a 256-bit key, a 96-bit nonce and produce a 16-byte tag, so the
+/// stream framing is the same whichever one is negotiated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum CipherSuite {
+    /// AES-256-GCM, fastest with hardware AES support
+    #[value(name = "aes-256-gcm")]
+    Aes256Gcm,
+    /// ChaCha20-Poly1305, fast in software on any CPU
+    #[value(name = "chacha20-poly1305")]
+    ChaCha20Poly1305,
+}
+
+impl CipherSuite {
+    pub const ALL: [CipherSuite; 2] = [CipherSuite::Aes256Gcm, CipherSuite::ChaCha20Poly1305];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CipherSuite::Aes256Gcm => CIPHER_AES_256_GCM,
+            CipherSuite::ChaCha20Poly1305 => CIPHER_CHACHA20_POLY1305,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        CipherSuite::ALL
+            .into_iter()
+            .find(|suite| suite.as_str() == name)
+    }
+
+    /// One-byte identifier used in the Noise reply
+    pub fn id(&self) -> u8 {
+        match self {
+            CipherSuite::Aes256Gcm => 1,
+            CipherSuite::ChaCha20Poly1305 => 2,
+        }
+    }
+
+    pub fn from_id(id: u8) -> Option<Self> {
+        CipherSuite::ALL.into_iter().find(|suite| suite.id() == id)
+    }
+
+    /// Local preference, fastest first: AES-256-GCM leads only on CPUs with AES
+    /// instructions
+    ///
+    /// A `required` suite (from `--cipher`) is the only one offered, so the session
+    /// uses it or fails, the same as on the legacy setup.
+    pub fn preferred(required: Option<CipherSuite>) -> Vec<CipherSuite> {
+        match required {
+            Some(suite) => vec![suite],
+            None if has_aes_acceleration() => {
+                vec![CipherSuite::Aes256Gcm, CipherSuite::ChaCha20Poly1305]
+            }
+            None => vec![CipherSuite::ChaCha20Poly1305, CipherSuite::Aes256Gcm],
+        }
+    }
+
+    /// Pick the suite for a session from both peers' preference lists
+    ///
+    /// Agreeing first choices win. Otherwise one peer is likely missing AES instructions,
+    /// and ChaCha20-Poly1305 costs the other peer far less than software AES costs it.
+    pub fn negotiate(ours: &[CipherSuite], theirs: &[CipherSuite]) -> Option<CipherSuite> {
+        let common: Vec<CipherSuite> = ours
+            .iter()
+            .copied()
+            .filter(|suite| theirs.contains(suite))
+            .collect();
+
+        match (ours.first(), theirs.first()) {
+            (Some(a), Some(b)) if a == b && common.contains(a) => Some(*a),
+            _ if common.contains(&CipherSuite::ChaCha20Poly1305) => {
+                Some(CipherSuite::ChaCha20Poly1305)
+            }
+            _ => common.first().copied(),
+        }
+    }
+}
+
+impl std::fmt::Display for CipherSuite {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+fn has_aes_acceleration() -> bool {
+    #[cfg(target_arch = "x86_64")]
+    {
+        std::arch::is_x86_feature_detected!("aes")
+            && std::arch::is_x86_feature_detected!("pclmulqdq")
+    }
+    #[cfg(target_arch = "aarch64")]
+    {
+        std::arch::is_aarch64_feature_detected!("aes")
+            && std::arch::is_aarch64_feature_detected!("pmull")
+    }
+    #[cfg(not(any(target_arch = "x86_64", target_arch = "aarch64")))]
+    {
+        false
+    }
+}
+
+/// Keyed AEAD of the negotiated suite
+enum SuiteCipher {
+    Aes256Gcm(Box<Aes256Gcm>),
+    ChaCha20Poly1305(ChaCha20Poly1305),
+}
+
+impl SuiteCipher {
+    fn new(suite: CipherSuite, key: &[u8; 32]) -> Self {
+        match suite {
+            CipherSuite::Aes256Gcm => SuiteCipher::Aes256Gcm(Box::new(Aes256Gcm::new(key.into()))),
+            CipherSuite::ChaCha20Poly1305 => {
+                SuiteCipher::ChaCha20Poly1305(ChaCha20Poly1305::new(key.into()))
+            }
+        }
+    }
+
+    fn encrypt(&self, nonce: &[u8; 12], payload: Payload) -> aes_gcm::aead::Result<Vec<u8>> {
+        match self {
+            SuiteCipher::Aes256Gcm(cipher) => cipher.encrypt(nonce.into(), payload),
+            SuiteCipher::ChaCha20Poly1305(cipher) => cipher.encrypt(nonce.into(), payload),
+        }
+    }
+
+    fn decrypt(&self, nonce: &[u8; 12], payload: Payload) -> aes_gcm::aead::Result<Vec<u8>> {
+        match self {
+            SuiteCipher::Aes256Gcm(cipher) => cipher.decrypt(nonce.into(), payload),
+            SuiteCipher::ChaCha20Poly1305(cipher) => cipher.decrypt(nonce.into(), payload),
+        }
+    }
+}
 
 /// Encrypt a chunk of data using AES-256-GCM
 ///
@@ -78,3 +222,205 @@ pub fn encrypt_file(aes_key: &[u8; 32], data: &[u8]) -> Result<Vec<u8>> {
 pub fn decrypt_file(aes_key: &[u8; 32], encrypted_data: &[u8]) -> Result<Vec<u8>> {
     decrypt_chunk(aes_key, encrypted_data)
 }
+
+/// Shared nonce/AAD state of a STREAM-style chunk sequence
+///
+/// Each chunk's nonce is `[7-byte prefix][4-byte BE index][1-byte final flag]`, where the
+/// prefix is derived from the session id. The AAD repeats the session id, index and flag,
+/// so a dropped, duplicated, reordered or truncated chunk fails authentication at the
+/// exact index where it happens instead of only at the final file hash.
+struct StreamState {
+    suite: CipherSuite,
+    cipher: SuiteCipher,
+    nonce_prefix: [u8; 7],
+    session_id: Vec<u8>,
+    index: u32,
+    finished: bool,
+}
+
+impl StreamState {
+    fn new(suite: CipherSuite, key: &[u8; 32], session_id: &str) -> Self {
+        let digest = Sha256::new()
+            .chain_update(STREAM_LABEL)
+            .chain_update(session_id.as_bytes())
+            .finalize();
+
+        let mut nonce_prefix = [0u8; 7];
+        nonce_prefix.copy_from_slice(&digest[..7]);
+
+        StreamState {
+            suite,
+            cipher: SuiteCipher::new(suite, key),
+            nonce_prefix,
+            session_id: session_id.as_bytes().to_vec(),
+            index: 0,
+            finished: false,
+        }
+    }
+
+    fn nonce(&self, flag: u8) -> [u8; 12] {
+        let mut nonce = [0u8; 12];
+        nonce[..7].copy_from_slice(&self.nonce_prefix);
+        nonce[7..11].copy_from_slice(&self.index.to_be_bytes());
+        nonce[11] = flag;
+        nonce
+    }
+
+    /// AAD: [label][4-byte BE session id length][session id][4-byte BE index][flag]
+    fn aad(&self, flag: u8) -> Vec<u8> {
+        let mut aad = Vec::with_capacity(STREAM_LABEL.len() + self.session_id.len() + 9);
+        aad.extend_from_slice(STREAM_LABEL);
+        aad.extend_from_slice(&(self.session_id.len() as u32).to_be_bytes());
+        aad.extend_from_slice(&self.session_id);
+        aad.extend_from_slice(&self.index.to_be_bytes());
+        aad.push(flag);
+        aad
+    }
+
+    fn check_open(&self) -> Result<()> {
+        if self.finished {
+            return Err(Error::CryptoError(format!(
+                "Chunk {} arrived after the final chunk",
+                self.index
+            )));
+        }
+        Ok(())
+    }
+
+    fn advance(&mut self, flag: u8) -> Result<()> {
+        if flag == FLAG_FINAL {
+            self.finished = true;
+        }
+        self.index = self
+            .index
+            .checked_add(1)
+            .ok_or_else(|| Error::CryptoError("Chunk counter overflow".to_string()))?;
+        Ok(())
+    }
+}
+
+/// Encrypts the chunks of one transfer session in order
+pub struct StreamEncryptor {
+    state: StreamState,
+}
+
+impl StreamEncryptor {
+    pub fn new(suite: CipherSuite, key: &[u8; 32], session_id: &str) -> Self {
+        StreamEncryptor {
+            state: StreamState::new(suite, key, session_id),
+        }
+    }
+
+    pub fn suite(&self) -> CipherSuite {
+        self.state.suite
+    }
+
+    /// Encrypt the next chunk, `last` marks the end of the stream
+    ///
+    /// Returns: [1-byte flag][ciphertext][16-byte authentication tag]
+    pub fn encrypt_next(&mut self, plaintext: &[u8], last: bool) -> Result<Vec<u8>> {
+        self.state.check_open()?;
+
+        let flag = if last { FLAG_FINAL } else { FLAG_MORE };
+        let nonce = self.state.nonce(flag);
+        let aad = self.state.aad(flag);
+
+        let ciphertext = self
+            .state
+            .cipher
+            .encrypt(
+                &nonce,
+                Payload {
+                    msg: plaintext,
+                    aad: &aad,
+                },
+            )
+            .map_err(|e| {
+                Error::CryptoError(format!("{} encryption failed: {}", self.state.suite, e))
+            })?;
+
+        let mut encrypted = Vec::with_capacity(1 + ciphertext.len());
+        encrypted.push(flag);
+        encrypted.extend_from_slice(&ciphertext);
+
+        self.state.advance(flag)?;
+        Ok(encrypted)
+    }
+
+    /// Index of the next chunk to be encrypted
+    pub fn index(&self) -> u32 {
+        self.state.index
+    }
+}
+
+/// Decrypts and checks the order of the chunks of one transfer session
+pub struct StreamDecryptor {
+    state: StreamState,
+}
+
+impl StreamDecryptor {
+    pub fn new(suite: CipherSuite, key: &[u8; 32], session_id: &str) -> Self {
+        StreamDecryptor {
+            state: StreamState::new(suite, key, session_id),
+        }
+    }
+
+    pub fn suite(&self) -> CipherSuite {
+        self.state.suite
+    }
+
+    /// Decrypt the next chunk, returns the plaintext and whether it was the last chunk
+    pub fn decrypt_next(&mut self, encrypted: &[u8]) -> Result<(Vec<u8>, bool)> {
+        self.state.check_open()?;
+
+        // Validate minimum length (flag + tag)
+        if encrypted.len() < 1 + 16 {
+            return Err(Error::CryptoError(format!(
+                "Chunk {} too short: {} bytes (need at least 17)",
+                self.state.index,
+                encrypted.len()
+            )));
+        }
+
+        let flag = encrypted[0];
+        if flag != FLAG_MORE && flag != FLAG_FINAL {
+            return Err(Error::CryptoError(format!(
+                "Chunk {} has an invalid flag: {:#04x}",
+                self.state.index, flag
+            )));
+        }
+
+        let nonce = self.state.nonce(flag);
+        let aad = self.state.aad(flag);
+
+        let plaintext = self
+            .state
+            .cipher
+            .decrypt(
+                &nonce,
+                Payload {
+                    msg: &encrypted[1..],
+                    aad: &aad,
+                },
+            )
+            .map_err(|_e| {
+                Error::CryptoError(format!(
+                    "Chunk {} failed authentication (dropped, reordered or tampered)",
+                    self.state.index
+                ))
+            })?;
+
+        self.state.advance(flag)?;
+        Ok((plaintext, flag == FLAG_FINAL))
+    }
+
+    /// Index of the next chunk expected
+    pub fn index(&self) -> u32 {
+        self.state.index
+    }
+
+    /// Whether the final chunk has been received
+    pub fn is_finished(&self) -> bool {
+        self.state.finished
+    }
+}
@@ -0,0 +1,66 @@
+#[cfg(unix)]
+use crate::agent::AgentClient;
+use crate::crypto::noise;
+use crate::utils::error::{Error, Result};
+use ed25519_dalek::{Signature, SignatureError, Signer, SigningKey, VerifyingKey};
+
+/// The user's identity key, unlocked in this process or held by `rs agent`
+///
+/// Everything a transfer needs from the private key goes through here: Ed25519
+/// signatures and the X25519 DH of the Noise handshake.
+pub enum Identity {
+    Local(SigningKey),
+    #[cfg(unix)]
+    Agent(AgentClient),
+}
+
+impl Identity {
+    pub fn verifying_key(&self) -> VerifyingKey {
+        match self {
+            Identity::Local(signing_key) => signing_key.verifying_key(),
+            #[cfg(unix)]
+            Identity::Agent(agent) => agent.public_key(),
+        }
+    }
+
+    pub fn sign(&self, data: &[u8]) -> Result<Signature> {
+        match self {
+            Identity::Local(signing_key) => Ok(signing_key.sign(data)),
+            #[cfg(unix)]
+            Identity::Agent(agent) => agent.sign(data),
+        }
+    }
+
+    /// X25519 between the identity key (converted as for Noise) and a peer's X25519 key
+    pub fn x25519(&self, public_key: &[u8]) -> Result<[u8; 32]> {
+        match self {
+            Identity::Local(signing_key) => {
+                let public_key: [u8; 32] = public_key
+                    .try_into()
+                    .map_err(|_e| Error::CryptoError("Invalid X25519 public key".to_string()))?;
+
+                let shared = x25519_dalek::x25519(noise::x25519_secret(signing_key), public_key);
+                if shared == [0u8; 32] {
+                    return Err(Error::CryptoError(
+                        "Low-order X25519 public key".to_string(),
+                    ));
+                }
+                Ok(shared)
+            }
+            #[cfg(unix)]
+            Identity::Agent(agent) => agent.x25519(public_key),
+        }
+    }
+}
+
+impl From<SigningKey> for Identity {
+    fn from(signing_key: SigningKey) -> Self {
+        Identity::Local(signing_key)
+    }
+}
+
+impl Signer<Signature> for Identity {
+    fn try_sign(&self, data: &[u8]) -> std::result::Result<Signature, SignatureError> {
+        self.sign(data).map_err(SignatureError::from_source)
+    }
+}
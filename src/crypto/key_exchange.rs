@@ -1,9 +1,16 @@
+use crate::crypto::signing;
 use crate::utils::error::{Error, Result};
 use aes_gcm::aead::rand_core::OsRng;
+use ed25519_dalek::{Signature, Signer, VerifyingKey};
 use hkdf::Hkdf;
+use ml_kem::kem::{Decapsulate, Encapsulate};
+use ml_kem::{Ciphertext, Encoded, EncodedSizeUser, KemCore, MlKem768};
 use sha2::Sha256;
 use x25519_dalek::{EphemeralSecret, PublicKey};
 
+type KemDecapsulationKey = <MlKem768 as KemCore>::DecapsulationKey;
+type KemEncapsulationKey = <MlKem768 as KemCore>::EncapsulationKey;
+
 /// Ephemeral keypair for a single file transfer session
 pub struct EphemeralKeyPair {
     pub secret: EphemeralSecret,
@@ -26,6 +33,65 @@ impl EphemeralKeyPair {
     }
 }
 
+/// ML-KEM-768 keypair for the post-quantum half of a hybrid exchange (receiver side)
+///
+/// Like the ephemeral X25519 keypair it lives for one transfer only.
+pub struct KemKeyPair {
+    decapsulation_key: KemDecapsulationKey,
+    encapsulation_key: KemEncapsulationKey,
+}
+
+impl KemKeyPair {
+    pub fn generate() -> Self {
+        let (decapsulation_key, encapsulation_key) = MlKem768::generate(&mut OsRng);
+
+        KemKeyPair {
+            decapsulation_key,
+            encapsulation_key,
+        }
+    }
+
+    /// Get the encapsulation key as a hex-encoded string for transmission
+    pub fn public_key_hex(&self) -> String {
+        hex::encode(self.encapsulation_key.as_bytes())
+    }
+
+    /// Recover the shared secret from the sender's ciphertext
+    pub fn decapsulate(&self, ciphertext: &[u8]) -> Result<[u8; 32]> {
+        let ciphertext = Ciphertext::<MlKem768>::try_from(ciphertext)
+            .map_err(|_e| Error::CryptoError("Invalid ML-KEM ciphertext length".to_string()))?;
+        let shared_secret = self
+            .decapsulation_key
+            .decapsulate(&ciphertext)
+            .map_err(|_e| Error::CryptoError("ML-KEM decapsulation failed".to_string()))?;
+
+        Ok(shared_secret.into())
+    }
+}
+
+/// Encapsulate a fresh shared secret to the receiver's ML-KEM key (sender side)
+///
+/// Returns the ciphertext to send and the shared secret.
+pub fn kem_encapsulate(encapsulation_key_hex: &str) -> Result<(Vec<u8>, [u8; 32])> {
+    let bytes = hex::decode(encapsulation_key_hex)
+        .map_err(|_e| Error::CryptoError("Invalid hex ML-KEM key".to_string()))?;
+    let encoded = Encoded::<KemEncapsulationKey>::try_from(bytes.as_slice())
+        .map_err(|_e| Error::CryptoError("Invalid ML-KEM key length".to_string()))?;
+
+    let (ciphertext, shared_secret) = KemEncapsulationKey::from_bytes(&encoded)
+        .encapsulate(&mut OsRng)
+        .map_err(|_e| Error::CryptoError("ML-KEM encapsulation failed".to_string()))?;
+
+    Ok((ciphertext.to_vec(), shared_secret.into()))
+}
+
+/// Session secret of a hybrid exchange: the classical secret followed by the ML-KEM one
+///
+/// Fed to the key schedule as a whole, so the keys stay safe while either half holds.
+pub fn hybrid_secret(classical: &[u8], post_quantum: &[u8; 32]) -> Vec<u8> {
+    [classical, post_quantum.as_slice()].concat()
+}
+
 /// Parse a hex-encoded X25519 public key
 pub fn parse_public_key(hex_str: &str) -> Result<PublicKey> {
     let bytes = hex::decode(hex_str)
@@ -44,6 +110,41 @@ pub fn parse_public_key(hex_str: &str) -> Result<PublicKey> {
     Ok(PublicKey::from(key_bytes))
 }
 
+/// Sign the message vouching for our ephemeral key with the identity key (hex signature)
+///
+/// `message` comes from `protocol::ephemeral_key_message`.
+pub fn sign_ephemeral_key(signer: &impl Signer<Signature>, message: &str) -> Result<String> {
+    Ok(hex::encode(signing::sign_data(signer, message)?.to_bytes()))
+}
+
+/// Check that the peer's ephemeral key is vouched for by its trusted identity key
+///
+/// Must pass before the key is used, otherwise the relay could have swapped it.
+pub fn verify_ephemeral_key(
+    identity: &VerifyingKey,
+    message: &str,
+    signature_hex: &str,
+) -> Result<()> {
+    if signature_hex.is_empty() {
+        return Err(Error::CryptoError(
+            "Peer did not sign its ephemeral key (outdated client?)".to_string(),
+        ));
+    }
+
+    let signature_bytes = hex::decode(signature_hex)
+        .map_err(|_e| Error::CryptoError("Invalid ephemeral key signature hex".to_string()))?;
+    let signature = Signature::from_bytes(
+        signature_bytes
+            .as_slice()
+            .try_into()
+            .map_err(|_e| Error::CryptoError("Invalid ephemeral key signature length".into()))?,
+    );
+
+    signing::verify_signature(identity, message, &signature).map_err(|_e| {
+        Error::CryptoError("Ephemeral key is not signed by the trusted contact".to_string())
+    })
+}
+
 /// Compute the ECDH shared secret from our ephemeral private key and their public key
 pub fn compute_shared_secret(our_secret: EphemeralSecret, their_public: PublicKey) -> [u8; 32] {
     let shared_secret = our_secret.diffie_hellman(&their_public);
@@ -0,0 +1,146 @@
+use crate::config::{
+    KEY_FILE_ITERATIONS, KEY_FILE_MAGIC, KEY_FILE_MAX_ITERATIONS, KEY_FILE_MAX_MEMORY_KIB,
+    KEY_FILE_MAX_PARALLELISM, KEY_FILE_MEMORY_KIB, KEY_FILE_PARALLELISM, KEY_FILE_VERSION,
+};
+use crate::utils::error::{Error, Result};
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::rand_core::RngCore;
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use argon2::{Algorithm, Argon2, Params, Version};
+use chacha20poly1305::ChaCha20Poly1305;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const SECRET_LEN: usize = 32;
+const TAG_LEN: usize = 16;
+
+/// Argon2id cost parameters, stored in the key file header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KdfParams {
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            memory_kib: KEY_FILE_MEMORY_KIB,
+            iterations: KEY_FILE_ITERATIONS,
+            parallelism: KEY_FILE_PARALLELISM,
+        }
+    }
+}
+
+impl KdfParams {
+    fn check(&self) -> Result<()> {
+        if self.memory_kib > KEY_FILE_MAX_MEMORY_KIB
+            || self.iterations > KEY_FILE_MAX_ITERATIONS
+            || self.parallelism > KEY_FILE_MAX_PARALLELISM
+        {
+            return Err(Error::CryptoError(
+                "Key file asks for excessive Argon2 parameters".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    fn derive_key(&self, passphrase: &str, salt: &[u8]) -> Result<[u8; 32]> {
+        self.check()?;
+        let params = Params::new(self.memory_kib, self.iterations, self.parallelism, Some(32))
+            .map_err(|e| Error::CryptoError(format!("Invalid Argon2 parameters: {}", e)))?;
+
+        let mut key = [0u8; 32];
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|e| Error::CryptoError(format!("Argon2 key derivation failed: {}", e)))?;
+        Ok(key)
+    }
+}
+
+/// Whether the private key file content is passphrase-protected (or the raw legacy key)
+pub fn is_encrypted(bytes: &[u8]) -> bool {
+    bytes.starts_with(KEY_FILE_MAGIC)
+}
+
+/// Encrypt a private key under a passphrase
+///
+/// Format v1 (integers big-endian):
+/// `[magic][version 1B][memory KiB 4B][iterations 4B][lanes 4B][salt 16B][nonce 12B][ciphertext 32B + tag 16B]`
+///
+/// The key is Argon2id(passphrase, salt), the cipher ChaCha20-Poly1305 with everything
+/// before the ciphertext as AAD, so the cost parameters can't be lowered unnoticed.
+pub fn seal(secret: &[u8; 32], passphrase: &str, params: &KdfParams) -> Result<Vec<u8>> {
+    let mut salt = [0u8; SALT_LEN];
+    OsRng.fill_bytes(&mut salt);
+    let mut nonce = [0u8; NONCE_LEN];
+    OsRng.fill_bytes(&mut nonce);
+
+    let mut header = Vec::with_capacity(KEY_FILE_MAGIC.len() + 13 + SALT_LEN + NONCE_LEN);
+    header.extend_from_slice(KEY_FILE_MAGIC);
+    header.push(KEY_FILE_VERSION);
+    header.extend_from_slice(&params.memory_kib.to_be_bytes());
+    header.extend_from_slice(&params.iterations.to_be_bytes());
+    header.extend_from_slice(&params.parallelism.to_be_bytes());
+    header.extend_from_slice(&salt);
+    header.extend_from_slice(&nonce);
+
+    let key = params.derive_key(passphrase, &salt)?;
+    let ciphertext = ChaCha20Poly1305::new(&key.into())
+        .encrypt(
+            &nonce.into(),
+            Payload {
+                msg: secret,
+                aad: &header,
+            },
+        )
+        .map_err(|_e| Error::CryptoError("Failed to encrypt private key".to_string()))?;
+
+    header.extend_from_slice(&ciphertext);
+    Ok(header)
+}
+
+/// Decrypt a private key file written by `seal`
+pub fn open(bytes: &[u8], passphrase: &str) -> Result<[u8; 32]> {
+    let invalid = || Error::InvalidInput("Invalid private key file".to_string());
+
+    let rest = bytes.strip_prefix(KEY_FILE_MAGIC).ok_or_else(invalid)?;
+    let (version, rest) = rest.split_first().ok_or_else(invalid)?;
+    if *version != KEY_FILE_VERSION {
+        return Err(Error::InvalidInput(format!(
+            "Unsupported private key file version {}",
+            version
+        )));
+    }
+
+    let expected = 12 + SALT_LEN + NONCE_LEN + SECRET_LEN + TAG_LEN;
+    if rest.len() != expected {
+        return Err(invalid());
+    }
+    let read_u32 = |at: usize| u32::from_be_bytes(rest[at..at + 4].try_into().unwrap());
+    let params = KdfParams {
+        memory_kib: read_u32(0),
+        iterations: read_u32(4),
+        parallelism: read_u32(8),
+    };
+    let salt = &rest[12..12 + SALT_LEN];
+    let nonce: [u8; NONCE_LEN] = rest[12 + SALT_LEN..12 + SALT_LEN + NONCE_LEN]
+        .try_into()
+        .map_err(|_e| invalid())?;
+    let header_len = bytes.len() - SECRET_LEN - TAG_LEN;
+
+    let key = params.derive_key(passphrase, salt)?;
+    let secret = ChaCha20Poly1305::new(&key.into())
+        .decrypt(
+            &nonce.into(),
+            Payload {
+                msg: &bytes[header_len..],
+                aad: &bytes[..header_len],
+            },
+        )
+        .map_err(|_e| {
+            Error::CryptoError("Wrong passphrase or corrupted private key file".to_string())
+        })?;
+
+    secret.try_into().map_err(|_e| invalid())
+}
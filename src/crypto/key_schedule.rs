@@ -0,0 +1,161 @@
+use crate::config::{
+    CONFIRMATION_CODE_LEN, CONTROL_TAG_LEN, CONTROL_TAG_SEPARATOR, KEY_SCHEDULE_LABEL,
+};
+use crate::crypto::encryption::CipherSuite;
+use crate::server::TransferRole;
+use crate::utils::error::{Error, Result};
+use hkdf::Hkdf;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+/// Keys of one transfer session
+///
+/// Key schedule v1: one HKDF-SHA256 extract over the session secret (both keys of the
+/// Noise split, followed by the ML-KEM shared secret in hybrid sessions),
+/// salted with the handshake transcript,
+/// then one expand per output with info `rshare-key-schedule|v<version>|<purpose>`:
+///
+/// - `sender-to-receiver`: AEAD key for the data stream
+/// - `receiver-to-sender`: AEAD key for data flowing back (nothing does yet)
+/// - `control`: HMAC key authenticating control messages (`DONE`, `ERROR:...`)
+/// - `confirmation`: value both users can compare to rule out a man in the middle
+///
+/// Key schedule v2 is the same with the negotiated cipher suite bound into every output,
+/// info `rshare-key-schedule|v2|<suite>|<purpose>`, so a session can't be steered onto a
+/// different suite than the one both peers derived keys for. Earlier schedules always
+/// use AES-256-GCM.
+///
+/// A new version only adds a branch in `derive`, peers negotiate the one they both speak.
+/// Sessions with peers that predate the schedule use a single key for everything and
+/// leave control messages unprotected (`legacy`).
+pub struct SessionKeys {
+    /// Key schedule version (0: legacy single key)
+    pub version: u32,
+    /// AEAD the stream keys are meant for
+    pub suite: CipherSuite,
+    pub sender_to_receiver: [u8; 32],
+    pub receiver_to_sender: [u8; 32],
+    pub control: Option<ControlKey>,
+    pub confirmation: Option<[u8; 32]>,
+    /// The session secret includes a post-quantum (ML-KEM) shared secret
+    pub post_quantum: bool,
+}
+
+/// HMAC key authenticating control messages in both directions
+#[derive(Clone)]
+pub struct ControlKey([u8; 32]);
+
+impl SessionKeys {
+    /// Expand the session secret with the given key schedule version
+    pub fn derive(version: u32, suite: CipherSuite, secret: &[u8], salt: &[u8]) -> Result<Self> {
+        let bound_suite = match (version, suite) {
+            (1, CipherSuite::Aes256Gcm) => None,
+            (1, suite) => {
+                return Err(Error::CryptoError(format!(
+                    "Key schedule v1 does not support {}",
+                    suite
+                )));
+            }
+            (_, suite) => Some(suite),
+        };
+
+        match version {
+            1 | 2 => {
+                let hkdf = Hkdf::<Sha256>::new(Some(salt), secret);
+                let expand = |purpose: &str| -> Result<[u8; 32]> {
+                    let mut output = [0u8; 32];
+                    hkdf.expand(label(version, bound_suite, purpose).as_bytes(), &mut output)
+                        .map_err(|_e| Error::CryptoError("HKDF key derivation failed".into()))?;
+                    Ok(output)
+                };
+
+                Ok(SessionKeys {
+                    version,
+                    suite,
+                    sender_to_receiver: expand("sender-to-receiver")?,
+                    receiver_to_sender: expand("receiver-to-sender")?,
+                    control: Some(ControlKey(expand("control")?)),
+                    confirmation: Some(expand("confirmation")?),
+                    post_quantum: false,
+                })
+            }
+            v => Err(Error::CryptoError(format!(
+                "Unsupported key schedule version {}",
+                v
+            ))),
+        }
+    }
+
+    /// Single key for both directions, as older peers use it
+    pub fn legacy(key: [u8; 32]) -> Self {
+        SessionKeys {
+            version: 0,
+            suite: CipherSuite::Aes256Gcm,
+            sender_to_receiver: key,
+            receiver_to_sender: key,
+            control: None,
+            confirmation: None,
+            post_quantum: false,
+        }
+    }
+
+    /// Short form of the confirmation value for display, e.g. `3f9a-0c41-77de`
+    pub fn confirmation_code(&self) -> Option<String> {
+        self.confirmation.map(|value| {
+            value[..CONFIRMATION_CODE_LEN]
+                .chunks(2)
+                .map(hex::encode)
+                .collect::<Vec<_>>()
+                .join("-")
+        })
+    }
+}
+
+impl ControlKey {
+    /// `<message>|<tag>`, the tag binds the message to the role that sent it
+    pub fn seal(&self, role: TransferRole, message: &str) -> String {
+        let tag = self.mac(role, message).finalize().into_bytes();
+        format!(
+            "{}{}{}",
+            message,
+            CONTROL_TAG_SEPARATOR,
+            hex::encode(&tag[..CONTROL_TAG_LEN])
+        )
+    }
+
+    /// Check the tag of a line sealed by `role` and return the message
+    pub fn open(&self, role: TransferRole, line: &str) -> Result<String> {
+        let unauthenticated =
+            || Error::CryptoError("Control message failed authentication".to_string());
+
+        let (message, tag) = line
+            .rsplit_once(CONTROL_TAG_SEPARATOR)
+            .ok_or_else(unauthenticated)?;
+        let tag = hex::decode(tag).map_err(|_e| unauthenticated())?;
+        if tag.len() != CONTROL_TAG_LEN {
+            return Err(unauthenticated());
+        }
+
+        self.mac(role, message)
+            .verify_truncated_left(&tag)
+            .map_err(|_e| unauthenticated())?;
+        Ok(message.to_string())
+    }
+
+    fn mac(&self, role: TransferRole, message: &str) -> Hmac<Sha256> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(&self.0)
+            .expect("HMAC accepts keys of any length");
+        mac.update(role.as_str().as_bytes());
+        mac.update(&[CONTROL_TAG_SEPARATOR as u8]);
+        mac.update(message.as_bytes());
+        mac
+    }
+}
+
+/// HKDF info of one key schedule output
+fn label(version: u32, suite: Option<CipherSuite>, purpose: &str) -> String {
+    match suite {
+        Some(suite) => format!("{}|v{}|{}|{}", KEY_SCHEDULE_LABEL, version, suite, purpose),
+        None => format!("{}|v{}|{}", KEY_SCHEDULE_LABEL, version, purpose),
+    }
+}
@@ -1,3 +1,12 @@
+pub mod card;
 pub mod encryption;
+pub mod identity;
 pub mod key_exchange;
+pub mod key_file;
+pub mod key_format;
+pub mod key_schedule;
+pub mod noise;
+pub mod rotation;
+pub mod sas;
+pub mod seed;
 pub mod signing;
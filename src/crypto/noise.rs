@@ -0,0 +1,264 @@
+#[cfg(unix)]
+use crate::agent::AgentClient;
+use crate::config::{NOISE_MAX_MESSAGE_LEN, NOISE_PARAMS};
+use crate::crypto::encryption::CipherSuite;
+use crate::crypto::identity::Identity;
+use crate::crypto::key_exchange;
+use crate::crypto::key_schedule::SessionKeys;
+use crate::utils::error::{Error, Result};
+use ed25519_dalek::{SigningKey, VerifyingKey};
+#[cfg(unix)]
+use snow::params::{CipherChoice, DHChoice, HashChoice};
+#[cfg(unix)]
+use snow::resolvers::{CryptoResolver, DefaultResolver};
+#[cfg(unix)]
+use snow::types::{Cipher, Dh, Hash, Random};
+use snow::{Builder, HandshakeState};
+
+/// Noise IK handshake between the two identity keys
+///
+/// The receiver initiates, since it already knows which sender it expects. The sender
+/// learns the receiver's static key from the first message and must check it against
+/// the contact. Ed25519 identity keys are converted to X25519 for the DH steps.
+pub struct NoiseHandshake {
+    state: HandshakeState,
+}
+
+impl NoiseHandshake {
+    /// Start the handshake towards `remote` (receiver side)
+    pub fn initiator(identity: &Identity, remote: &VerifyingKey, prologue: &[u8]) -> Result<Self> {
+        let local = local_static_key(identity);
+        let remote = x25519_public(remote);
+
+        let state = builder(identity)?
+            .local_private_key(&local)
+            .and_then(|b| b.remote_public_key(&remote))
+            .and_then(|b| b.prologue(prologue))
+            .and_then(|b| b.build_initiator())
+            .map_err(noise_error)?;
+
+        Ok(NoiseHandshake { state })
+    }
+
+    /// Answer a handshake from a not yet known peer (sender side)
+    pub fn responder(identity: &Identity, prologue: &[u8]) -> Result<Self> {
+        let local = local_static_key(identity);
+
+        let state = builder(identity)?
+            .local_private_key(&local)
+            .and_then(|b| b.prologue(prologue))
+            .and_then(|b| b.build_responder())
+            .map_err(noise_error)?;
+
+        Ok(NoiseHandshake { state })
+    }
+
+    /// Produce the next handshake message carrying `payload`
+    pub fn write_message(&mut self, payload: &[u8]) -> Result<Vec<u8>> {
+        let mut message = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+        let len = self
+            .state
+            .write_message(payload, &mut message)
+            .map_err(noise_error)?;
+        message.truncate(len);
+        Ok(message)
+    }
+
+    /// Process the peer's handshake message and return its payload
+    pub fn read_message(&mut self, message: &[u8]) -> Result<Vec<u8>> {
+        let mut payload = vec![0u8; NOISE_MAX_MESSAGE_LEN];
+        let len = self
+            .state
+            .read_message(message, &mut payload)
+            .map_err(noise_error)?;
+        payload.truncate(len);
+        Ok(payload)
+    }
+
+    /// Whether the peer's static key is the X25519 form of `identity`
+    pub fn remote_is(&self, identity: &VerifyingKey) -> bool {
+        self.state.get_remote_static() == Some(x25519_public(identity).as_slice())
+    }
+
+    /// Hash of the whole handshake transcript (including the prologue)
+    pub fn handshake_hash(&self) -> &[u8] {
+        self.state.get_handshake_hash()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.state.is_handshake_finished()
+    }
+
+    /// Secret the finished handshake agreed on: both keys of the Noise split
+    ///
+    /// They come from the chaining key, so they depend on every DH of the handshake
+    /// (ephemeral and static) and neither side can choose them alone.
+    pub fn split_secret(&mut self) -> Result<[u8; 64]> {
+        if !self.is_finished() {
+            return Err(Error::CryptoError(
+                "Noise handshake did not complete".to_string(),
+            ));
+        }
+
+        let (initiator, responder) = self.state.dangerously_get_raw_split();
+        let mut secret = [0u8; 64];
+        secret[..32].copy_from_slice(&initiator);
+        secret[32..].copy_from_slice(&responder);
+        Ok(secret)
+    }
+}
+
+/// Expand the handshake's split secret with the negotiated key schedule, salted with
+/// the transcript
+///
+/// In hybrid sessions the ML-KEM shared secret is mixed in as well.
+pub fn derive_session_keys(
+    split_secret: &[u8],
+    kem_secret: Option<&[u8; 32]>,
+    handshake_hash: &[u8],
+    key_schedule: u32,
+    suite: CipherSuite,
+) -> Result<SessionKeys> {
+    match kem_secret {
+        Some(kem_secret) => {
+            let secret = key_exchange::hybrid_secret(split_secret, kem_secret);
+            let mut keys = SessionKeys::derive(key_schedule, suite, &secret, handshake_hash)?;
+            keys.post_quantum = true;
+            Ok(keys)
+        }
+        None => SessionKeys::derive(key_schedule, suite, split_secret, handshake_hash),
+    }
+}
+
+/// X25519 private key of an Ed25519 identity
+pub fn x25519_secret(identity: &SigningKey) -> [u8; 32] {
+    identity.to_scalar_bytes()
+}
+
+/// X25519 public key of an Ed25519 identity
+pub fn x25519_public(identity: &VerifyingKey) -> [u8; 32] {
+    identity.to_montgomery().to_bytes()
+}
+
+fn builder(identity: &Identity) -> Result<Builder<'static>> {
+    let params = NOISE_PARAMS
+        .parse()
+        .map_err(|_e| Error::CryptoError("Invalid Noise parameters".to_string()))?;
+
+    match identity {
+        Identity::Local(_) => Ok(Builder::new(params)),
+        #[cfg(unix)]
+        Identity::Agent(agent) => Ok(Builder::with_resolver(
+            params,
+            Box::new(AgentResolver {
+                agent: agent.clone(),
+            }),
+        )),
+    }
+}
+
+/// What the builder takes as our static key: the secret, or with the agent the public
+/// key, which `AgentDh` keeps while the agent does the DH with the secret
+fn local_static_key(identity: &Identity) -> [u8; 32] {
+    match identity {
+        Identity::Local(signing_key) => x25519_secret(signing_key),
+        #[cfg(unix)]
+        Identity::Agent(agent) => x25519_public(&agent.public_key()),
+    }
+}
+
+/// Default primitives, except that DH with our static key is done by the agent
+#[cfg(unix)]
+struct AgentResolver {
+    agent: AgentClient,
+}
+
+#[cfg(unix)]
+impl CryptoResolver for AgentResolver {
+    fn resolve_rng(&self) -> Option<Box<dyn Random>> {
+        DefaultResolver.resolve_rng()
+    }
+
+    fn resolve_dh(&self, choice: &DHChoice) -> Option<Box<dyn Dh>> {
+        let inner = DefaultResolver.resolve_dh(choice)?;
+        Some(Box::new(AgentDh {
+            agent: self.agent.clone(),
+            inner,
+            static_public: None,
+        }))
+    }
+
+    fn resolve_hash(&self, choice: &HashChoice) -> Option<Box<dyn Hash>> {
+        DefaultResolver.resolve_hash(choice)
+    }
+
+    fn resolve_cipher(&self, choice: &CipherChoice) -> Option<Box<dyn Cipher>> {
+        DefaultResolver.resolve_cipher(choice)
+    }
+}
+
+/// DH key that is either our static key in the agent or a local (ephemeral) key
+///
+/// The builder only calls `set` for the static key, ephemeral keys are `generate`d.
+#[cfg(unix)]
+struct AgentDh {
+    agent: AgentClient,
+    inner: Box<dyn Dh>,
+    static_public: Option<Vec<u8>>,
+}
+
+#[cfg(unix)]
+impl Dh for AgentDh {
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+
+    fn pub_len(&self) -> usize {
+        self.inner.pub_len()
+    }
+
+    fn priv_len(&self) -> usize {
+        self.inner.priv_len()
+    }
+
+    fn set(&mut self, public_key: &[u8]) {
+        self.static_public = Some(public_key.to_vec());
+    }
+
+    fn generate(&mut self, rng: &mut dyn Random) -> std::result::Result<(), snow::Error> {
+        self.static_public = None;
+        self.inner.generate(rng)
+    }
+
+    fn pubkey(&self) -> &[u8] {
+        match &self.static_public {
+            Some(public_key) => public_key,
+            None => self.inner.pubkey(),
+        }
+    }
+
+    fn privkey(&self) -> &[u8] {
+        self.inner.privkey()
+    }
+
+    fn dh(&self, public_key: &[u8], out: &mut [u8]) -> std::result::Result<(), snow::Error> {
+        if self.static_public.is_none() {
+            return self.inner.dh(public_key, out);
+        }
+
+        // snow hands over a buffer sized for its largest DH, the key is at the front
+        let public_key = public_key.get(..self.pub_len()).ok_or(snow::Error::Dh)?;
+        let shared = self
+            .agent
+            .x25519(public_key)
+            .map_err(|_e| snow::Error::Dh)?;
+        out.get_mut(..shared.len())
+            .ok_or(snow::Error::Dh)?
+            .copy_from_slice(&shared);
+        Ok(())
+    }
+}
+
+fn noise_error(e: snow::Error) -> Error {
+    Error::CryptoError(format!("Noise handshake failed: {}", e))
+}
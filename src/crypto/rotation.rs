@@ -0,0 +1,111 @@
+use crate::config::{KEY_ROTATION_CONTEXT, KEY_ROTATION_VERSION, MAX_KEY_ROTATIONS};
+use crate::crypto::signing;
+use crate::utils::error::{Error, Result};
+use ed25519_dalek::{Signature, SigningKey};
+use serde::{Deserialize, Serialize};
+
+/// Statement that an identity key was replaced, signed by both the old and the new key
+///
+/// The old key's signature hands the trust placed in it over to the new key, the new
+/// key's signature shows the holder of the new key made the statement.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyRotation {
+    pub version: u32,
+    /// Hex-encoded Ed25519 public keys
+    pub old_key: String,
+    pub new_key: String,
+    /// Unix timestamp (seconds)
+    pub rotated_at: u64,
+    /// Hex-encoded signatures over `signed_message()`, one per key
+    pub old_signature: String,
+    pub new_signature: String,
+}
+
+impl KeyRotation {
+    /// Statement from `old_key` to `new_key`, signed by both
+    pub fn new(old_key: &SigningKey, new_key: &SigningKey, rotated_at: u64) -> Result<Self> {
+        let mut rotation = KeyRotation {
+            version: KEY_ROTATION_VERSION,
+            old_key: hex::encode(old_key.verifying_key().to_bytes()),
+            new_key: hex::encode(new_key.verifying_key().to_bytes()),
+            rotated_at,
+            old_signature: String::new(),
+            new_signature: String::new(),
+        };
+
+        let message = rotation.signed_message();
+        rotation.old_signature = hex::encode(signing::sign_bytes(old_key, &message)?.to_bytes());
+        rotation.new_signature = hex::encode(signing::sign_bytes(new_key, &message)?.to_bytes());
+        Ok(rotation)
+    }
+
+    /// Message both keys sign: `context|v<version>|old_key|new_key|rotated_at`
+    pub fn signed_message(&self) -> Vec<u8> {
+        format!(
+            "{}|v{}|{}|{}|{}",
+            KEY_ROTATION_CONTEXT, self.version, self.old_key, self.new_key, self.rotated_at
+        )
+        .into_bytes()
+    }
+
+    /// Check the version and both signatures
+    pub fn verify(&self) -> Result<()> {
+        if self.version != KEY_ROTATION_VERSION {
+            return Err(Error::SessionError(format!(
+                "Unsupported key rotation version {} (we speak v{})",
+                self.version, KEY_ROTATION_VERSION
+            )));
+        }
+        if self.old_key == self.new_key {
+            return Err(invalid("old and new key are the same"));
+        }
+
+        let message = self.signed_message();
+        for (key, signature) in [
+            (&self.old_key, &self.old_signature),
+            (&self.new_key, &self.new_signature),
+        ] {
+            let key = signing::parse_verifying_key(key)?;
+            let signature = hex::decode(signature)
+                .ok()
+                .and_then(|bytes| Signature::from_slice(&bytes).ok())
+                .ok_or_else(|| invalid("signature"))?;
+            signing::verify_bytes(&key, &message, &signature)
+                .map_err(|_e| invalid("signature does not verify"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Statements leading from the key we trust to the key a contact presents, each verified
+///
+/// Several rotations in a row are followed, so a contact who was offline for more than
+/// one rotation still catches up. When a key was rotated twice (e.g. after a failed
+/// save) the most recent statement wins.
+pub fn follow(chain: &[KeyRotation], trusted: &str, presented: &str) -> Result<Vec<KeyRotation>> {
+    if chain.len() > MAX_KEY_ROTATIONS {
+        return Err(invalid("too many statements"));
+    }
+
+    let mut current = trusted;
+    let mut path = Vec::new();
+    while current != presented && path.len() < chain.len() {
+        let Some(link) = chain.iter().rev().find(|link| link.old_key == current) else {
+            break;
+        };
+        link.verify()?;
+        path.push(link.clone());
+        current = &link.new_key;
+    }
+
+    if current != presented {
+        return Err(Error::SessionError(
+            "No valid key rotation from the trusted key to the presented key".to_string(),
+        ));
+    }
+    Ok(path)
+}
+
+fn invalid(what: &str) -> Error {
+    Error::SessionError(format!("Invalid key rotation: {}", what))
+}
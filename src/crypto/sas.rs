@@ -0,0 +1,48 @@
+use crate::config::{SAS_CONTEXT, SAS_GROUP_DIGITS, SAS_GROUPS, SAS_ITERATIONS};
+use ed25519_dalek::VerifyingKey;
+use sha2::{Digest, Sha256};
+
+/// Short authentication string two contacts compare to check they hold each other's key
+///
+/// One half per key, each computed from that key alone (as in Signal's safety numbers),
+/// the halves ordered by key so both sides read the same code. A man in the middle
+/// substituting his own keys must match each half separately: a second preimage on
+/// 20 digits (about 66 bits) of an iterated hash per half, which cannot be traded for
+/// a birthday search across the two.
+pub fn short_auth_string(ours: &VerifyingKey, theirs: &VerifyingKey) -> String {
+    let (low, high) = if ours.as_bytes() <= theirs.as_bytes() {
+        (ours, theirs)
+    } else {
+        (theirs, ours)
+    };
+
+    format!("{} {}", key_code(low), key_code(high))
+}
+
+/// One key's half of the code: `SAS_GROUPS` groups of `SAS_GROUP_DIGITS` digits
+pub fn key_code(key: &VerifyingKey) -> String {
+    let mut digest = Sha256::new()
+        .chain_update(SAS_CONTEXT.as_bytes())
+        .chain_update(key.as_bytes())
+        .finalize();
+    for _ in 0..SAS_ITERATIONS {
+        digest = Sha256::new()
+            .chain_update(digest)
+            .chain_update(key.as_bytes())
+            .finalize();
+    }
+
+    // Five bytes (40 bits) per group, reduced to the group's digits
+    let modulus = 10u64.pow(SAS_GROUP_DIGITS as u32);
+    digest
+        .chunks(5)
+        .take(SAS_GROUPS)
+        .map(|chunk| {
+            let value = chunk
+                .iter()
+                .fold(0u64, |acc, byte| (acc << 8) | *byte as u64);
+            format!("{:0width$}", value % modulus, width = SAS_GROUP_DIGITS)
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,45 @@
+use crate::config::SEED_PHRASE_WORDS;
+use crate::utils::error::{Error, Result};
+use bip39::Mnemonic;
+use ed25519_dalek::SigningKey;
+
+/// Seed phrase for the private key: the 32-byte Ed25519 secret as 24 BIP39 English words
+///
+/// The words encode the secret itself, not a seed it is derived from, so any existing
+/// key can be backed up and restoring it gives back the same key and fingerprint.
+pub fn to_phrase(signing_key: &SigningKey) -> Result<String> {
+    let mnemonic = Mnemonic::from_entropy(signing_key.as_bytes())
+        .map_err(|e| Error::CryptoError(format!("Failed to encode seed phrase: {}", e)))?;
+    Ok(mnemonic.to_string())
+}
+
+/// Private key encoded by a seed phrase, checking its words and checksum
+///
+/// Case and extra whitespace are ignored, so the phrase can be typed back as written down.
+pub fn from_phrase(phrase: &str) -> Result<SigningKey> {
+    let normalized = phrase
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .collect::<Vec<_>>()
+        .join(" ");
+
+    let word_count = normalized
+        .split(' ')
+        .filter(|word| !word.is_empty())
+        .count();
+    if word_count != SEED_PHRASE_WORDS {
+        return Err(Error::InvalidInput(format!(
+            "Seed phrase has {} words, expected {}",
+            word_count, SEED_PHRASE_WORDS
+        )));
+    }
+
+    let mnemonic = Mnemonic::parse_normalized(&normalized)
+        .map_err(|e| Error::InvalidInput(format!("Invalid seed phrase: {}", e)))?;
+    let (entropy, len) = mnemonic.to_entropy_array();
+    let secret: [u8; 32] = entropy[..len]
+        .try_into()
+        .map_err(|_e| Error::InvalidInput("Invalid seed phrase length".to_string()))?;
+
+    Ok(SigningKey::from_bytes(&secret))
+}
@@ -1,12 +1,54 @@
 use crate::utils::error::{Error, Result};
-use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
+use ed25519_dalek::{Signature, Signer, VerifyingKey};
 // Verifier trait is required for verify_strict method
 #[allow(unused_imports)]
 use ed25519_dalek::Verifier;
 
 /// Sign data and return signature
-pub fn sign_data(signing_key: &SigningKey, data: &str) -> Result<Signature> {
-    Ok(signing_key.sign(data.as_bytes()))
+///
+/// Takes a `SigningKey` or an `Identity` (which may be held by the agent).
+pub fn sign_data(signer: &impl Signer<Signature>, data: &str) -> Result<Signature> {
+    sign_bytes(signer, data.as_bytes())
+}
+
+/// Sign raw bytes (canonically encoded structures) and return signature
+pub fn sign_bytes(signer: &impl Signer<Signature>, data: &[u8]) -> Result<Signature> {
+    signer
+        .try_sign(data)
+        .map_err(|e| Error::CryptoError(format!("Signing failed: {}", e)))
+}
+
+/// Verify a signature over raw bytes
+pub fn verify_bytes(
+    verifying_key: &VerifyingKey,
+    data: &[u8],
+    signature: &Signature,
+) -> Result<()> {
+    verifying_key
+        .verify_strict(data, signature)
+        .map_err(|_e| Error::InvalidInput("Signature verification failed".to_string()))
+}
+
+/// Parse a hex-encoded Ed25519 public key (a contact's fingerprint)
+pub fn parse_verifying_key(hex_str: &str) -> Result<VerifyingKey> {
+    let bytes = hex::decode(hex_str)
+        .map_err(|_e| Error::CryptoError("Invalid hex public key".to_string()))?;
+    let bytes: [u8; 32] = bytes
+        .as_slice()
+        .try_into()
+        .map_err(|_e| Error::CryptoError("Invalid verify key length".to_string()))?;
+
+    VerifyingKey::from_bytes(&bytes).map_err(|_e| Error::CryptoError("Invalid public key".into()))
+}
+
+/// Public key as hex in groups of four, easier to read out and compare
+pub fn format_fingerprint(verifying_key: &VerifyingKey) -> String {
+    hex::encode(verifying_key.to_bytes())
+        .as_bytes()
+        .chunks(4)
+        .map(|group| String::from_utf8_lossy(group).into_owned())
+        .collect::<Vec<_>>()
+        .join(" ")
 }
 
 /// Verify signature
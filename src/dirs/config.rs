@@ -1,5 +1,5 @@
 use crate::config::*;
-use crate::dirs::keys;
+use crate::dirs::{keys, profile};
 use crate::utils::error::{Error, Result};
 use local_ip_address::local_ip;
 use serde::{Deserialize, Serialize};
@@ -15,9 +15,27 @@ pub struct Config {
 pub struct PathConfig {
     pub keys_path: PathBuf,
     pub download_path: PathBuf,
+    /// What to do when a received file already exists (absent in older configs)
+    #[serde(default)]
+    pub on_conflict: ConflictPolicy,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// How the receiver handles a download whose name is already taken
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum ConflictPolicy {
+    /// Replace the existing file
+    Overwrite,
+    /// Keep both, saving the new file as `name (1).ext`
+    #[default]
+    Rename,
+    /// Keep the existing file and drop the new one
+    Skip,
+    /// Prompt for one of the above
+    Ask,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ServerConfig {
     pub server_name: String,
     pub default: bool,
@@ -32,10 +50,8 @@ impl Default for Config {
             path: {
                 PathConfig {
                     keys_path: keys::get_default_keys_dir().unwrap(),
-                    download_path: dirs::home_dir()
-                        .unwrap_or_else(|| PathBuf::from("."))
-                        .join("rshare")
-                        .join("downloads"),
+                    download_path: profile::default_download_dir(),
+                    on_conflict: ConflictPolicy::default(),
                 }
             },
             server: vec![get_default_server_config().unwrap()],
@@ -49,10 +65,8 @@ impl Config {
             path: {
                 PathConfig {
                     keys_path: key_path,
-                    download_path: dirs::home_dir()
-                        .unwrap_or_else(|| PathBuf::from("."))
-                        .join("rshare")
-                        .join("downloads"),
+                    download_path: profile::default_download_dir(),
+                    on_conflict: ConflictPolicy::default(),
                 }
             },
             server: vec![get_default_server_config().unwrap()],
@@ -108,9 +122,7 @@ pub fn get_default_server(config: &Config) -> Result<ServerConfig> {
 }
 
 pub fn get_config_path() -> Result<PathBuf> {
-    let home = dirs::home_dir()
-        .ok_or_else(|| Error::FileError("Could not find home directory".to_string()))?;
-    Ok(home.join(".rshare").join("config.toml"))
+    Ok(profile::base_dir()?.join(CONFIG_FILE))
 }
 
 pub fn exists_config_at(config_path: &Path) -> bool {
@@ -142,9 +154,12 @@ pub fn save_config(config: &Config) -> Result<()> {
 }
 
 pub fn load_config() -> Result<Config> {
-    let config_path = get_config_path()?;
+    load_config_from(&get_config_path()?)
+}
 
-    let content = std::fs::read_to_string(&config_path)
+/// Load the config at `config_path` (another profile's, say)
+pub fn load_config_from(config_path: &Path) -> Result<Config> {
+    let content = std::fs::read_to_string(config_path)
         .map_err(|_e| Error::FileError("Failed to read config".to_string()))?;
 
     toml::from_str(&content).map_err(|_e| Error::InvalidInput("Invalid config file".to_string()))
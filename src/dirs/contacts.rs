@@ -1,3 +1,5 @@
+use crate::crypto::rotation::KeyRotation;
+use crate::dirs::profile;
 use crate::utils::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -8,11 +10,44 @@ pub struct Contact {
     pub name: String,
     pub public_key: String, // Hex-encoded
     pub added_at: String,   // Timestamp
+    /// When the user compared short authentication strings with the contact
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verified_at: Option<String>,
+}
+
+impl Contact {
+    /// Verification stays across key rotations, each new key is vouched for by the old one
+    pub fn is_verified(&self) -> bool {
+        self.verified_at.is_some()
+    }
+}
+
+/// Change made to a contact without the user, kept for auditing
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct AuditEntry {
+    pub contact: String,
+    pub at: String, // Timestamp
+    #[serde(flatten)]
+    pub event: AuditEvent,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AuditEvent {
+    /// The contact presented a rotation statement signed by the key we trusted
+    KeyRotated {
+        old_key: String,
+        new_key: String,
+        rotated_at: u64,
+    },
 }
 
 #[derive(Debug, Serialize, Deserialize, Default)]
 pub struct ContactList {
     pub contacts: HashMap<String, Contact>,
+    /// Automatic changes, oldest first
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub audit: Vec<AuditEntry>,
 }
 
 impl ContactList {
@@ -33,6 +68,7 @@ impl ContactList {
             name: name.clone(),
             public_key,
             added_at: chrono::Utc::now().to_rfc3339(),
+            verified_at: None,
         };
 
         self.contacts.insert(name, contact);
@@ -53,6 +89,53 @@ impl ContactList {
         Ok(())
     }
 
+    /// Record that the user checked the contact's key out of band
+    pub fn mark_verified(&mut self, name: &str) -> Result<()> {
+        let contact = self
+            .contacts
+            .get_mut(name)
+            .ok_or_else(|| Error::InvalidInput(format!("Contact '{}' not found", name)))?;
+        contact.verified_at = Some(chrono::Utc::now().to_rfc3339());
+        Ok(())
+    }
+
+    /// Move a contact to its new key along verified rotation statements, auditing each step
+    pub fn apply_rotation(&mut self, name: &str, rotations: &[KeyRotation]) -> Result<()> {
+        let contact = self
+            .contacts
+            .get_mut(name)
+            .ok_or_else(|| Error::InvalidInput(format!("Contact '{}' not found", name)))?;
+
+        for rotation in rotations {
+            if contact.public_key != rotation.old_key {
+                return Err(Error::InvalidInput(format!(
+                    "Key rotation does not start at the key of '{}'",
+                    name
+                )));
+            }
+
+            contact.public_key = rotation.new_key.clone();
+            self.audit.push(AuditEntry {
+                contact: name.to_string(),
+                at: chrono::Utc::now().to_rfc3339(),
+                event: AuditEvent::KeyRotated {
+                    old_key: rotation.old_key.clone(),
+                    new_key: rotation.new_key.clone(),
+                    rotated_at: rotation.rotated_at,
+                },
+            });
+        }
+        Ok(())
+    }
+
+    /// Audit entries of one contact, oldest first
+    pub fn audit_of(&self, name: &str) -> Vec<&AuditEntry> {
+        self.audit
+            .iter()
+            .filter(|entry| entry.contact == name)
+            .collect()
+    }
+
     /// List all contacts
     pub fn list(&self) -> Vec<&Contact> {
         self.contacts.values().collect()
@@ -61,9 +144,7 @@ impl ContactList {
 
 /// Get contacts file path
 fn get_contacts_path() -> Result<PathBuf> {
-    let home = dirs::home_dir()
-        .ok_or_else(|| Error::FileError("Could not find home directory".to_string()))?;
-    Ok(home.join(".rshare").join("contact.json"))
+    Ok(profile::base_dir()?.join("contact.json"))
 }
 
 /// Load contacts from disk
@@ -1,18 +1,25 @@
+#[cfg(unix)]
+use crate::agent::AgentClient;
+use crate::config::{KEY_ROTATIONS_FILE, MAX_KEY_ROTATIONS};
+use crate::crypto::identity::Identity;
+use crate::crypto::key_file::{self, KdfParams};
+use crate::crypto::rotation::KeyRotation;
+use crate::dirs::profile;
 use crate::utils::error::{Error, Result};
+use crate::utils::passphrase;
 use ed25519_dalek::{Signer, SigningKey, Verifier, VerifyingKey};
 use rand::TryRngCore;
 use rand::rngs::OsRng;
 use std::fs;
+use std::io::Write;
 use std::path::{Path, PathBuf};
 
 const PRIVATE_KEY_FILE: &str = "private.key";
 const PUBLIC_KEY_FILE: &str = "public.key";
 
-/// Get the default directory for storing keys
+/// Get the default directory for storing keys (in the active profile)
 pub fn get_default_keys_dir() -> Result<PathBuf> {
-    let home = dirs::home_dir()
-        .ok_or_else(|| Error::ConfigError("Could not determine home directory".to_string()))?;
-    Ok(home.join(".rshare").join("keys"))
+    Ok(profile::base_dir()?.join("keys"))
 }
 
 /// Check if keys exist at given path (or default)
@@ -43,11 +50,22 @@ pub fn generate_keys() -> Result<(SigningKey, VerifyingKey)> {
     Ok((signing_key, verifying_key))
 }
 
+/// Whether the private key at the given path is passphrase-protected
+pub fn is_encrypted_at(custom_dir: &Path) -> Result<bool> {
+    let private_bytes = fs::read(custom_dir.join(PRIVATE_KEY_FILE))
+        .map_err(|_e| Error::FileError("Failed to read private key".to_string()))?;
+    Ok(key_file::is_encrypted(&private_bytes))
+}
+
 /// Save keys to disk with proper OS-level security
+///
+/// With a passphrase the private key is stored encrypted (see `key_file::seal`),
+/// otherwise as the raw 32-byte secret.
 pub fn save_keys_to(
     signing_key: &SigningKey,
     verifying_key: &VerifyingKey,
     custom_dir: PathBuf,
+    passphrase: Option<&str>,
 ) -> Result<PathBuf> {
     // Create directory with restrictive permissions
     fs::create_dir_all(&custom_dir)
@@ -56,8 +74,37 @@ pub fn save_keys_to(
     let private_path = custom_dir.join(PRIVATE_KEY_FILE);
     let public_path = custom_dir.join(PUBLIC_KEY_FILE);
 
-    // Write private key
-    fs::write(&private_path, signing_key.to_bytes())
+    let private_bytes = match passphrase {
+        Some(passphrase) => {
+            key_file::seal(&signing_key.to_bytes(), passphrase, &KdfParams::default())?
+        }
+        None => signing_key.to_bytes().to_vec(),
+    };
+
+    // Write private key next to the old one and swap it in, so a failed write
+    // (e.g. while changing the passphrase) never leaves a broken identity behind
+    // The staged file is created owner-only, the key is never readable by others
+    let staged_path = custom_dir.join(format!("{}.tmp", PRIVATE_KEY_FILE));
+    match fs::remove_file(&staged_path) {
+        Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+            return Err(Error::FileError(
+                "Failed to remove stale private key".to_string(),
+            ));
+        }
+        _ => {}
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    options
+        .open(&staged_path)
+        .and_then(|mut file| file.write_all(&private_bytes))
+        .map_err(|_e| Error::FileError("Failed to write private key".to_string()))?;
+    fs::rename(&staged_path, &private_path)
         .map_err(|_e| Error::FileError("Failed to write private key".to_string()))?;
 
     // Write public key
@@ -71,15 +118,15 @@ pub fn save_keys_to(
 
         // Private key: only owner can read/write (600)
         fs::set_permissions(&private_path, fs::Permissions::from_mode(0o600))
-            .map_err(|_e| Error::FileError(format!("Failed to set private key permissions")))?;
+            .map_err(|_e| Error::FileError("Failed to set private key permissions".to_string()))?;
 
         // Public key: owner read/write, others read (644)
         fs::set_permissions(&public_path, fs::Permissions::from_mode(0o644))
-            .map_err(|_e| Error::FileError(format!("Failed to set public key permissions")))?;
+            .map_err(|_e| Error::FileError("Failed to set public key permissions".to_string()))?;
 
         // Directory: only owner access (700)
         fs::set_permissions(&custom_dir, fs::Permissions::from_mode(0o700))
-            .map_err(|_e| Error::FileError(format!("Failed to set directory permissions")))?;
+            .map_err(|_e| Error::FileError("Failed to set directory permissions".to_string()))?;
     }
 
     // On Windows, use security attributes
@@ -92,30 +139,69 @@ pub fn save_keys_to(
 }
 
 /// Load keys from disk (custom or default path)
+///
+/// A passphrase-protected private key is unlocked with `passphrase::read_passphrase`.
 pub fn load_keys_from(custom_dir: &Path) -> Result<(SigningKey, VerifyingKey)> {
-    let private_path = custom_dir.join(PRIVATE_KEY_FILE);
-    let public_path = custom_dir.join(PUBLIC_KEY_FILE);
+    load_keys(custom_dir, |private_path| {
+        passphrase::read_passphrase(&format!("Passphrase for {}: ", private_path.display()))
+    })
+}
 
-    // Read private key bytes
-    let private_bytes = fs::read(&private_path)
-        .map_err(|_e| Error::FileError("Failed to read private key".to_string()))?;
+/// Load keys from disk, unlocking the private key with the given passphrase if needed
+pub fn load_keys_with_passphrase(
+    custom_dir: &Path,
+    passphrase: &str,
+) -> Result<(SigningKey, VerifyingKey)> {
+    load_keys(custom_dir, |_private_path| Ok(passphrase.to_string()))
+}
 
-    let private_key_bytes: [u8; 32] = private_bytes
-        .try_into()
-        .map_err(|_e| Error::InvalidInput("Invalid private key size".to_string()))?;
+/// Identity for signing: the running agent if it holds this key, else the key on disk
+pub fn load_identity(custom_dir: &Path) -> Result<Identity> {
+    #[cfg(unix)]
+    if let Some(agent) = AgentClient::find()
+        && agent.public_key() == load_public_key_from(custom_dir)?
+    {
+        return Ok(Identity::Agent(agent));
+    }
 
-    // Read public key bytes
-    let public_bytes = fs::read(&public_path)
+    let (signing_key, _verifying_key) = load_keys_from(custom_dir)?;
+    Ok(Identity::Local(signing_key))
+}
+
+/// Load the public key alone, which never needs the passphrase
+pub fn load_public_key_from(custom_dir: &Path) -> Result<VerifyingKey> {
+    let public_bytes = fs::read(custom_dir.join(PUBLIC_KEY_FILE))
         .map_err(|_e| Error::FileError("Failed to read public key".to_string()))?;
 
     let public_key_bytes: [u8; 32] = public_bytes
         .try_into()
         .map_err(|_e| Error::InvalidInput("Invalid public key size".to_string()))?;
 
+    VerifyingKey::from_bytes(&public_key_bytes)
+        .map_err(|_e| Error::InvalidInput("Invalid public key".to_string()))
+}
+
+fn load_keys(
+    custom_dir: &Path,
+    passphrase: impl FnOnce(&Path) -> Result<String>,
+) -> Result<(SigningKey, VerifyingKey)> {
+    let private_path = custom_dir.join(PRIVATE_KEY_FILE);
+
+    // Read private key bytes
+    let private_bytes = fs::read(&private_path)
+        .map_err(|_e| Error::FileError("Failed to read private key".to_string()))?;
+
+    let private_key_bytes: [u8; 32] = if key_file::is_encrypted(&private_bytes) {
+        key_file::open(&private_bytes, &passphrase(&private_path)?)?
+    } else {
+        private_bytes
+            .try_into()
+            .map_err(|_e| Error::InvalidInput("Invalid private key size".to_string()))?
+    };
+
     // Construct keys
     let signing_key = SigningKey::from_bytes(&private_key_bytes);
-    let verifying_key = VerifyingKey::from_bytes(&public_key_bytes)
-        .map_err(|_e| Error::InvalidInput("Invalid public key".to_string()))?;
+    let verifying_key = load_public_key_from(custom_dir)?;
 
     Ok((signing_key, verifying_key))
 }
@@ -132,9 +218,37 @@ pub fn validate_keypair(signing_key: &SigningKey, verifying_key: &VerifyingKey)
     Ok(())
 }
 
+/// Our own key rotation statements, oldest first (none if the key was never rotated)
+pub fn load_rotations_from(custom_dir: &Path) -> Result<Vec<KeyRotation>> {
+    let path = custom_dir.join(KEY_ROTATIONS_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let content = fs::read_to_string(&path)?;
+    serde_json::from_str(&content)
+        .map_err(|_e| Error::ConfigError("Invalid key rotations json file".to_string()))
+}
+
+/// The rotation statements a sender presents: the most recent ones
+pub fn recent_rotations_from(custom_dir: &Path) -> Result<Vec<KeyRotation>> {
+    let mut rotations = load_rotations_from(custom_dir)?;
+    let excess = rotations.len().saturating_sub(MAX_KEY_ROTATIONS);
+    rotations.drain(..excess);
+    Ok(rotations)
+}
+
+/// Save our key rotation statements
+pub fn save_rotations_to(custom_dir: &Path, rotations: &[KeyRotation]) -> Result<()> {
+    let content = serde_json::to_string_pretty(rotations)
+        .map_err(|_e| Error::ConfigError("Failed to serialize key rotations".to_string()))?;
+    fs::write(custom_dir.join(KEY_ROTATIONS_FILE), content)?;
+    Ok(())
+}
+
 /// Get public key fingerprint for display (from custom or default path)
 pub fn get_public_key_fingerprint_from(custom_dir: &Path) -> Result<String> {
-    let (_, verifying_key) = load_keys_from(custom_dir)?;
+    let verifying_key = load_public_key_from(custom_dir)?;
     let bytes = verifying_key.to_bytes();
     Ok(hex::encode(&bytes[..8])) // First 8 bytes as hex
 }
@@ -1,3 +1,5 @@
 pub mod config;
 pub mod contacts;
 pub mod keys;
+pub mod offers;
+pub mod profile;
@@ -0,0 +1,73 @@
+use crate::config::MAX_SEEN_OFFERS;
+use crate::dirs::profile;
+use crate::utils::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Ids of offers already accepted, kept until they expire so a replay is refused
+#[derive(Debug, Serialize, Deserialize, Default)]
+pub struct SeenOffers {
+    /// Offer id (hex) -> expiry (Unix seconds)
+    pub offers: HashMap<String, u64>,
+}
+
+impl SeenOffers {
+    pub fn contains(&self, offer_id: &str) -> bool {
+        self.offers.contains_key(offer_id)
+    }
+
+    /// Remember an offer, forgetting expired ones
+    ///
+    /// `retain_until` is the offer's expiry plus the tolerated clock skew, after
+    /// which a replay is refused as expired anyway. Ids still within that window are
+    /// never dropped, so with the cache full of them the new offer is refused instead:
+    /// accepting it without remembering it would let it be replayed.
+    pub fn insert(&mut self, offer_id: String, retain_until: u64, now: u64) -> Result<()> {
+        self.offers.retain(|_id, until| *until >= now);
+
+        if self.offers.len() >= MAX_SEEN_OFFERS {
+            return Err(Error::SessionError(format!(
+                "Too many recent offers ({}), try again once some expire",
+                MAX_SEEN_OFFERS
+            )));
+        }
+
+        self.offers.insert(offer_id, retain_until);
+        Ok(())
+    }
+}
+
+/// Get seen offers file path
+fn get_seen_offers_path() -> Result<PathBuf> {
+    Ok(profile::base_dir()?.join("seen_offers.json"))
+}
+
+/// Load the seen offers cache from disk
+pub fn load_seen_offers() -> Result<SeenOffers> {
+    let path = get_seen_offers_path()?;
+
+    if !path.exists() {
+        return Ok(SeenOffers::default());
+    }
+
+    let content = std::fs::read_to_string(&path)?;
+    serde_json::from_str(&content)
+        .map_err(|_e| Error::ConfigError("Invalid seen offers json file".to_string()))
+}
+
+/// Save the seen offers cache to disk
+pub fn save_seen_offers(seen: &SeenOffers) -> Result<()> {
+    let path = get_seen_offers_path()?;
+
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .map_err(|_e| Error::FileError("Failed to create config directory".to_string()))?;
+    }
+
+    let content = serde_json::to_string_pretty(seen)
+        .map_err(|_e| Error::ConfigError("Failed to serialize seen offers".to_string()))?;
+    std::fs::write(&path, content)?;
+
+    Ok(())
+}
@@ -0,0 +1,192 @@
+use crate::config::{
+    ACTIVE_PROFILE_FILE, DEFAULT_PROFILE, MAX_PROFILE_NAME_LEN, PROFILE_ENV, PROFILES_DIR,
+};
+use crate::utils::error::{Error, Result};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+/// Profile chosen for this process with `--profile`
+static SELECTED: Mutex<Option<String>> = Mutex::new(None);
+
+/// Use profile `name` for the rest of this process (`--profile`, `profile create`)
+pub fn select(name: &str) -> Result<()> {
+    validate_name(name)?;
+    let mut selected = SELECTED
+        .lock()
+        .map_err(|_e| Error::ConfigError("Profile selection is poisoned".to_string()))?;
+    *selected = Some(name.to_string());
+    Ok(())
+}
+
+/// Active profile: `--profile`, else `$RSHARE_PROFILE`, else the one set with `rs profile use`
+pub fn active() -> Result<String> {
+    let selected = SELECTED
+        .lock()
+        .map_err(|_e| Error::ConfigError("Profile selection is poisoned".to_string()))?
+        .clone();
+    if let Some(name) = selected {
+        return Ok(name);
+    }
+
+    if let Ok(name) = std::env::var(PROFILE_ENV)
+        && !name.is_empty()
+    {
+        validate_name(&name)?;
+        return Ok(name);
+    }
+
+    let active_path = root_dir()?.join(ACTIVE_PROFILE_FILE);
+    if active_path.exists() {
+        let name = fs::read_to_string(&active_path)?.trim().to_string();
+        validate_name(&name)?;
+        return Ok(name);
+    }
+
+    Ok(DEFAULT_PROFILE.to_string())
+}
+
+/// Directory of the active profile, holding its config, contacts and default keys
+pub fn base_dir() -> Result<PathBuf> {
+    let name = active()?;
+    let dir = dir_of(&name)?;
+
+    if name != DEFAULT_PROFILE && !dir.is_dir() {
+        return Err(Error::ConfigError(format!(
+            "Profile '{}' does not exist, create it with `rs profile create {}`",
+            name, name
+        )));
+    }
+    Ok(dir)
+}
+
+/// Directory of profile `name`, whether it exists or not
+pub fn dir_of(name: &str) -> Result<PathBuf> {
+    let root = root_dir()?;
+    if name == DEFAULT_PROFILE {
+        return Ok(root);
+    }
+    Ok(root.join(PROFILES_DIR).join(name))
+}
+
+/// Where the active profile's downloads go unless the config says otherwise
+pub fn default_download_dir() -> PathBuf {
+    let downloads = dirs::home_dir()
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join("rshare");
+
+    match active() {
+        Ok(name) if name != DEFAULT_PROFILE => downloads.join(name).join("downloads"),
+        _ => downloads.join("downloads"),
+    }
+}
+
+pub fn exists(name: &str) -> Result<bool> {
+    Ok(name == DEFAULT_PROFILE || dir_of(name)?.is_dir())
+}
+
+/// All profiles, the default one first
+pub fn list() -> Result<Vec<String>> {
+    let mut names = Vec::new();
+    let profiles_dir = root_dir()?.join(PROFILES_DIR);
+
+    if profiles_dir.is_dir() {
+        for entry in fs::read_dir(&profiles_dir)? {
+            let entry = entry?;
+            if let Some(name) = entry.file_name().to_str()
+                && entry.path().is_dir()
+                && validate_name(name).is_ok()
+            {
+                names.push(name.to_string());
+            }
+        }
+    }
+
+    names.sort();
+    names.insert(0, DEFAULT_PROFILE.to_string());
+    Ok(names)
+}
+
+/// Create the directory of a new profile (owner-only on Unix)
+pub fn create(name: &str) -> Result<PathBuf> {
+    validate_name(name)?;
+    if exists(name)? {
+        return Err(Error::InvalidInput(format!(
+            "Profile '{}' already exists",
+            name
+        )));
+    }
+
+    let dir = dir_of(name)?;
+    fs::create_dir_all(&dir)
+        .map_err(|_e| Error::FileError("Failed to create profile directory".to_string()))?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&dir, fs::Permissions::from_mode(0o700)).map_err(|_e| {
+            Error::FileError("Failed to set profile directory permissions".to_string())
+        })?;
+    }
+
+    Ok(dir)
+}
+
+/// Remove the directory of a profile that failed to initialize
+pub fn discard(name: &str) -> Result<()> {
+    if name == DEFAULT_PROFILE {
+        return Ok(());
+    }
+    let dir = dir_of(name)?;
+    if dir.is_dir() {
+        fs::remove_dir_all(&dir)?;
+    }
+    Ok(())
+}
+
+/// Make `name` the profile used when neither `--profile` nor `$RSHARE_PROFILE` is given
+pub fn set_default(name: &str) -> Result<()> {
+    validate_name(name)?;
+    if !exists(name)? {
+        return Err(Error::InvalidInput(format!(
+            "Profile '{}' does not exist",
+            name
+        )));
+    }
+
+    let active_path = root_dir()?.join(ACTIVE_PROFILE_FILE);
+    if name == DEFAULT_PROFILE {
+        if active_path.exists() {
+            fs::remove_file(&active_path)?;
+        }
+        return Ok(());
+    }
+
+    fs::create_dir_all(root_dir()?)?;
+    fs::write(&active_path, format!("{}\n", name))?;
+    Ok(())
+}
+
+/// Profile names become directory names: letters, digits, `-` and `_` only
+pub fn validate_name(name: &str) -> Result<()> {
+    let valid = !name.is_empty()
+        && name.len() <= MAX_PROFILE_NAME_LEN
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+
+    if !valid {
+        return Err(Error::InvalidInput(format!(
+            "Invalid profile name '{}' (1-{} letters, digits, '-' or '_')",
+            name, MAX_PROFILE_NAME_LEN
+        )));
+    }
+    Ok(())
+}
+
+/// ~/.rshare, home of the default profile and of the other profiles' directories
+fn root_dir() -> Result<PathBuf> {
+    let home = dirs::home_dir()
+        .ok_or_else(|| Error::FileError("Could not find home directory".to_string()))?;
+    Ok(home.join(".rshare"))
+}
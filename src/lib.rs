@@ -1,3 +1,5 @@
+#[cfg(unix)]
+pub mod agent;
 pub mod args;
 pub mod cli;
 pub mod config;
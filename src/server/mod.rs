@@ -1,3 +1,5 @@
+pub mod offer;
+pub mod protocol;
 mod relay;
 
 pub use relay::*;
@@ -0,0 +1,232 @@
+use crate::config::{
+    MAX_OFFER_LIFETIME_SECS, OFFER_CLOCK_SKEW_SECS, OFFER_LIFETIME_SECS, OFFER_MAGIC, OFFER_VERSION,
+};
+use crate::crypto::signing;
+use crate::utils::error::{Error, Result};
+use aes_gcm::aead::OsRng;
+use aes_gcm::aead::rand_core::RngCore;
+use ed25519_dalek::{Signature, Signer, VerifyingKey};
+
+/// Transfer offer the sender signs and the receiver checks before accepting
+///
+/// Binds the file metadata to the recipient, the sender's ephemeral key and a
+/// validity window, and carries a random id so a captured offer cannot be replayed.
+///
+/// Canonical encoding, integers big-endian, strings as u32 length + UTF-8:
+/// `OFFER_MAGIC, version u8, offer_id [16], created_at u64, expires_at u64,
+/// file_size u64, sender_fp, recipient_fp, sender_ephemeral_key, filename, file_hash`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Offer {
+    pub version: u8,
+    pub offer_id: [u8; 16],
+    /// Unix timestamps (seconds)
+    pub created_at: u64,
+    pub expires_at: u64,
+    pub file_size: u64,
+    pub sender_fp: String,
+    pub recipient_fp: String,
+    pub sender_ephemeral_key: String,
+    pub filename: String,
+    pub file_hash: String,
+}
+
+/// Encoded offer and signature as they travel through the relay, both hex-encoded
+#[derive(Debug, Clone)]
+pub struct SignedOffer {
+    pub offer: String,
+    pub signature: String,
+}
+
+impl Offer {
+    /// New offer with a random id, valid for `OFFER_LIFETIME_SECS` from now
+    pub fn new(
+        sender_fp: &str,
+        recipient_fp: &str,
+        sender_ephemeral_key: &str,
+        filename: &str,
+        file_size: u64,
+        file_hash: &str,
+    ) -> Self {
+        let mut offer_id = [0u8; 16];
+        OsRng.fill_bytes(&mut offer_id);
+        let created_at = now();
+
+        Offer {
+            version: OFFER_VERSION,
+            offer_id,
+            created_at,
+            expires_at: created_at + OFFER_LIFETIME_SECS,
+            file_size,
+            sender_fp: sender_fp.to_string(),
+            recipient_fp: recipient_fp.to_string(),
+            sender_ephemeral_key: sender_ephemeral_key.to_string(),
+            filename: filename.to_string(),
+            file_hash: file_hash.to_string(),
+        }
+    }
+
+    pub fn offer_id_hex(&self) -> String {
+        hex::encode(self.offer_id)
+    }
+
+    /// Canonical encoding, the exact bytes that are signed
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = OFFER_MAGIC.as_bytes().to_vec();
+        bytes.push(self.version);
+        bytes.extend_from_slice(&self.offer_id);
+        for number in [self.created_at, self.expires_at, self.file_size] {
+            bytes.extend_from_slice(&number.to_be_bytes());
+        }
+        for text in [
+            &self.sender_fp,
+            &self.recipient_fp,
+            &self.sender_ephemeral_key,
+            &self.filename,
+            &self.file_hash,
+        ] {
+            bytes.extend_from_slice(&(text.len() as u32).to_be_bytes());
+            bytes.extend_from_slice(text.as_bytes());
+        }
+        bytes
+    }
+
+    /// Decode the canonical encoding, rejecting unknown versions and trailing bytes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        let mut reader = Reader { bytes };
+
+        if reader.take(OFFER_MAGIC.len())? != OFFER_MAGIC.as_bytes() {
+            return Err(invalid("not an offer"));
+        }
+        let version = reader.take(1)?[0];
+        if version != OFFER_VERSION {
+            return Err(Error::SessionError(format!(
+                "Unsupported offer version {} (we speak v{})",
+                version, OFFER_VERSION
+            )));
+        }
+
+        let offer = Offer {
+            version,
+            offer_id: reader
+                .take(16)?
+                .try_into()
+                .map_err(|_e| invalid("offer id"))?,
+            created_at: reader.u64()?,
+            expires_at: reader.u64()?,
+            file_size: reader.u64()?,
+            sender_fp: reader.string()?,
+            recipient_fp: reader.string()?,
+            sender_ephemeral_key: reader.string()?,
+            filename: reader.string()?,
+            file_hash: reader.string()?,
+        };
+
+        if !reader.bytes.is_empty() {
+            return Err(invalid("trailing bytes"));
+        }
+        Ok(offer)
+    }
+
+    /// Sign the canonical encoding with the sender's identity key
+    pub fn sign(&self, signer: &impl Signer<Signature>) -> Result<SignedOffer> {
+        let bytes = self.to_bytes();
+        let signature = signing::sign_bytes(signer, &bytes)?;
+
+        Ok(SignedOffer {
+            offer: hex::encode(bytes),
+            signature: hex::encode(signature.to_bytes()),
+        })
+    }
+
+    /// Check that the offer is meant for us, for this session, and still valid at `now`
+    pub fn validate(
+        &self,
+        my_fingerprint: &str,
+        sender_ephemeral_key: &str,
+        now: u64,
+    ) -> Result<()> {
+        if self.recipient_fp != my_fingerprint {
+            return Err(Error::SessionError(
+                "Offer is addressed to another recipient".to_string(),
+            ));
+        }
+        if self.sender_ephemeral_key != sender_ephemeral_key {
+            return Err(Error::SessionError(
+                "Offer belongs to another session (ephemeral key mismatch)".to_string(),
+            ));
+        }
+        if self.expires_at <= self.created_at
+            || self.expires_at - self.created_at > MAX_OFFER_LIFETIME_SECS
+        {
+            return Err(Error::SessionError(
+                "Offer has an invalid validity window".to_string(),
+            ));
+        }
+        if self.created_at > now + OFFER_CLOCK_SKEW_SECS {
+            return Err(Error::SessionError(
+                "Offer was created in the future (check the clocks)".to_string(),
+            ));
+        }
+        if now > self.expires_at + OFFER_CLOCK_SKEW_SECS {
+            return Err(Error::SessionError("Offer has expired".to_string()));
+        }
+        Ok(())
+    }
+}
+
+impl SignedOffer {
+    /// Verify the sender's signature and decode the offer
+    pub fn verify(&self, sender_key: &VerifyingKey) -> Result<Offer> {
+        let bytes = hex::decode(&self.offer).map_err(|_e| invalid("offer hex"))?;
+        let signature_bytes =
+            hex::decode(&self.signature).map_err(|_e| invalid("signature hex"))?;
+        let signature = Signature::from_bytes(
+            signature_bytes
+                .as_slice()
+                .try_into()
+                .map_err(|_e| invalid("signature length"))?,
+        );
+
+        signing::verify_bytes(sender_key, &bytes, &signature)
+            .map_err(|_e| Error::SessionError("Offer signature is invalid".to_string()))?;
+        Offer::from_bytes(&bytes)
+    }
+}
+
+/// Current Unix time in seconds
+pub fn now() -> u64 {
+    chrono::Utc::now().timestamp().max(0) as u64
+}
+
+fn invalid(what: &str) -> Error {
+    Error::SessionError(format!("Malformed offer: {}", what))
+}
+
+/// Cursor over an encoded offer
+struct Reader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8]> {
+        if self.bytes.len() < len {
+            return Err(invalid("truncated"));
+        }
+        let (head, rest) = self.bytes.split_at(len);
+        self.bytes = rest;
+        Ok(head)
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let bytes = self.take(8)?;
+        Ok(u64::from_be_bytes(
+            bytes.try_into().map_err(|_e| invalid("integer"))?,
+        ))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = u32::from_be_bytes(self.take(4)?.try_into().map_err(|_e| invalid("length"))?);
+        let bytes = self.take(len as usize)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_e| invalid("text is not UTF-8"))
+    }
+}
@@ -0,0 +1,442 @@
+use crate::config::{
+    ACCEPT_SIGNAL_PREFIX, COMPRESSION_ZSTD, EPHEMERAL_KEY_CONTEXT, ERROR_SIGNAL_PREFIX,
+    HANDSHAKE_NOISE_IK, HELLO_SIGNAL_PREFIX, KEM_ALGORITHM, KEY_SCHEDULE_VERSIONS,
+    NEGOTIATION_TIMEOUT_MILLIS, NOISE_PROLOGUE_LABEL, NOISE_SIGNAL_PREFIX, PROTOCOL_VERSION,
+};
+use crate::crypto::encryption::CipherSuite;
+use crate::server::{TransferRole, TransferSession};
+use crate::utils::error::{Error, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+/// Receiver's transfer parameters, sent right after the socket is paired
+///
+/// Wire format: `HELLO:<json>\n`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hello {
+    pub version: u32,
+    /// Bytes the receiver already holds from an earlier interrupted transfer
+    #[serde(default)]
+    pub resume_offset: u64,
+    /// Compression codecs the receiver can decode (absent on older receivers)
+    #[serde(default)]
+    pub compression: Vec<String>,
+    /// Data streams the receiver wants to use, already capped by the relay
+    #[serde(default = "single_stream")]
+    pub streams: u32,
+    /// Receiver's identity signature over its ephemeral key (`ephemeral_key_message`)
+    #[serde(default)]
+    pub key_signature: String,
+    /// Session setups the receiver supports besides the signed ephemeral keys
+    #[serde(default)]
+    pub handshakes: Vec<String>,
+}
+
+/// Sender's confirmation of the parameters, sent before any file data
+///
+/// Wire format: `ACCEPT:<json>\n`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Accept {
+    pub version: u32,
+    /// Offset the sender will continue from (0 if resume was declined)
+    #[serde(default)]
+    pub resume_offset: u64,
+    /// Codec applied to data chunks, which then carry a compression flag
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+    /// Data streams both sides open (never more than the receiver asked for)
+    #[serde(default = "single_stream")]
+    pub streams: u32,
+    /// Sender's identity signature over its ephemeral key (`ephemeral_key_message`)
+    #[serde(default)]
+    pub key_signature: String,
+    /// Session setup chosen from the receiver's list (none: signed ephemeral keys)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub handshake: Option<String>,
+}
+
+fn single_stream() -> u32 {
+    1
+}
+
+impl Hello {
+    pub fn new(resume_offset: u64, streams: u32, key_signature: String) -> Self {
+        Hello {
+            version: PROTOCOL_VERSION,
+            resume_offset,
+            compression: vec![COMPRESSION_ZSTD.to_string()],
+            streams,
+            key_signature,
+            handshakes: vec![HANDSHAKE_NOISE_IK.to_string()],
+        }
+    }
+
+    /// Whether the receiver can decode `codec`
+    pub fn supports_compression(&self, codec: &str) -> bool {
+        self.compression.iter().any(|c| c == codec)
+    }
+
+    /// Whether the receiver can run the `handshake` session setup
+    pub fn supports_handshake(&self, handshake: &str) -> bool {
+        self.handshakes.iter().any(|h| h == handshake)
+    }
+}
+
+impl Accept {
+    pub fn new(
+        resume_offset: u64,
+        compression: Option<String>,
+        streams: u32,
+        key_signature: String,
+        handshake: Option<String>,
+    ) -> Self {
+        Accept {
+            version: PROTOCOL_VERSION,
+            resume_offset,
+            compression,
+            streams,
+            key_signature,
+            handshake,
+        }
+    }
+}
+
+/// Signed file hash delivered in the final chunk when the sender hashes while sending
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HashTrailer {
+    pub hash: String,
+    /// Hex-encoded Ed25519 signature over `trailer_message(..)`
+    pub signature: String,
+}
+
+impl HashTrailer {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes)
+            .map_err(|_e| Error::SessionError("Invalid hash trailer".to_string()))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+}
+
+/// Message signed in a hash trailer, bound to the session and the announced file
+pub fn trailer_message(session_id: &str, filename: &str, filesize: u64, hash: &str) -> String {
+    format!("{}|{}|{}|{}", session_id, filename, filesize, hash)
+}
+
+/// Message each side signs with its identity key to vouch for its ephemeral key
+///
+/// Binds the key to the signer's role, both identities and the session, so the relay
+/// can neither swap keys nor replay a signature in another session or direction.
+/// `handshakes` are the setups the signer offers (receiver) or picked (sender): a relay
+/// that strips them to force the signed ephemeral keys breaks the signature. Without
+/// any, the message is the one older peers sign.
+pub fn ephemeral_key_message(
+    role: TransferRole,
+    session_id: &str,
+    sender_fp: &str,
+    receiver_fp: &str,
+    ephemeral_key: &str,
+    handshakes: &[String],
+) -> String {
+    let mut message = format!(
+        "{}|{}|{}|{}|{}|{}",
+        EPHEMERAL_KEY_CONTEXT,
+        role.as_str(),
+        session_id,
+        sender_fp,
+        receiver_fp,
+        ephemeral_key
+    );
+    if !handshakes.is_empty() {
+        message.push('|');
+        message.push_str(&handshakes.join(","));
+    }
+    message
+}
+
+/// One Noise handshake message
+///
+/// Wire format: `NOISE:<json>\n`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HandshakeMessage {
+    /// Hex-encoded Noise message
+    pub message: String,
+}
+
+/// Receiver's payload in the first Noise message (empty from older receivers)
+///
+/// Carried inside the handshake rather than in HELLO, so the relay can neither read
+/// nor strip it, and older senders (which ignore the payload) still agree on the prologue.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct NoiseInit {
+    /// Key schedule versions the receiver supports
+    #[serde(default)]
+    pub key_schedules: Vec<u32>,
+    /// Post-quantum KEM key for a hybrid exchange (absent on older receivers)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kem: Option<KemOffer>,
+    /// Cipher suites the receiver supports, most preferred first (absent on older
+    /// receivers, which only speak AES-256-GCM)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub cipher_suites: Vec<String>,
+}
+
+/// Receiver's KEM key, the sender encapsulates to it if either side asks for hybrid
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KemOffer {
+    pub algorithm: String,
+    /// Hex-encoded encapsulation key
+    pub public_key: String,
+    /// The receiver refuses a classical-only session
+    #[serde(default)]
+    pub required: bool,
+}
+
+impl NoiseInit {
+    pub fn new(kem_public_key: String, kem_required: bool, cipher_suites: &[CipherSuite]) -> Self {
+        NoiseInit {
+            key_schedules: KEY_SCHEDULE_VERSIONS.to_vec(),
+            kem: Some(KemOffer {
+                algorithm: KEM_ALGORITHM.to_string(),
+                public_key: kem_public_key,
+                required: kem_required,
+            }),
+            cipher_suites: cipher_suites
+                .iter()
+                .map(|suite| suite.as_str().to_string())
+                .collect(),
+        }
+    }
+
+    /// The receiver's KEM key, if it is one we can use
+    pub fn kem_offer(&self) -> Option<&KemOffer> {
+        self.kem
+            .as_ref()
+            .filter(|offer| offer.algorithm == KEM_ALGORITHM)
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if bytes.is_empty() {
+            return Ok(NoiseInit::default());
+        }
+        serde_json::from_slice(bytes)
+            .map_err(|_e| Error::SessionError("Invalid handshake payload".to_string()))
+    }
+
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Whether the receiver can run key schedule `version`
+    pub fn supports_key_schedule(&self, version: u32) -> bool {
+        self.key_schedules.contains(&version)
+    }
+
+    /// Newest key schedule both sides can run
+    pub fn key_schedule(&self) -> Option<u32> {
+        KEY_SCHEDULE_VERSIONS
+            .iter()
+            .copied()
+            .find(|version| self.supports_key_schedule(*version))
+    }
+
+    /// The receiver's cipher suites this build knows, in its order of preference
+    pub fn cipher_suites(&self) -> Vec<CipherSuite> {
+        if self.cipher_suites.is_empty() {
+            return vec![CipherSuite::Aes256Gcm];
+        }
+        self.cipher_suites
+            .iter()
+            .filter_map(|name| CipherSuite::from_name(name))
+            .collect()
+    }
+}
+
+/// Sender's payload in the Noise reply
+///
+/// Wire format: the chosen key schedule version (4B big-endian), then from key schedule
+/// v2 on the chosen cipher suite (1B id), then the ML-KEM ciphertext in hybrid sessions.
+/// The session keys themselves come from the handshake, nothing secret is sent.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NoiseReply {
+    pub key_schedule: u32,
+    /// Chosen cipher suite, AES-256-GCM before key schedule v2
+    pub cipher_suite: CipherSuite,
+    pub kem_ciphertext: Option<Vec<u8>>,
+}
+
+impl NoiseReply {
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut payload = self.key_schedule.to_be_bytes().to_vec();
+        if self.key_schedule >= 2 {
+            payload.push(self.cipher_suite.id());
+        }
+        if let Some(ciphertext) = &self.kem_ciphertext {
+            payload.extend_from_slice(ciphertext);
+        }
+        payload
+    }
+
+    pub fn from_bytes(payload: &[u8]) -> Result<Self> {
+        let invalid = || Error::CryptoError("Invalid handshake reply".to_string());
+
+        let (version, rest) = payload.split_at_checked(4).ok_or_else(invalid)?;
+        let version = u32::from_be_bytes(version.try_into().map_err(|_e| invalid())?);
+        let (cipher_suite, ciphertext) = if version >= 2 {
+            let (id, ciphertext) = rest.split_first().ok_or_else(invalid)?;
+            let suite = CipherSuite::from_id(*id).ok_or_else(|| {
+                Error::CryptoError(format!("Unknown cipher suite {} in handshake", id))
+            })?;
+            (suite, ciphertext)
+        } else {
+            (CipherSuite::Aes256Gcm, rest)
+        };
+
+        Ok(NoiseReply {
+            key_schedule: version,
+            cipher_suite,
+            kem_ciphertext: (!ciphertext.is_empty()).then(|| ciphertext.to_vec()),
+        })
+    }
+}
+
+/// Noise prologue, binding the handshake to the session and the negotiated parameters
+///
+/// Once the Noise handshake runs, a relay that tampered with HELLO or ACCEPT (e.g. the
+/// codec, streams or resume offset) makes it fail. Stripping the handshake itself to
+/// force the legacy setup never gets here, the signed ephemeral keys catch that.
+pub fn noise_prologue(session_id: &str, hello: &Hello, accept: &Accept) -> Result<Vec<u8>> {
+    Ok(format!(
+        "{}|{}|{}|{}",
+        NOISE_PROLOGUE_LABEL,
+        session_id,
+        serde_json::to_string(hello)?,
+        serde_json::to_string(accept)?
+    )
+    .into_bytes())
+}
+
+/// Send a control message (`DONE`, `ERROR:<reason>`) to the peer
+///
+/// Once the session has a control key the line is sealed: `<message>|<tag>\n`.
+pub async fn send_control(session: &mut TransferSession, message: &str) -> Result<()> {
+    let line = match session.control_key() {
+        Some(key) => key.seal(session.role(), message),
+        None => message.to_string(),
+    };
+    session.write_all(format!("{}\n", line).as_bytes()).await?;
+    session.flush().await
+}
+
+/// Tell the peer why the transfer is rejected
+pub async fn send_error(session: &mut TransferSession, reason: &str) -> Result<()> {
+    send_control(session, &format!("{}{}", ERROR_SIGNAL_PREFIX, reason)).await
+}
+
+/// Wait for the peer's next control message, checking its tag if the session has a control key
+pub async fn recv_control(session: &mut TransferSession) -> Result<String> {
+    let line = session.read_line().await?;
+    let peer = match session.role() {
+        TransferRole::Sender => TransferRole::Receiver,
+        TransferRole::Receiver => TransferRole::Sender,
+    };
+
+    match session.control_key() {
+        Some(key) => key.open(peer, &line),
+        None => Ok(line),
+    }
+}
+
+/// Send one Noise handshake message
+pub async fn send_handshake(session: &mut TransferSession, message: &[u8]) -> Result<()> {
+    let message = HandshakeMessage {
+        message: hex::encode(message),
+    };
+    send_message(session, NOISE_SIGNAL_PREFIX, &message).await
+}
+
+/// Wait for the peer's next Noise handshake message
+pub async fn recv_handshake(session: &mut TransferSession) -> Result<Vec<u8>> {
+    let message: HandshakeMessage = recv_message(session, NOISE_SIGNAL_PREFIX).await?;
+    hex::decode(message.message)
+        .map_err(|_e| Error::SessionError("Invalid handshake message".to_string()))
+}
+
+/// Send the receiver's HELLO
+pub async fn send_hello(session: &mut TransferSession, hello: &Hello) -> Result<()> {
+    send_message(session, HELLO_SIGNAL_PREFIX, hello).await
+}
+
+/// Wait for the receiver's HELLO (sender side)
+pub async fn recv_hello(session: &mut TransferSession) -> Result<Hello> {
+    let hello: Hello = recv_message(session, HELLO_SIGNAL_PREFIX).await?;
+    check_version(hello.version)?;
+    Ok(hello)
+}
+
+/// Send the sender's ACCEPT
+pub async fn send_accept(session: &mut TransferSession, accept: &Accept) -> Result<()> {
+    send_message(session, ACCEPT_SIGNAL_PREFIX, accept).await
+}
+
+/// Wait for the sender's ACCEPT (receiver side)
+pub async fn recv_accept(session: &mut TransferSession) -> Result<Accept> {
+    let accept: Accept = recv_message(session, ACCEPT_SIGNAL_PREFIX).await?;
+    check_version(accept.version)?;
+    Ok(accept)
+}
+
+/// Write `<prefix><json>\n` and flush
+async fn send_message<T: Serialize>(
+    session: &mut TransferSession,
+    prefix: &str,
+    message: &T,
+) -> Result<()> {
+    let line = format!("{}{}\n", prefix, serde_json::to_string(message)?);
+    session.write_all(line.as_bytes()).await?;
+    session.flush().await
+}
+
+/// Read a `<prefix><json>\n` line, surfacing `ERROR:` lines from the peer
+async fn recv_message<T: DeserializeOwned>(
+    session: &mut TransferSession,
+    prefix: &str,
+) -> Result<T> {
+    let line = tokio::time::timeout(
+        tokio::time::Duration::from_millis(NEGOTIATION_TIMEOUT_MILLIS),
+        session.read_line(),
+    )
+    .await
+    .map_err(|_e| {
+        Error::SessionError("Peer did not negotiate the transfer (outdated client?)".to_string())
+    })??;
+
+    if let Some(reason) = line.strip_prefix(ERROR_SIGNAL_PREFIX) {
+        return Err(Error::SessionError(format!(
+            "Peer rejected the transfer: {}",
+            reason
+        )));
+    }
+
+    let payload = line.strip_prefix(prefix).ok_or_else(|| {
+        Error::SessionError(format!(
+            "Expected {} message from peer",
+            prefix.trim_end_matches(':')
+        ))
+    })?;
+
+    serde_json::from_str(payload)
+        .map_err(|_e| Error::SessionError("Invalid negotiation message".to_string()))
+}
+
+fn check_version(version: u32) -> Result<()> {
+    if version != PROTOCOL_VERSION {
+        return Err(Error::SessionError(format!(
+            "Protocol version mismatch: peer speaks v{}, we speak v{}",
+            version, PROTOCOL_VERSION
+        )));
+    }
+    Ok(())
+}
@@ -1,9 +1,22 @@
-use crate::config::{ACK_SIGNAL, BUFFER_SIZE, MAX_DONE_WAIT_MILLIS, READY_SIGNAL};
+use crate::config::{
+    ACK_SIGNAL, BUFFER_SIZE, MAX_CONTROL_LINE_LEN, MAX_DONE_WAIT_MILLIS, MAX_FRAME_SIZE,
+    READY_SIGNAL, STRIPE_QUEUE_FRAMES,
+};
+use crate::crypto::key_schedule::ControlKey;
+use crate::crypto::rotation::KeyRotation;
+use crate::server::offer::SignedOffer;
 use crate::utils::error::{Error, Result};
 use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
-use tokio::io::{AsyncReadExt, AsyncWriteExt, BufReader, BufWriter, ReadHalf, WriteHalf};
+use std::sync::Arc;
+use std::task::Poll;
+use tokio::io::{
+    AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader, BufWriter,
+    ReadHalf, WriteHalf,
+};
 use tokio::net::{TcpSocket, TcpStream};
+use tokio::sync::{Mutex, mpsc};
+use tokio::task::JoinHandle;
 
 /// Transfer role in the relay session
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -36,6 +49,12 @@ struct ServeRequest {
     file_hash: String,
     #[serde(rename = "senderEphemeralKey")]
     sender_ephemeral_key: String,
+    offer: String,
+    #[serde(rename = "offerSignature")]
+    offer_signature: String,
+    /// Our rotation statements as JSON, only sent once the key was rotated
+    #[serde(rename = "keyRotation", skip_serializing_if = "Option::is_none")]
+    key_rotation: Option<String>,
 }
 
 #[derive(Debug, Serialize)]
@@ -57,6 +76,9 @@ struct ServeResponse {
     message: String,
     #[serde(rename = "receiverEphemeralKey")]
     receiver_ephemeral_key: Option<String>,
+    /// Data connections the relay pairs per session (absent on older relays)
+    #[serde(rename = "maxStreams")]
+    max_streams: Option<u32>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -80,15 +102,50 @@ struct ListenResponse {
     sender_ephemeral_key: Option<String>,
     #[serde(rename = "receiverEphemeralKey")]
     receiver_ephemeral_key: Option<String>,
+    /// Data connections the relay pairs per session (absent on older relays)
+    #[serde(rename = "maxStreams")]
+    max_streams: Option<u32>,
+    /// Signed offer (absent from older senders and relays)
+    offer: Option<String>,
+    #[serde(rename = "offerSignature")]
+    offer_signature: Option<String>,
+    /// Sender's rotation statements (absent unless it rotated, and from older relays)
+    #[serde(rename = "keyRotation")]
+    key_rotation: Option<String>,
 }
 
+type Reader = BufReader<ReadHalf<TcpStream>>;
+type Writer = BufWriter<WriteHalf<TcpStream>>;
+
+/// A striped frame: its index in the session, then its data
+type StripedFrame = (u64, Vec<u8>);
+
 /// Active transfer session with socket connection
+///
+/// Control messages always use the primary connection. With extra data streams,
+/// frames are striped over the primary and every extra stream: each connection is
+/// written by its own task, which takes the next frame from a shared bounded queue,
+/// and read by its own task. Frames carry their index, so the receiver puts them back
+/// in order and a slow connection only holds up the frames it carries.
 #[allow(dead_code)]
 pub struct TransferSession {
     session_id: String,
     role: TransferRole,
-    buf_reader: BufReader<ReadHalf<TcpStream>>,
-    buf_writer: BufWriter<WriteHalf<TcpStream>>,
+    /// Primary connection, `None` while its half is lent to a striping task
+    buf_reader: Option<Reader>,
+    buf_writer: Option<Writer>,
+    /// Extra data streams, in stream order
+    stream_readers: Vec<Reader>,
+    stream_writers: Vec<Writer>,
+    stream_count: usize,
+    striped_writes: Option<StripedWrites<Writer>>,
+    striped_reads: Option<StripedReads>,
+    frames_written: u64,
+    frames_read: u64,
+    /// Authenticates control messages once the key schedule provides a key
+    control_key: Option<ControlKey>,
+    /// Data connections the relay allows for this session (1 = no parallel streams)
+    pub max_streams: u32,
     // Metadata (only populated for receiver)
     pub filename: Option<String>,
     pub file_size: Option<u64>,
@@ -97,12 +154,146 @@ pub struct TransferSession {
     pub file_hash: Option<String>,
     pub sender_ephemeral_key: Option<String>,
     pub receiver_ephemeral_key: Option<String>,
+    pub offer: Option<SignedOffer>,
+    pub key_rotations: Vec<KeyRotation>,
+}
+
+/// Writer tasks of a striped session, one per connection
+///
+/// Each task takes the next frame from a shared bounded queue and writes it as
+/// [8B big-endian index][4B size][data], so a free connection picks up the work.
+pub struct StripedWrites<W> {
+    queue: mpsc::Sender<StripedFrame>,
+    lanes: Vec<JoinHandle<Result<W>>>,
+}
+
+/// Reader tasks of a striped session, with the frame each connection delivered next
+pub struct StripedReads {
+    lanes: Vec<mpsc::Receiver<Result<StripedFrame>>>,
+    heads: Vec<Option<StripedFrame>>,
+    tasks: Vec<JoinHandle<()>>,
+}
+
+impl<W: AsyncWrite + Unpin + Send + 'static> StripedWrites<W> {
+    /// Hand every connection to its own writer task
+    pub fn start(writers: Vec<W>) -> Self {
+        let (queue, frames) = mpsc::channel(STRIPE_QUEUE_FRAMES);
+        let frames = Arc::new(Mutex::new(frames));
+
+        let lanes = writers
+            .into_iter()
+            .map(|writer| tokio::spawn(write_lane(writer, frames.clone())))
+            .collect();
+
+        StripedWrites { queue, lanes }
+    }
+
+    /// Queue frame `index` for the first connection free to take it
+    pub async fn send(&self, index: u64, frame: Vec<u8>) -> Result<()> {
+        // A connection task only stops early when its connection failed
+        if self.lanes.iter().any(|lane| lane.is_finished()) {
+            return Err(lane_closed());
+        }
+
+        self.queue
+            .send((index, frame))
+            .await
+            .map_err(|_e| lane_closed())
+    }
+
+    /// Let the queue drain, stop the writer tasks and take the connections back
+    pub async fn finish(self) -> Result<Vec<W>> {
+        let StripedWrites { queue, lanes } = self;
+        drop(queue);
+
+        let mut writers = Vec::with_capacity(lanes.len());
+        let mut result = Ok(());
+        for lane in lanes {
+            match lane.await.map_err(|_e| lane_closed()).and_then(|w| w) {
+                Ok(writer) => writers.push(writer),
+                Err(e) => result = result.and(Err(e)),
+            }
+        }
+        result.map(|_| writers)
+    }
+}
+
+impl StripedReads {
+    /// Hand every connection to its own reader task
+    pub fn start<R: AsyncRead + Unpin + Send + 'static>(readers: Vec<R>) -> Self {
+        let mut lanes = Vec::with_capacity(readers.len());
+        let mut tasks = Vec::with_capacity(readers.len());
+        for reader in readers {
+            let (frames, lane) = mpsc::channel(STRIPE_QUEUE_FRAMES);
+            tasks.push(tokio::spawn(read_lane(reader, frames)));
+            lanes.push(lane);
+        }
+
+        StripedReads {
+            heads: lanes.iter().map(|_| None).collect(),
+            lanes,
+            tasks,
+        }
+    }
+
+    /// Frame `index`, whichever connection it came in on
+    ///
+    /// Every connection delivers its frames in order, so the frame is always at the
+    /// head of one of them. Fails once it can no longer arrive: every connection
+    /// holds a later frame, one holds an earlier one again, or a connection closed.
+    pub async fn read_frame(&mut self, index: u64) -> Result<Vec<u8>> {
+        loop {
+            if let Some(lane) = self
+                .heads
+                .iter()
+                .position(|head| matches!(head, Some((i, _)) if *i == index))
+                && let Some((_index, frame)) = self.heads[lane].take()
+            {
+                return Ok(frame);
+            }
+
+            if self.heads.iter().flatten().any(|(i, _)| *i < index)
+                || self.heads.iter().all(Option::is_some)
+            {
+                return Err(Error::SessionError(format!(
+                    "Frame {} is missing from the striped streams",
+                    index
+                )));
+            }
+
+            let StripedReads { lanes, heads, .. } = self;
+            std::future::poll_fn(|cx| {
+                for (lane, head) in lanes.iter_mut().zip(heads.iter_mut()) {
+                    if head.is_some() {
+                        continue;
+                    }
+                    match lane.poll_recv(cx) {
+                        Poll::Ready(Some(frame)) => {
+                            return Poll::Ready(frame.map(|f| *head = Some(f)));
+                        }
+                        Poll::Ready(None) => return Poll::Ready(Err(lane_closed())),
+                        Poll::Pending => {}
+                    }
+                }
+                Poll::Pending
+            })
+            .await?;
+        }
+    }
+}
+
+impl Drop for StripedReads {
+    fn drop(&mut self) {
+        for task in &self.tasks {
+            task.abort();
+        }
+    }
 }
 
 impl TransferSession {
     /// Read data from the socket connection
     pub async fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-        self.buf_reader
+        self.reader()?
             .read(buf)
             .await
             .map_err(|_e| Error::NetworkError("Failed to read from socket".to_string()))
@@ -110,33 +301,115 @@ impl TransferSession {
 
     /// Read exact amount of data from the socket connection
     pub async fn read_exact(&mut self, buf: &mut [u8]) -> Result<()> {
-        self.buf_reader.read_exact(buf).await?;
+        self.reader()?.read_exact(buf).await?;
         Ok(())
     }
 
+    /// Read a newline-terminated control message (without the newline)
+    pub async fn read_line(&mut self) -> Result<String> {
+        let mut line = Vec::new();
+        self.reader()?
+            .take(MAX_CONTROL_LINE_LEN as u64)
+            .read_until(b'\n', &mut line)
+            .await?;
+
+        if line.pop() != Some(b'\n') {
+            return Err(Error::SessionError(
+                "Connection closed while reading control message".to_string(),
+            ));
+        }
+
+        String::from_utf8(line)
+            .map_err(|_e| Error::SessionError("Invalid control message".to_string()))
+    }
+
     /// Write data to the socket connection
     #[allow(dead_code)]
     pub async fn write(&mut self, data: &[u8]) -> Result<usize> {
-        self.buf_writer
+        self.finish_striped_writes().await?;
+        self.writer()?
             .write(data)
             .await
             .map_err(|_e| Error::NetworkError("Failed to write to socket".to_string()))
     }
 
     /// Write all data to the socket connection
+    ///
+    /// Striped frames still queued go out first.
     pub async fn write_all(&mut self, data: &[u8]) -> Result<()> {
-        self.buf_writer
+        self.finish_striped_writes().await?;
+        self.writer()?
             .write_all(data)
             .await
             .map_err(|_e| Error::NetworkError("Failed to write all to socket".to_string()))
     }
 
-    /// Flush the socket connection
+    /// Write a length-prefixed frame: [4B big-endian size][data]
+    ///
+    /// With several streams the frame is queued for the first connection that is free
+    /// to take it, prefixed with its index: [8B big-endian index][4B size][data].
+    pub async fn write_frame(&mut self, data: &[u8]) -> Result<()> {
+        if self.stream_count == 1 {
+            return write_frame_to(self.writer()?, data).await;
+        }
+
+        if self.striped_writes.is_none() {
+            let primary = self.buf_writer.take().ok_or_else(lane_closed)?;
+            let writers = std::iter::once(primary).chain(self.stream_writers.drain(..));
+            self.striped_writes = Some(StripedWrites::start(writers.collect()));
+        }
+        let Some(striped) = &self.striped_writes else {
+            return Err(lane_closed());
+        };
+
+        let index = self.frames_written;
+        self.frames_written += 1;
+        if let Err(e) = striped.send(index, data.to_vec()).await {
+            // Report why the connection failed rather than that it did
+            self.finish_striped_writes().await?;
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    /// Read a length-prefixed frame written by `write_frame`
+    pub async fn read_frame(&mut self) -> Result<Vec<u8>> {
+        if self.stream_count == 1 {
+            return read_frame_from(self.reader()?).await;
+        }
+
+        if self.striped_reads.is_none() {
+            let primary = self.buf_reader.take().ok_or_else(lane_closed)?;
+            let readers = std::iter::once(primary).chain(self.stream_readers.drain(..));
+            self.striped_reads = Some(StripedReads::start(readers.collect()));
+        }
+        let Some(striped) = &mut self.striped_reads else {
+            return Err(lane_closed());
+        };
+
+        let frame = striped.read_frame(self.frames_read).await?;
+        self.frames_read += 1;
+        Ok(frame)
+    }
+
+    /// Flush the socket connection (and every extra data stream)
+    ///
+    /// Waits until every striped frame has been written.
     pub async fn flush(&mut self) -> Result<()> {
-        self.buf_writer
-            .flush()
-            .await
-            .map_err(|_e| Error::NetworkError("Failed to flush socket".to_string()))
+        self.finish_striped_writes().await?;
+
+        for writer in self.buf_writer.iter_mut().chain(&mut self.stream_writers) {
+            writer
+                .flush()
+                .await
+                .map_err(|_e| Error::NetworkError("Failed to flush socket".to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// Number of data streams in use (primary included)
+    pub fn stream_count(&self) -> usize {
+        self.stream_count
     }
 
     /// Get the session ID
@@ -145,10 +418,118 @@ impl TransferSession {
     }
 
     /// Get the transfer role
-    #[allow(dead_code)]
     pub fn role(&self) -> TransferRole {
         self.role
     }
+
+    /// Authenticate control messages from now on
+    pub fn set_control_key(&mut self, key: ControlKey) {
+        self.control_key = Some(key);
+    }
+
+    pub fn control_key(&self) -> Option<&ControlKey> {
+        self.control_key.as_ref()
+    }
+
+    fn reader(&mut self) -> Result<&mut Reader> {
+        self.buf_reader.as_mut().ok_or_else(lane_closed)
+    }
+
+    fn writer(&mut self) -> Result<&mut Writer> {
+        self.buf_writer.as_mut().ok_or_else(lane_closed)
+    }
+
+    /// Stop the writer tasks once every queued frame is written, and take the
+    /// connections back
+    async fn finish_striped_writes(&mut self) -> Result<()> {
+        let Some(striped) = self.striped_writes.take() else {
+            return Ok(());
+        };
+
+        let mut writers = striped.finish().await?.into_iter();
+        self.buf_writer = writers.next();
+        self.stream_writers.extend(writers);
+        Ok(())
+    }
+}
+
+/// Write striped frames from the shared queue to one connection until it closes
+async fn write_lane<W: AsyncWrite + Unpin>(
+    mut writer: W,
+    frames: Arc<Mutex<mpsc::Receiver<StripedFrame>>>,
+) -> Result<W> {
+    loop {
+        let next = frames.lock().await.recv().await;
+        let Some((index, frame)) = next else {
+            return Ok(writer);
+        };
+
+        writer
+            .write_all(&index.to_be_bytes())
+            .await
+            .map_err(|_e| Error::NetworkError("Failed to write all to socket".to_string()))?;
+        write_frame_to(&mut writer, &frame).await?;
+        writer
+            .flush()
+            .await
+            .map_err(|_e| Error::NetworkError("Failed to flush socket".to_string()))?;
+    }
+}
+
+/// Read striped frames from one connection, stopping at the first error
+async fn read_lane<R: AsyncRead + Unpin>(
+    mut reader: R,
+    frames: mpsc::Sender<Result<StripedFrame>>,
+) {
+    loop {
+        let mut index = [0u8; 8];
+        let frame = match reader.read_exact(&mut index).await {
+            Ok(_) => read_frame_from(&mut reader)
+                .await
+                .map(|frame| (u64::from_be_bytes(index), frame)),
+            Err(e) => Err(e.into()),
+        };
+
+        let failed = frame.is_err();
+        if frames.send(frame).await.is_err() || failed {
+            return;
+        }
+    }
+}
+
+fn lane_closed() -> Error {
+    Error::NetworkError("Data stream closed".to_string())
+}
+
+/// Write `[4B big-endian size][data]` to one connection
+async fn write_frame_to<W: AsyncWrite + Unpin>(writer: &mut W, data: &[u8]) -> Result<()> {
+    let size = data.len() as u32;
+    writer
+        .write_all(&size.to_be_bytes())
+        .await
+        .map_err(|_e| Error::NetworkError("Failed to write all to socket".to_string()))?;
+    writer
+        .write_all(data)
+        .await
+        .map_err(|_e| Error::NetworkError("Failed to write all to socket".to_string()))
+}
+
+/// Read one `[4B big-endian size][data]` frame from one connection
+async fn read_frame_from<R: AsyncRead + Unpin>(reader: &mut R) -> Result<Vec<u8>> {
+    let mut size_buffer = [0u8; 4];
+    reader.read_exact(&mut size_buffer).await?;
+
+    let size = u32::from_be_bytes(size_buffer) as usize;
+    if size > MAX_FRAME_SIZE {
+        return Err(Error::SessionError(format!(
+            "Frame too large: {} bytes (max {})",
+            size, MAX_FRAME_SIZE
+        )));
+    }
+
+    let mut frame = vec![0u8; size];
+    reader.read_exact(&mut frame).await?;
+    Ok(frame)
 }
 
 /// Client for interacting with the relay server
@@ -192,6 +573,7 @@ impl RelayClient {
     }
 
     /// Initiate a file transfer as sender (blocks until receiver connects)
+    #[allow(clippy::too_many_arguments)]
     pub async fn serve(
         &self,
         sender_fingerprint: String,
@@ -201,6 +583,8 @@ impl RelayClient {
         signature: String,
         file_hash: String,
         sender_ephemeral_key: String,
+        offer: SignedOffer,
+        key_rotations: &[KeyRotation],
     ) -> Result<TransferSession> {
         // Call HTTP API to create session
         let client = reqwest::Client::new();
@@ -217,6 +601,12 @@ impl RelayClient {
             signature,
             file_hash,
             sender_ephemeral_key,
+            offer: offer.offer,
+            offer_signature: offer.signature,
+            key_rotation: match key_rotations {
+                [] => None,
+                rotations => Some(serde_json::to_string(rotations)?),
+            },
         };
 
         let response = client
@@ -242,7 +632,7 @@ impl RelayClient {
 
         // Connect to socket server
         let socket = self
-            .connect_socket(&session.session_id, TransferRole::Sender)
+            .connect_socket(&session.session_id, TransferRole::Sender, 0)
             .await?;
 
         let (read_half, write_half) = tokio::io::split(socket);
@@ -252,8 +642,17 @@ impl RelayClient {
         Ok(TransferSession {
             session_id: session.session_id,
             role: TransferRole::Sender,
-            buf_reader,
-            buf_writer,
+            buf_reader: Some(buf_reader),
+            buf_writer: Some(buf_writer),
+            stream_readers: Vec::new(),
+            stream_writers: Vec::new(),
+            stream_count: 1,
+            striped_writes: None,
+            striped_reads: None,
+            frames_written: 0,
+            frames_read: 0,
+            control_key: None,
+            max_streams: session.max_streams.unwrap_or(1).max(1),
             filename: None,
             file_size: None,
             signature: None,
@@ -261,6 +660,8 @@ impl RelayClient {
             file_hash: None,
             sender_ephemeral_key: None,
             receiver_ephemeral_key: session.receiver_ephemeral_key,
+            offer: None,
+            key_rotations: Vec::new(),
         })
     }
 
@@ -328,10 +729,20 @@ impl RelayClient {
         let receiver_ephemeral_key = session.receiver_ephemeral_key.ok_or_else(|| {
             Error::NetworkError("Server did not return receiver ephemeral key".into())
         })?;
+        let max_streams = session.max_streams.unwrap_or(1).max(1);
+        let offer = match (session.offer, session.offer_signature) {
+            (Some(offer), Some(signature)) => Some(SignedOffer { offer, signature }),
+            _ => None,
+        };
+        // Unreadable statements count as none, the sender's key then has to match as usual
+        let key_rotations = session
+            .key_rotation
+            .and_then(|rotations| serde_json::from_str(&rotations).ok())
+            .unwrap_or_default();
 
         // Connect to socket server
         let socket = self
-            .connect_socket(&session_id, TransferRole::Receiver)
+            .connect_socket(&session_id, TransferRole::Receiver, 0)
             .await?;
 
         let (read_half, write_half) = tokio::io::split(socket);
@@ -341,8 +752,17 @@ impl RelayClient {
         Ok(TransferSession {
             session_id,
             role: TransferRole::Receiver,
-            buf_reader,
-            buf_writer,
+            buf_reader: Some(buf_reader),
+            buf_writer: Some(buf_writer),
+            stream_readers: Vec::new(),
+            stream_writers: Vec::new(),
+            stream_count: 1,
+            striped_writes: None,
+            striped_reads: None,
+            frames_written: 0,
+            frames_read: 0,
+            control_key: None,
+            max_streams,
             filename: Some(filename),
             file_size: Some(file_size),
             signature: Some(signature),
@@ -350,11 +770,43 @@ impl RelayClient {
             file_hash: Some(file_hash),
             sender_ephemeral_key: Some(sender_ephemeral_key),
             receiver_ephemeral_key: Some(receiver_ephemeral_key),
+            offer,
+            key_rotations,
         })
     }
 
+    /// Open extra data streams so the session uses `count` connections in total
+    ///
+    /// Both peers must call this with the same count, after agreeing on it over the
+    /// primary connection. Each stream is paired by the relay on its own.
+    pub async fn open_streams(&self, session: &mut TransferSession, count: u32) -> Result<()> {
+        for stream in session.stream_count() as u32..count {
+            let socket = self
+                .connect_socket(&session.session_id, session.role, stream)
+                .await?;
+
+            let (read_half, write_half) = tokio::io::split(socket);
+            session
+                .stream_readers
+                .push(BufReader::with_capacity(BUFFER_SIZE, read_half));
+            session
+                .stream_writers
+                .push(BufWriter::with_capacity(BUFFER_SIZE, write_half));
+            session.stream_count += 1;
+        }
+
+        Ok(())
+    }
+
     /// Connect to the socket server and perform handshake
-    async fn connect_socket(&self, session_id: &str, role: TransferRole) -> Result<TcpStream> {
+    ///
+    /// Stream 0 is the primary connection, higher numbers are extra data streams.
+    async fn connect_socket(
+        &self,
+        session_id: &str,
+        role: TransferRole,
+        stream: u32,
+    ) -> Result<TcpStream> {
         let addr_str = format!("{}:{}", self.server_ip, self.socket_port);
         let addr: SocketAddr = addr_str
             .parse()
@@ -380,8 +832,11 @@ impl RelayClient {
             .await
             .map_err(|_e| Error::NetworkError("Failed to connect to socket server".to_string()))?;
 
-        // Send handshake: "session_id:role"
-        let handshake = format!("{}:{}\n", session_id, role.as_str());
+        // Send handshake: "session_id:role", or "session_id:role:stream" for extra streams
+        let handshake = match stream {
+            0 => format!("{}:{}\n", session_id, role.as_str()),
+            n => format!("{}:{}:{}\n", session_id, role.as_str(), n),
+        };
         socket
             .write_all(handshake.as_bytes())
             .await
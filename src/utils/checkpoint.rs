@@ -0,0 +1,93 @@
+use crate::config::CHECKPOINT_SUFFIX;
+use crate::utils::download;
+use crate::utils::error::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Sidecar checkpoint kept next to a partial download
+///
+/// Stored as `<file><CHECKPOINT_SUFFIX>` so an interrupted transfer of the same
+/// file from the same sender can continue from `offset` instead of byte zero.
+/// The partial data itself lives in the download's part file.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Checkpoint {
+    pub session_id: String,
+    pub filename: String,
+    pub file_size: u64,
+    pub file_hash: String,
+    pub sender_fp: String,
+    /// Bytes written and flushed to the partial file
+    pub offset: u64,
+    pub updated_at: String,
+}
+
+impl Checkpoint {
+    pub fn new(
+        session_id: &str,
+        filename: &str,
+        file_size: u64,
+        file_hash: &str,
+        sender_fp: &str,
+    ) -> Self {
+        Checkpoint {
+            session_id: session_id.to_string(),
+            filename: filename.to_string(),
+            file_size,
+            file_hash: file_hash.to_string(),
+            sender_fp: sender_fp.to_string(),
+            offset: 0,
+            updated_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    /// Sidecar path for a download target
+    pub fn path_for(file_path: &Path) -> PathBuf {
+        let mut name = file_path.as_os_str().to_os_string();
+        name.push(CHECKPOINT_SUFFIX);
+        PathBuf::from(name)
+    }
+
+    /// Load the checkpoint for a download target, if one exists
+    pub fn load(file_path: &Path) -> Result<Option<Self>> {
+        let path = Self::path_for(file_path);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = std::fs::read_to_string(&path)?;
+        let checkpoint = serde_json::from_str(&content)
+            .map_err(|_e| Error::FileError("Invalid checkpoint file".to_string()))?;
+
+        Ok(Some(checkpoint))
+    }
+
+    /// Write the checkpoint next to the download target
+    pub fn save(&mut self, file_path: &Path) -> Result<()> {
+        self.updated_at = chrono::Utc::now().to_rfc3339();
+        let content = serde_json::to_string_pretty(self)?;
+        std::fs::write(Self::path_for(file_path), content)?;
+        Ok(())
+    }
+
+    /// Delete the checkpoint for a download target (no-op if missing)
+    pub fn remove(file_path: &Path) -> Result<()> {
+        let path = Self::path_for(file_path);
+        if path.exists() {
+            std::fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Whether this checkpoint belongs to the offered file from the same sender
+    pub fn matches(&self, file_size: u64, file_hash: &str, sender_fp: &str) -> bool {
+        self.file_size == file_size && self.file_hash == file_hash && self.sender_fp == sender_fp
+    }
+
+    /// Offset to resume from, bounded by what is actually in the part file
+    pub fn resume_offset(&self, file_path: &Path) -> u64 {
+        let on_disk = std::fs::metadata(download::part_path(file_path))
+            .map(|m| m.len())
+            .unwrap_or(0);
+        self.offset.min(on_disk).min(self.file_size)
+    }
+}
@@ -0,0 +1,61 @@
+use crate::config::ZSTD_LEVEL;
+use crate::utils::error::{Error, Result};
+
+/// Chunk flag: payload follows as-is
+pub const CHUNK_RAW: u8 = 0x00;
+
+/// Chunk flag: payload is a zstd frame
+pub const CHUNK_ZSTD: u8 = 0x01;
+
+/// Compress a file chunk before encryption
+///
+/// Output is `[flag][payload]`. Chunks that zstd cannot shrink (media, archives,
+/// already compressed data) are sent raw, so the flag costs one byte at most.
+///
+/// # Arguments
+/// * `data` - Plaintext chunk
+///
+/// # Returns
+/// * `Result<Vec<u8>>` - Flagged chunk, ready for `encrypt_next`
+pub fn compress_chunk(data: &[u8]) -> Result<Vec<u8>> {
+    let compressed = zstd::bulk::compress(data, ZSTD_LEVEL)
+        .map_err(|_e| Error::UnknownIssue("Failed to compress chunk".to_string()))?;
+
+    let (flag, payload) = if compressed.len() < data.len() {
+        (CHUNK_ZSTD, compressed.as_slice())
+    } else {
+        (CHUNK_RAW, data)
+    };
+
+    let mut chunk = Vec::with_capacity(1 + payload.len());
+    chunk.push(flag);
+    chunk.extend_from_slice(payload);
+    Ok(chunk)
+}
+
+/// Undo `compress_chunk` after decryption
+///
+/// # Arguments
+/// * `chunk` - Flagged chunk
+/// * `max_len` - Largest plaintext accepted (guards against decompression bombs)
+///
+/// # Returns
+/// * `Result<Vec<u8>>` - Original plaintext chunk
+pub fn decompress_chunk(chunk: &[u8], max_len: usize) -> Result<Vec<u8>> {
+    let (flag, payload) = chunk
+        .split_first()
+        .ok_or_else(|| Error::SessionError("Empty compressed chunk".to_string()))?;
+
+    match *flag {
+        CHUNK_RAW if payload.len() <= max_len => Ok(payload.to_vec()),
+        CHUNK_ZSTD => zstd::bulk::decompress(payload, max_len)
+            .map_err(|_e| Error::SessionError("Invalid compressed chunk".to_string())),
+        CHUNK_RAW => Err(Error::SessionError(
+            "Chunk exceeds the maximum size".to_string(),
+        )),
+        _ => Err(Error::SessionError(format!(
+            "Unknown chunk compression flag: {}",
+            flag
+        ))),
+    }
+}
@@ -0,0 +1,93 @@
+use crate::config::PART_SUFFIX;
+use crate::dirs::config::ConflictPolicy;
+use crate::utils::error::{Error, Result};
+use colored::Colorize;
+use std::io::{IsTerminal, Write};
+use std::path::{Path, PathBuf};
+
+/// Temporary file a download is written to until it has been verified
+pub fn part_path(target: &Path) -> PathBuf {
+    let mut name = target.as_os_str().to_os_string();
+    name.push(PART_SUFFIX);
+    PathBuf::from(name)
+}
+
+/// Decide where a verified download goes, applying `policy` if `target` is taken
+///
+/// Returns `None` when the download should be dropped.
+pub fn resolve_conflict(target: &Path, policy: ConflictPolicy) -> Result<Option<PathBuf>> {
+    if !target.exists() {
+        return Ok(Some(target.to_path_buf()));
+    }
+
+    match policy {
+        ConflictPolicy::Overwrite => Ok(Some(target.to_path_buf())),
+        ConflictPolicy::Rename => Ok(Some(free_name(target))),
+        ConflictPolicy::Skip => Ok(None),
+        ConflictPolicy::Ask => resolve_conflict(target, ask(target)?),
+    }
+}
+
+/// First `name (N).ext` next to `target` that is not taken yet
+pub fn free_name(target: &Path) -> PathBuf {
+    let stem = target
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension = target
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (1u32..)
+        .map(|n| target.with_file_name(format!("{} ({}){}", stem, n, extension)))
+        .find(|candidate| !candidate.exists() && !part_path(candidate).exists())
+        .expect("ran out of file names")
+}
+
+/// Flush a verified download to disk and move it to its final name
+///
+/// The part file is synced before the rename and the directory after it, so the
+/// final name never points at a truncated file, even after a crash.
+pub fn commit(part: &Path, target: &Path) -> Result<()> {
+    std::fs::File::open(part)?.sync_all()?;
+    std::fs::rename(part, target)?;
+
+    #[cfg(unix)]
+    if let Some(parent) = target.parent().filter(|p| !p.as_os_str().is_empty()) {
+        std::fs::File::open(parent)?.sync_all()?;
+    }
+
+    Ok(())
+}
+
+/// Ask on the terminal what to do with an existing file
+fn ask(target: &Path) -> Result<ConflictPolicy> {
+    if !std::io::stdin().is_terminal() {
+        return Err(Error::InvalidInput(format!(
+            "{} already exists and stdin is not a terminal to ask",
+            target.display()
+        )));
+    }
+
+    loop {
+        print!(
+            "{} {} already exists. [o]verwrite, [r]ename, [s]kip? ",
+            "?".bright_yellow().bold(),
+            target.display().to_string().bright_yellow()
+        );
+        std::io::stdout().flush()?;
+
+        let mut answer = String::new();
+        if std::io::stdin().read_line(&mut answer)? == 0 {
+            return Err(Error::InvalidInput("No answer given".to_string()));
+        }
+
+        match answer.trim().to_lowercase().as_str() {
+            "o" | "overwrite" => return Ok(ConflictPolicy::Overwrite),
+            "r" | "rename" => return Ok(ConflictPolicy::Rename),
+            "s" | "skip" => return Ok(ConflictPolicy::Skip),
+            _ => continue,
+        }
+    }
+}
@@ -1,11 +1,11 @@
-//use crate::config::constants::HASH_CHUNK_SIZE;
+use crate::config::constants::KEY_FINGERPRINT_DISPLAY_LEN;
 use crate::utils::error::Result;
 use memmap2::Mmap;
 use sha2::{Digest, Sha256};
 use std::fs::File;
 use std::path::{Path, PathBuf};
 //use tokio::fs::File;
-//use tokio::io::AsyncReadExt;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 /// Compute SHA256 hash of a file
 ///
@@ -38,6 +38,32 @@ pub async fn compute_file_hash(file_path: &PathBuf) -> Result<String> {
     Ok(hex::encode(hash_result))
 }
 
+/// Read until `buffer` is full or the reader is exhausted
+///
+/// Plain `read` may return short counts, but transfer chunks must line up with
+/// `FILE_CHUNK_SIZE` blocks so each one can be checked against its Merkle leaf.
+///
+/// # Returns
+/// * `Result<usize>` - Bytes read, only less than `buffer.len()` at end of input
+pub async fn read_block<R: AsyncRead + Unpin>(reader: &mut R, buffer: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+
+    while filled < buffer.len() {
+        let n = reader.read(&mut buffer[filled..]).await?;
+        if n == 0 {
+            break;
+        }
+        filled += n;
+    }
+
+    Ok(filled)
+}
+
+/// Shorten a hash for display, without panicking on short input
+pub fn display_hash(hash: &str) -> &str {
+    hash.get(..KEY_FINGERPRINT_DISPLAY_LEN).unwrap_or(hash)
+}
+
 /// Validate file path exists and is accessible
 ///
 /// # Arguments
@@ -0,0 +1,171 @@
+use crate::utils::error::{Error, Result};
+use crate::utils::hash;
+use crate::utils::sanitize::sanitize_filename;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::path::{Component, Path, PathBuf};
+
+/// A single file inside a multi-file transfer
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestEntry {
+    /// Relative path using `/` separators, e.g. `dist/assets/app.js`
+    pub path: String,
+    pub size: u64,
+    /// Hex-encoded SHA256 of the file contents
+    pub hash: String,
+    /// Local source path (sender only, never serialized)
+    #[serde(skip)]
+    pub source: PathBuf,
+}
+
+/// Signed list of files sent in one session
+///
+/// The manifest is sent as the first encrypted frame of a multi-file transfer.
+/// Its SHA256 is part of the signed transfer metadata, so the receiver can trust
+/// every path, size and hash listed here.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Manifest {
+    pub entries: Vec<ManifestEntry>,
+}
+
+impl Manifest {
+    /// Build a manifest from files and/or directories
+    ///
+    /// Directories are walked recursively and keep their own name as the top-level
+    /// path component, so `rs serve --file dist` is rebuilt as `dist/...` on the receiver.
+    pub async fn build(paths: &[PathBuf]) -> Result<Self> {
+        let mut sources = Vec::new();
+
+        for path in paths {
+            if !path.exists() {
+                return Err(Error::FileError(format!(
+                    "File does not exist: {}",
+                    path.display()
+                )));
+            }
+
+            let name = path_name(path)?;
+            if path.is_dir() {
+                collect_dir(path, &name, &mut sources)?;
+            } else if path.is_file() {
+                sources.push((name, path.clone()));
+            } else {
+                return Err(Error::InvalidInput(format!(
+                    "{} is not a file or directory",
+                    path.display()
+                )));
+            }
+        }
+
+        if sources.is_empty() {
+            return Err(Error::InvalidInput("No files to send".into()));
+        }
+
+        let mut seen = HashSet::new();
+        let mut entries = Vec::with_capacity(sources.len());
+        for (relative, source) in sources {
+            if !seen.insert(relative.clone()) {
+                return Err(Error::InvalidInput(format!(
+                    "Duplicate path in transfer: {}",
+                    relative
+                )));
+            }
+
+            let size = std::fs::metadata(&source)?.len();
+            let hash = hash::compute_file_hash(&source).await?;
+            entries.push(ManifestEntry {
+                path: relative,
+                size,
+                hash,
+                source,
+            });
+        }
+
+        Ok(Manifest { entries })
+    }
+
+    /// Parse a manifest received from the sender
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        serde_json::from_slice(bytes)
+            .map_err(|_e| Error::SessionError("Invalid transfer manifest".to_string()))
+    }
+
+    /// Serialize the manifest for transmission
+    pub fn to_bytes(&self) -> Result<Vec<u8>> {
+        Ok(serde_json::to_vec(self)?)
+    }
+
+    /// Hex-encoded SHA256 of the serialized manifest
+    pub fn digest(bytes: &[u8]) -> String {
+        hex::encode(Sha256::digest(bytes))
+    }
+
+    /// Total size of all files in bytes
+    pub fn total_size(&self) -> u64 {
+        self.entries.iter().map(|e| e.size).sum()
+    }
+}
+
+/// A manifest path resolved below the download directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResolvedEntry {
+    pub path: PathBuf,
+    /// What had to be changed in any component, empty if the path was used as sent
+    pub changes: Vec<String>,
+}
+
+/// Resolve a manifest path below the download directory
+///
+/// Rejects absolute paths and any `.`/`..` components so a manifest can never
+/// write outside of `root`. Every component then goes through `sanitize_filename`,
+/// the same as a single-file name.
+pub fn resolve_entry_path(root: &Path, relative: &str) -> Result<ResolvedEntry> {
+    let unsafe_path = || Error::SessionError(format!("Unsafe path in manifest: {:?}", relative));
+
+    let mut path = root.to_path_buf();
+    let mut changes = Vec::new();
+    for part in relative.split('/') {
+        let mut components = Path::new(part).components();
+        let (Some(Component::Normal(_)), None) = (components.next(), components.next()) else {
+            return Err(unsafe_path());
+        };
+
+        let safe = sanitize_filename(part).map_err(|_| unsafe_path())?;
+        for change in safe.changes {
+            changes.push(format!("{}: {}", part, change));
+        }
+        path.push(safe.name);
+    }
+
+    Ok(ResolvedEntry { path, changes })
+}
+
+/// Final path component of a user supplied path (handles `.` and trailing slashes)
+fn path_name(path: &Path) -> Result<String> {
+    let absolute = std::fs::canonicalize(path)?;
+    absolute
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| Error::InvalidInput(format!("Invalid path: {}", path.display())))
+}
+
+/// Recursively collect regular files below `dir`, sorted for a stable manifest
+fn collect_dir(dir: &Path, prefix: &str, out: &mut Vec<(String, PathBuf)>) -> Result<()> {
+    let mut children: Vec<_> = std::fs::read_dir(dir)?.collect::<std::io::Result<_>>()?;
+    children.sort_by_key(|c| c.file_name());
+
+    for child in children {
+        let file_type = child.file_type()?;
+        let relative = format!("{}/{}", prefix, child.file_name().to_string_lossy());
+
+        // Symlinks are skipped to avoid loops and leaking files outside the tree
+        if file_type.is_dir() {
+            collect_dir(&child.path(), &relative, out)?;
+        } else if file_type.is_file() {
+            out.push((relative, child.path()));
+        }
+    }
+
+    Ok(())
+}
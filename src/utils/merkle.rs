@@ -0,0 +1,164 @@
+use crate::config::FILE_CHUNK_SIZE;
+use crate::utils::error::{Error, Result};
+use crate::utils::hash::read_block;
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use tokio::fs::File;
+use tokio::io::AsyncReadExt;
+
+/// SHA256 output
+pub type Hash = [u8; 32];
+
+/// Domain separation prefix for leaf hashes
+const LEAF_PREFIX: u8 = 0x00;
+
+/// Domain separation prefix for interior node hashes
+const NODE_PREFIX: u8 = 0x01;
+
+/// Hash of one `FILE_CHUNK_SIZE` block: SHA256(0x00 || block)
+pub fn leaf_hash(block: &[u8]) -> Hash {
+    Sha256::new()
+        .chain_update([LEAF_PREFIX])
+        .chain_update(block)
+        .finalize()
+        .into()
+}
+
+/// Hash of an interior node: SHA256(0x01 || left || right)
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    Sha256::new()
+        .chain_update([NODE_PREFIX])
+        .chain_update(left)
+        .chain_update(right)
+        .finalize()
+        .into()
+}
+
+/// Number of `FILE_CHUNK_SIZE` blocks in a file of `size` bytes
+pub fn block_count(size: u64) -> u64 {
+    size.div_ceil(FILE_CHUNK_SIZE as u64)
+}
+
+/// Merkle tree over the `FILE_CHUNK_SIZE` blocks of a file
+///
+/// Only the leaves are stored, the root is recomputed on demand. An unpaired node
+/// at the end of a level is promoted unchanged, and an empty file has the root
+/// `leaf_hash(&[])`. The signed root lets the receiver check every block as soon as
+/// it arrives instead of only after the last byte.
+#[derive(Debug, Clone, Default)]
+pub struct MerkleTree {
+    leaves: Vec<Hash>,
+}
+
+impl MerkleTree {
+    pub fn new() -> Self {
+        MerkleTree::default()
+    }
+
+    /// Build the tree by reading a file block by block
+    pub async fn from_file(file_path: &Path) -> Result<Self> {
+        let mut file = File::open(file_path).await?;
+        let mut buffer = vec![0u8; FILE_CHUNK_SIZE];
+        let mut tree = MerkleTree::new();
+
+        loop {
+            let n = read_block(&mut file, &mut buffer).await?;
+            if n == 0 {
+                break;
+            }
+            tree.push(&buffer[..n]);
+        }
+
+        Ok(tree)
+    }
+
+    /// Parse leaves sent as concatenated 32-byte hashes
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self> {
+        if !bytes.len().is_multiple_of(32) {
+            return Err(Error::SessionError(format!(
+                "Invalid Merkle leaves: {} bytes is not a multiple of 32",
+                bytes.len()
+            )));
+        }
+
+        let leaves = bytes
+            .chunks_exact(32)
+            .map(|leaf| leaf.try_into().expect("chunks are 32 bytes"))
+            .collect();
+
+        Ok(MerkleTree { leaves })
+    }
+
+    /// Serialize leaves as concatenated 32-byte hashes
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.leaves.concat()
+    }
+
+    /// Append the next block
+    pub fn push(&mut self, block: &[u8]) {
+        self.leaves.push(leaf_hash(block));
+    }
+
+    /// Number of leaves (blocks)
+    pub fn len(&self) -> usize {
+        self.leaves.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Check a received block against its leaf
+    pub fn verify_block(&self, index: u64, block: &[u8]) -> bool {
+        usize::try_from(index)
+            .ok()
+            .and_then(|i| self.leaves.get(i))
+            .is_some_and(|leaf| *leaf == leaf_hash(block))
+    }
+
+    /// Check the first `len` bytes of a file (a resumed partial download) block by block
+    ///
+    /// `len` must end on a block boundary or at the end of the file.
+    pub async fn verify_file_prefix(&self, file_path: &Path, len: u64) -> Result<bool> {
+        let mut file = File::open(file_path).await?.take(len);
+        let mut buffer = vec![0u8; FILE_CHUNK_SIZE];
+        let mut index = 0u64;
+
+        loop {
+            let n = read_block(&mut file, &mut buffer).await?;
+            if n == 0 {
+                return Ok(true);
+            }
+            if !self.verify_block(index, &buffer[..n]) {
+                return Ok(false);
+            }
+            index += 1;
+        }
+    }
+
+    /// Compute the root hash
+    pub fn root(&self) -> Hash {
+        if self.leaves.is_empty() {
+            return leaf_hash(&[]);
+        }
+
+        let mut level = self.leaves.clone();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| match pair {
+                    [left, right] => node_hash(left, right),
+                    [single] => *single,
+                    _ => unreachable!("chunks(2) yields one or two nodes"),
+                })
+                .collect();
+        }
+
+        level[0]
+    }
+
+    /// Hex-encoded root hash
+    pub fn root_hex(&self) -> String {
+        hex::encode(self.root())
+    }
+}
@@ -29,23 +29,41 @@ pub async fn show_welcome() -> Result<()> {
         Ok(loaded_config) => {
             // Config exists, check keys
             if keys_exist_at(&loaded_config.path.keys_path) {
-                match keys::load_keys_from(&loaded_config.path.keys_path) {
-                    Ok((private, public)) => {
-                        println!("{} Keys loaded", "✓".bright_green());
-                        println!(
-                            "   Private: {}...",
-                            hex::encode(&private.to_bytes()[..KEY_FINGERPRINT_DISPLAY_LEN])
-                                .dimmed()
-                        );
-                        println!(
-                            "   Public:  {}...",
-                            hex::encode(&public.to_bytes()[..KEY_FINGERPRINT_DISPLAY_LEN])
-                                .bright_white()
-                        );
+                // Never prompt for the passphrase just to show the welcome screen
+                if keys::is_encrypted_at(&loaded_config.path.keys_path).unwrap_or(false) {
+                    match keys::load_public_key_from(&loaded_config.path.keys_path) {
+                        Ok(public) => {
+                            println!("{} Keys loaded (passphrase-protected)", "✓".bright_green());
+                            println!(
+                                "   Public:  {}...",
+                                hex::encode(&public.to_bytes()[..KEY_FINGERPRINT_DISPLAY_LEN])
+                                    .bright_white()
+                            );
+                        }
+                        Err(_) => {
+                            println!("{} Failed to load keys", "✗".bright_red());
+                            println!(" rs init  --force   Reinitialize your keys");
+                        }
                     }
-                    Err(_) => {
-                        println!("{} Failed to load keys", "✗".bright_red());
-                        println!(" rs init  --force   Reinitialize your keys");
+                } else {
+                    match keys::load_keys_from(&loaded_config.path.keys_path) {
+                        Ok((private, public)) => {
+                            println!("{} Keys loaded", "✓".bright_green());
+                            println!(
+                                "   Private: {}...",
+                                hex::encode(&private.to_bytes()[..KEY_FINGERPRINT_DISPLAY_LEN])
+                                    .dimmed()
+                            );
+                            println!(
+                                "   Public:  {}...",
+                                hex::encode(&public.to_bytes()[..KEY_FINGERPRINT_DISPLAY_LEN])
+                                    .bright_white()
+                            );
+                        }
+                        Err(_) => {
+                            println!("{} Failed to load keys", "✗".bright_red());
+                            println!(" rs init  --force   Reinitialize your keys");
+                        }
                     }
                 }
             } else {
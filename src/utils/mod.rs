@@ -3,3 +3,17 @@ pub mod error;
 pub mod message;
 
 pub mod hash;
+
+pub mod manifest;
+
+pub mod checkpoint;
+
+pub mod merkle;
+
+pub mod compression;
+
+pub mod download;
+
+pub mod sanitize;
+
+pub mod passphrase;
@@ -0,0 +1,85 @@
+use crate::config::{PASSPHRASE_ENV, PASSPHRASE_FD_ENV, SEED_PHRASE_ENV, SEED_PHRASE_FD_ENV};
+use crate::utils::error::{Error, Result};
+use std::io::BufRead;
+
+/// Passphrase unlocking the private key
+///
+/// Taken from `$RSHARE_PASSPHRASE`, else the first line read from the file descriptor
+/// in `$RSHARE_PASSPHRASE_FD`, else prompted on the terminal.
+pub fn read_passphrase(prompt: &str) -> Result<String> {
+    match from_env(PASSPHRASE_ENV, PASSPHRASE_FD_ENV)? {
+        Some(passphrase) => Ok(passphrase),
+        None => prompt_passphrase(prompt, PASSPHRASE_ENV, PASSPHRASE_FD_ENV),
+    }
+}
+
+/// New passphrase from the given variables (for scripts), else prompted twice
+pub fn read_new_passphrase(env: &str, fd_env: &str) -> Result<String> {
+    let passphrase = match from_env(env, fd_env)? {
+        Some(passphrase) => passphrase,
+        None => {
+            let passphrase = prompt_passphrase("New passphrase: ", env, fd_env)?;
+            if prompt_passphrase("Repeat passphrase: ", env, fd_env)? != passphrase {
+                return Err(Error::InvalidInput("Passphrases do not match".to_string()));
+            }
+            passphrase
+        }
+    };
+
+    if passphrase.is_empty() {
+        return Err(Error::InvalidInput(
+            "Passphrase cannot be empty".to_string(),
+        ));
+    }
+    Ok(passphrase)
+}
+
+/// Seed phrase to restore the private key from, same sources as the passphrase
+pub fn read_seed_phrase() -> Result<String> {
+    match from_env(SEED_PHRASE_ENV, SEED_PHRASE_FD_ENV)? {
+        Some(phrase) => Ok(phrase),
+        None => prompt_passphrase("Seed phrase: ", SEED_PHRASE_ENV, SEED_PHRASE_FD_ENV),
+    }
+}
+
+fn from_env(env: &str, fd_env: &str) -> Result<Option<String>> {
+    if let Ok(passphrase) = std::env::var(env) {
+        return Ok(Some(passphrase));
+    }
+
+    match std::env::var(fd_env) {
+        Ok(fd) => {
+            let fd: u32 = fd.trim().parse().map_err(|_e| {
+                Error::InvalidInput(format!("{} is not a file descriptor: {}", fd_env, fd))
+            })?;
+            read_fd(fd).map(Some)
+        }
+        Err(_) => Ok(None),
+    }
+}
+
+#[cfg(unix)]
+fn read_fd(fd: u32) -> Result<String> {
+    let file = std::fs::File::open(format!("/dev/fd/{}", fd))
+        .map_err(|_e| Error::FileError(format!("Cannot read passphrase from fd {}", fd)))?;
+
+    let mut line = String::new();
+    std::io::BufReader::new(file).read_line(&mut line)?;
+    Ok(line.trim_end_matches(['\r', '\n']).to_string())
+}
+
+#[cfg(not(unix))]
+fn read_fd(_fd: u32) -> Result<String> {
+    Err(Error::InvalidInput(
+        "Reading the passphrase from a file descriptor needs a Unix system".to_string(),
+    ))
+}
+
+fn prompt_passphrase(prompt: &str, env: &str, fd_env: &str) -> Result<String> {
+    rpassword::prompt_password(prompt).map_err(|_e| {
+        Error::InvalidInput(format!(
+            "No terminal to ask for the passphrase, set {} or {}",
+            env, fd_env
+        ))
+    })
+}
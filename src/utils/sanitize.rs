@@ -0,0 +1,126 @@
+use crate::config::MAX_FILENAME_LEN;
+use crate::utils::error::{Error, Result};
+
+/// Device names Windows reserves regardless of extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Characters not allowed in file names on at least one supported platform
+const FORBIDDEN_CHARS: &[char] = &['<', '>', ':', '"', '|', '?', '*'];
+
+/// Longest extension kept when a name has to be shortened
+const MAX_KEPT_EXTENSION_LEN: usize = 16;
+
+/// A sender-supplied filename that is safe to join onto the download directory
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SafeFilename {
+    pub name: String,
+    /// What had to be changed, empty if the name was used as sent
+    pub changes: Vec<String>,
+}
+
+/// Turn a filename from the sender (or relay) into a single safe path component
+///
+/// Directory parts, traversal and absolute paths are stripped down to the last
+/// component. Control, invisible and bidirectional characters, Unicode replacement
+/// characters and characters Windows forbids become `_`. Reserved device names get
+/// a `_` prefix and overlong names are shortened. Names with nothing usable left
+/// are rejected.
+pub fn sanitize_filename(raw: &str) -> Result<SafeFilename> {
+    let mut changes = Vec::new();
+
+    // Only the last component survives, so `../x`, `/etc/x` and `C:\x` all become `x`
+    let base = raw.rsplit(['/', '\\']).next().unwrap_or(raw);
+    if base.len() != raw.len() {
+        changes.push("directory components removed".to_string());
+    }
+
+    let mut control = false;
+    let mut invisible = false;
+    let mut invalid = false;
+    let mut forbidden = false;
+    let name: String = base
+        .chars()
+        .map(|c| {
+            if c.is_control() {
+                control = true;
+            } else if is_invisible(c) {
+                invisible = true;
+            } else if c == char::REPLACEMENT_CHARACTER {
+                invalid = true;
+            } else if FORBIDDEN_CHARS.contains(&c) {
+                forbidden = true;
+            } else {
+                return c;
+            }
+            '_'
+        })
+        .collect();
+
+    for (found, change) in [
+        (control, "control characters replaced"),
+        (invisible, "invisible or bidirectional characters replaced"),
+        (invalid, "invalid Unicode replaced"),
+        (forbidden, "characters not allowed in file names replaced"),
+    ] {
+        if found {
+            changes.push(change.to_string());
+        }
+    }
+
+    // Windows silently drops trailing dots and spaces, which also turns `..` into nothing
+    let trimmed = name.trim_start_matches(' ').trim_end_matches(['.', ' ']);
+    if trimmed.len() != name.len() {
+        changes.push("leading spaces or trailing dots and spaces removed".to_string());
+    }
+    if trimmed.is_empty() {
+        return Err(Error::InvalidInput(format!(
+            "Unusable filename from sender: {:?}",
+            raw
+        )));
+    }
+    let mut name = trimmed.to_string();
+
+    let stem = name.split('.').next().unwrap_or_default().trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(stem))
+    {
+        name.insert(0, '_');
+        changes.push("reserved device name prefixed with _".to_string());
+    }
+
+    if name.len() > MAX_FILENAME_LEN {
+        name = shorten(&name);
+        changes.push(format!("shortened to {} bytes", name.len()));
+    }
+
+    Ok(SafeFilename { name, changes })
+}
+
+/// Zero-width and bidirectional formatting characters that disguise a name
+fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{200B}'..='\u{200F}' | '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}' | '\u{FEFF}'
+    )
+}
+
+/// Cut a name to `MAX_FILENAME_LEN` bytes on a char boundary, keeping a short extension
+fn shorten(name: &str) -> String {
+    let extension = name
+        .rfind('.')
+        .map(|dot| &name[dot..])
+        .filter(|ext| ext.len() <= MAX_KEPT_EXTENSION_LEN)
+        .unwrap_or_default();
+    let stem = &name[..name.len() - extension.len()];
+
+    let mut cut = MAX_FILENAME_LEN - extension.len();
+    while !stem.is_char_boundary(cut) {
+        cut -= 1;
+    }
+
+    format!("{}{}", &stem[..cut], extension)
+}
@@ -0,0 +1,68 @@
+#![cfg(unix)]
+
+use ed25519_dalek::SigningKey;
+use rshare::agent::{Agent, AgentClient};
+use rshare::crypto::identity::Identity;
+use rshare::crypto::noise::{self, NoiseHandshake};
+use rshare::crypto::signing;
+use std::time::Duration;
+
+#[tokio::test(flavor = "multi_thread")]
+async fn test_agent_signs_and_exchanges_without_exposing_the_key() {
+    let dir = std::env::temp_dir().join(format!("rshare-agent-{}", std::process::id()));
+    let socket = dir.join("agent.sock");
+    let signing_key = SigningKey::from_bytes(&[5u8; 32]);
+    let peer = SigningKey::from_bytes(&[6u8; 32]);
+
+    let agent = Agent::bind(&socket, signing_key.clone()).unwrap();
+    let running = tokio::spawn(agent.run(Duration::from_secs(60)));
+
+    // A second agent must not take over the live socket
+    assert!(Agent::bind(&socket, peer.clone()).is_err());
+
+    let client_socket = socket.clone();
+    tokio::task::spawn_blocking(move || {
+        let client = AgentClient::connect(&client_socket).unwrap();
+        assert_eq!(client.public_key(), signing_key.verifying_key());
+        assert!(client.expires_in() <= 60);
+
+        let signature = client.sign(b"offer").unwrap();
+        assert!(signing::verify_bytes(&signing_key.verifying_key(), b"offer", &signature).is_ok());
+
+        // Same static DH as the unlocked key
+        let peer_public = noise::x25519_public(&peer.verifying_key());
+        let local = Identity::from(signing_key.clone());
+        assert_eq!(
+            client.x25519(&peer_public).unwrap(),
+            local.x25519(&peer_public).unwrap()
+        );
+        assert!(client.x25519(&[0u8; 32]).is_err());
+
+        // A full Noise handshake with the agent holding one side's key
+        let receiver = Identity::Agent(client.clone());
+        let sender = Identity::from(peer.clone());
+        let mut initiator =
+            NoiseHandshake::initiator(&receiver, &peer.verifying_key(), b"prologue").unwrap();
+        let mut responder = NoiseHandshake::responder(&sender, b"prologue").unwrap();
+        let first = initiator.write_message(&[]).unwrap();
+        responder.read_message(&first).unwrap();
+        assert!(responder.remote_is(&signing_key.verifying_key()));
+
+        let reply = responder.write_message(b"reply").unwrap();
+        assert_eq!(initiator.read_message(&reply).unwrap(), b"reply");
+        assert_eq!(initiator.handshake_hash(), responder.handshake_hash());
+        assert_eq!(
+            initiator.split_secret().unwrap(),
+            responder.split_secret().unwrap()
+        );
+
+        client.stop().unwrap();
+    })
+    .await
+    .unwrap();
+
+    running.await.unwrap().unwrap();
+    assert!(!socket.exists());
+    assert!(AgentClient::connect(&socket).is_err());
+    let _ = std::fs::remove_dir_all(&dir);
+}
@@ -0,0 +1,118 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use ed25519_dalek::SigningKey;
+use rshare::config::{
+    IDENTITY_CARD_PREFIX, IDENTITY_CARD_VERSION, MAX_CARD_NAME_LEN, MAX_INVITATION_LIFETIME_DAYS,
+};
+use rshare::crypto::card::{IdentityCard, Invitation};
+use rshare::dirs::config::ServerConfig;
+
+fn relay() -> ServerConfig {
+    ServerConfig {
+        server_name: "office".to_string(),
+        default: false,
+        server_ip: "relay.example.com".to_string(),
+        http_port: 8080,
+        socket_port: 10000,
+    }
+}
+
+#[test]
+fn test_card_round_trips_with_and_without_relay() {
+    let key = SigningKey::from_bytes(&[3u8; 32]);
+
+    for relay in [Some(relay()), None] {
+        let card = IdentityCard::new(key.verifying_key(), "alice", relay, 1_700_000_000).unwrap();
+        let text = card.sign(&key).unwrap();
+        assert!(text.starts_with(IDENTITY_CARD_PREFIX));
+        assert!(IdentityCard::is_card(&text));
+        assert_eq!(IdentityCard::parse(&format!("  {}\n", text)).unwrap(), card);
+    }
+}
+
+#[test]
+fn test_card_must_be_signed_by_its_own_key() {
+    let key = SigningKey::from_bytes(&[3u8; 32]);
+    let other = SigningKey::from_bytes(&[4u8; 32]);
+    let card = IdentityCard::new(key.verifying_key(), "alice", Some(relay()), 100).unwrap();
+    assert!(card.sign(&other).is_err());
+
+    // Changing any byte of a signed card breaks it
+    let text = card.sign(&key).unwrap();
+    let mut bytes = URL_SAFE_NO_PAD
+        .decode(&text[IDENTITY_CARD_PREFIX.len()..])
+        .unwrap();
+    let name_at = bytes.windows(5).position(|w| w == b"alice").unwrap();
+    bytes[name_at] = b'm';
+    let tampered = format!("{}{}", IDENTITY_CARD_PREFIX, URL_SAFE_NO_PAD.encode(&bytes));
+    assert!(IdentityCard::parse(&tampered).is_err());
+
+    assert!(IdentityCard::parse("rshare-card:AAAA").is_err());
+    assert!(IdentityCard::parse(&hex::encode(key.verifying_key().to_bytes())).is_err());
+}
+
+#[test]
+fn test_card_name_is_a_safe_contact_name() {
+    let key = SigningKey::from_bytes(&[3u8; 32]).verifying_key();
+    assert!(IdentityCard::new(key, "alice.smith-2", None, 0).is_ok());
+    assert!(IdentityCard::new(key, &"a".repeat(MAX_CARD_NAME_LEN), None, 0).is_ok());
+
+    let too_long = "a".repeat(MAX_CARD_NAME_LEN + 1);
+    for name in [
+        "",
+        too_long.as_str(),
+        "self",
+        "SELF",
+        "alice\nbob",
+        "alice\x1b[31m",
+        "alice\u{202e}",
+        "ali\u{200b}ce",
+        "alice smith",
+    ] {
+        assert!(IdentityCard::new(key, name, None, 0).is_err(), "{:?}", name);
+    }
+
+    // A signed card carrying such a name is refused when read
+    let signing_key = SigningKey::from_bytes(&[3u8; 32]);
+    let card = IdentityCard {
+        version: IDENTITY_CARD_VERSION,
+        issued_at: 0,
+        public_key: key,
+        name: "\x1b[2Jadmin".to_string(),
+        relay: None,
+    };
+    assert!(IdentityCard::parse(&card.sign(&signing_key).unwrap()).is_err());
+}
+
+#[test]
+fn test_invitation_is_checked_for_signature_and_expiry() {
+    let key = SigningKey::from_bytes(&[3u8; 32]);
+    let issued_at = 1_700_000_000;
+    let day = 24 * 60 * 60;
+    let card = IdentityCard::new(key.verifying_key(), "alice", Some(relay()), issued_at).unwrap();
+
+    let invitation = Invitation::new(card.clone(), 7).unwrap();
+    assert_eq!(invitation.expires_at, issued_at + 7 * day);
+    let token = invitation.sign(&key).unwrap();
+    assert!(Invitation::is_invitation(&token));
+    assert!(!IdentityCard::is_card(&token));
+
+    let accepted = Invitation::parse(&token, issued_at + day).unwrap();
+    assert_eq!(accepted.card, card);
+    assert_eq!(accepted.card.relay.unwrap().server_ip, "relay.example.com");
+
+    // Expired, or issued in the future
+    assert!(Invitation::parse(&token, issued_at + 8 * day).is_err());
+    assert!(Invitation::parse(&token, issued_at - day).is_err());
+
+    // A card is not an invitation, and only its own key signs one
+    assert!(Invitation::parse(&card.sign(&key).unwrap(), issued_at).is_err());
+    assert!(
+        invitation
+            .sign(&SigningKey::from_bytes(&[4u8; 32]))
+            .is_err()
+    );
+
+    assert!(Invitation::new(card.clone(), 0).is_err());
+    assert!(Invitation::new(card, MAX_INVITATION_LIFETIME_DAYS + 1).is_err());
+}
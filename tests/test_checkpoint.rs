@@ -0,0 +1,41 @@
+use rshare::utils::checkpoint::Checkpoint;
+use rshare::utils::download;
+use std::path::Path;
+
+#[test]
+fn test_checkpoint_path() {
+    let path = Checkpoint::path_for(Path::new("downloads/backup.tar"));
+    assert_eq!(path, Path::new("downloads/backup.tar.rshare-resume"));
+}
+
+#[test]
+fn test_checkpoint_matches_same_file_and_sender() {
+    let checkpoint = Checkpoint::new("session-1", "backup.tar", 1024, "abcd", "alice");
+
+    assert!(checkpoint.matches(1024, "abcd", "alice"));
+    assert!(!checkpoint.matches(1024, "abcd", "mallory"));
+    assert!(!checkpoint.matches(1024, "ef01", "alice"));
+    assert!(!checkpoint.matches(2048, "abcd", "alice"));
+}
+
+#[test]
+fn test_checkpoint_roundtrip_and_resume_offset() {
+    let dir = std::env::temp_dir().join(format!("rshare-checkpoint-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("backup.tar");
+
+    // Only 100 bytes made it to disk, even though the checkpoint claims more
+    std::fs::write(download::part_path(&file_path), vec![0u8; 100]).unwrap();
+    let mut checkpoint = Checkpoint::new("session-1", "backup.tar", 1024, "abcd", "alice");
+    checkpoint.offset = 512;
+    checkpoint.save(&file_path).unwrap();
+
+    let loaded = Checkpoint::load(&file_path).unwrap().unwrap();
+    assert_eq!(loaded.offset, 512);
+    assert_eq!(loaded.resume_offset(&file_path), 100);
+
+    Checkpoint::remove(&file_path).unwrap();
+    assert!(Checkpoint::load(&file_path).unwrap().is_none());
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
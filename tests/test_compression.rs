@@ -0,0 +1,45 @@
+use rshare::config::{COMPRESSION_ZSTD, FILE_CHUNK_SIZE};
+use rshare::server::protocol::{Accept, Hello};
+use rshare::utils::compression::{CHUNK_RAW, CHUNK_ZSTD, compress_chunk, decompress_chunk};
+
+#[test]
+fn test_compressible_chunk_roundtrip() {
+    let data = b"2025-01-01 INFO request served in 3ms\n".repeat(1000);
+    let chunk = compress_chunk(&data).unwrap();
+
+    assert_eq!(chunk[0], CHUNK_ZSTD);
+    assert!(chunk.len() < data.len() / 10);
+    assert_eq!(decompress_chunk(&chunk, FILE_CHUNK_SIZE).unwrap(), data);
+}
+
+#[test]
+fn test_incompressible_chunk_sent_raw() {
+    let data: Vec<u8> = (0..4096).map(|_| rand::random::<u8>()).collect();
+    let chunk = compress_chunk(&data).unwrap();
+
+    assert_eq!(chunk[0], CHUNK_RAW);
+    assert_eq!(chunk.len(), data.len() + 1);
+    assert_eq!(decompress_chunk(&chunk, FILE_CHUNK_SIZE).unwrap(), data);
+}
+
+#[test]
+fn test_decompress_rejects_oversized_and_unknown() {
+    // Expands past the limit
+    let chunk = compress_chunk(&vec![0u8; 1024 * 1024]).unwrap();
+    assert!(decompress_chunk(&chunk, 1024).is_err());
+
+    assert!(decompress_chunk(&[0x7f, 1, 2, 3], FILE_CHUNK_SIZE).is_err());
+    assert!(decompress_chunk(&[], FILE_CHUNK_SIZE).is_err());
+}
+
+#[test]
+fn test_compression_negotiation_with_older_peers() {
+    // Older receivers do not advertise any codec
+    let hello: Hello = serde_json::from_str(r#"{"version":3,"resume_offset":0}"#).unwrap();
+    assert!(!hello.supports_compression(COMPRESSION_ZSTD));
+    assert!(Hello::new(0, 1, String::new()).supports_compression(COMPRESSION_ZSTD));
+
+    // Uncompressed ACCEPT looks exactly like before
+    let accept = serde_json::to_string(&Accept::new(0, None, 1, String::new(), None)).unwrap();
+    assert!(!accept.contains("compression"));
+}
@@ -0,0 +1,54 @@
+use rshare::dirs::config::ConflictPolicy;
+use rshare::utils::download;
+use std::path::Path;
+
+#[test]
+fn test_part_path() {
+    let path = download::part_path(Path::new("downloads/backup.tar"));
+    assert_eq!(path, Path::new("downloads/backup.tar.rshare-part"));
+}
+
+#[test]
+fn test_conflict_policies() {
+    let dir = std::env::temp_dir().join(format!("rshare-download-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let target = dir.join("report.pdf");
+
+    // A free name is used as-is, whatever the policy
+    let resolved = download::resolve_conflict(&target, ConflictPolicy::Skip).unwrap();
+    assert_eq!(resolved.as_deref(), Some(target.as_path()));
+
+    std::fs::write(&target, b"old").unwrap();
+    std::fs::write(dir.join("report (1).pdf"), b"older").unwrap();
+
+    let overwrite = download::resolve_conflict(&target, ConflictPolicy::Overwrite).unwrap();
+    assert_eq!(overwrite.as_deref(), Some(target.as_path()));
+
+    let rename = download::resolve_conflict(&target, ConflictPolicy::Rename).unwrap();
+    assert_eq!(rename, Some(dir.join("report (2).pdf")));
+
+    assert!(
+        download::resolve_conflict(&target, ConflictPolicy::Skip)
+            .unwrap()
+            .is_none()
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_commit_moves_part_into_place() {
+    let dir = std::env::temp_dir().join(format!("rshare-commit-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let target = dir.join("notes.txt");
+    let part = download::part_path(&target);
+
+    std::fs::write(&target, b"old").unwrap();
+    std::fs::write(&part, b"new").unwrap();
+    download::commit(&part, &target).unwrap();
+
+    assert!(!part.exists());
+    assert_eq!(std::fs::read(&target).unwrap(), b"new");
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -1,4 +1,6 @@
-use rshare::crypto::encryption::{decrypt_chunk, encrypt_chunk};
+use rshare::crypto::encryption::{
+    CipherSuite, StreamDecryptor, StreamEncryptor, decrypt_chunk, encrypt_chunk,
+};
 
 #[test]
 fn test_encrypt_decrypt_chunk() {
@@ -103,3 +105,218 @@ fn test_large_chunk() {
 
     assert_eq!(decrypted, plaintext);
 }
+
+#[test]
+fn test_stream_known_answer() {
+    // Nonce: SHA256("rshare-stream-v1" || session_id)[..7] || BE32(index) || flag
+    // AAD:   "rshare-stream-v1" || BE32(len(session_id)) || session_id || BE32(index) || flag
+    let aes_key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, &aes_key, "test-session");
+
+    let chunk0 = encryptor.encrypt_next(b"chunk zero", false).unwrap();
+    let chunk1 = encryptor.encrypt_next(b"", true).unwrap();
+
+    assert_eq!(
+        hex::encode(&chunk0),
+        "00b9447ee0ca30f2fc9de5b55e2f8394551ab4bab817348f9b74d4"
+    );
+    assert_eq!(hex::encode(&chunk1), "01ce13f670f0d0cf1895517b650b5956cb");
+}
+
+#[test]
+fn test_stream_roundtrip() {
+    let aes_key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+
+    let chunks = [b"first".as_slice(), b"second", b"third"];
+    for (i, chunk) in chunks.iter().enumerate() {
+        let last = i == chunks.len() - 1;
+        let encrypted = encryptor.encrypt_next(chunk, last).unwrap();
+
+        // Should have flag (1) + plaintext length + tag (16)
+        assert_eq!(encrypted.len(), 1 + chunk.len() + 16);
+
+        let (decrypted, was_last) = decryptor.decrypt_next(&encrypted).unwrap();
+        assert_eq!(decrypted, *chunk);
+        assert_eq!(was_last, last);
+    }
+
+    assert!(decryptor.is_finished());
+}
+
+#[test]
+fn test_stream_detects_reordering() {
+    let aes_key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+
+    let chunk0 = encryptor.encrypt_next(b"first", false).unwrap();
+    let chunk1 = encryptor.encrypt_next(b"second", false).unwrap();
+
+    // Chunk 1 delivered in place of chunk 0
+    assert!(decryptor.decrypt_next(&chunk1).is_err());
+
+    // The failed chunk does not advance the stream, chunk 0 still decrypts
+    decryptor.decrypt_next(&chunk0).unwrap();
+}
+
+#[test]
+fn test_stream_detects_dropped_chunk() {
+    let aes_key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+
+    let chunk0 = encryptor.encrypt_next(b"first", false).unwrap();
+    let _dropped = encryptor.encrypt_next(b"second", false).unwrap();
+    let chunk2 = encryptor.encrypt_next(b"third", true).unwrap();
+
+    decryptor.decrypt_next(&chunk0).unwrap();
+    let error = decryptor.decrypt_next(&chunk2).unwrap_err();
+
+    // Tampering is reported at the exact chunk index
+    assert!(error.to_string().contains("Chunk 1"));
+}
+
+#[test]
+fn test_stream_detects_duplicate_chunk() {
+    let aes_key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+
+    let chunk0 = encryptor.encrypt_next(b"first", false).unwrap();
+
+    decryptor.decrypt_next(&chunk0).unwrap();
+    assert!(decryptor.decrypt_next(&chunk0).is_err());
+}
+
+#[test]
+fn test_stream_detects_truncation_and_flag_tampering() {
+    let aes_key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+
+    let chunk0 = encryptor.encrypt_next(b"first", false).unwrap();
+
+    // Relay flips the flag to make a middle chunk look like the end of the stream
+    let mut forged_final = chunk0.clone();
+    forged_final[0] = 0x01;
+    assert!(decryptor.decrypt_next(&forged_final).is_err());
+
+    // Without the final chunk the stream is never finished
+    decryptor.decrypt_next(&chunk0).unwrap();
+    assert!(!decryptor.is_finished());
+}
+
+#[test]
+fn test_stream_rejects_chunks_after_final() {
+    let aes_key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+
+    let end = encryptor.encrypt_next(b"", true).unwrap();
+    decryptor.decrypt_next(&end).unwrap();
+
+    assert!(encryptor.encrypt_next(b"more", false).is_err());
+    assert!(decryptor.decrypt_next(&end).is_err());
+}
+
+#[test]
+fn test_stream_bound_to_session() {
+    let aes_key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::Aes256Gcm, &aes_key, "session-2");
+
+    let chunk0 = encryptor.encrypt_next(b"first", false).unwrap();
+    assert!(decryptor.decrypt_next(&chunk0).is_err());
+}
+
+#[test]
+fn test_stream_chacha20_poly1305_roundtrip() {
+    let key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::ChaCha20Poly1305, &key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::ChaCha20Poly1305, &key, "session-1");
+
+    let first = encryptor.encrypt_next(b"first", false).unwrap();
+    let last = encryptor.encrypt_next(b"last", true).unwrap();
+    assert_eq!(first.len(), 1 + 5 + 16);
+
+    assert_eq!(
+        decryptor.decrypt_next(&first).unwrap(),
+        (b"first".to_vec(), false)
+    );
+    assert_eq!(
+        decryptor.decrypt_next(&last).unwrap(),
+        (b"last".to_vec(), true)
+    );
+}
+
+#[test]
+fn test_stream_suite_mismatch_fails() {
+    let key = [0x42u8; 32];
+    let mut encryptor = StreamEncryptor::new(CipherSuite::ChaCha20Poly1305, &key, "session-1");
+    let mut decryptor = StreamDecryptor::new(CipherSuite::Aes256Gcm, &key, "session-1");
+
+    let chunk = encryptor.encrypt_next(b"data", true).unwrap();
+    assert!(decryptor.decrypt_next(&chunk).is_err());
+}
+
+#[test]
+fn test_cipher_suite_negotiation() {
+    use CipherSuite::{Aes256Gcm as Aes, ChaCha20Poly1305 as ChaCha};
+
+    // Agreeing first choices win
+    assert_eq!(
+        CipherSuite::negotiate(&[Aes, ChaCha], &[Aes, ChaCha]),
+        Some(Aes)
+    );
+    assert_eq!(
+        CipherSuite::negotiate(&[ChaCha, Aes], &[ChaCha, Aes]),
+        Some(ChaCha)
+    );
+
+    // A peer without AES instructions gets ChaCha20-Poly1305, whichever side it is
+    assert_eq!(
+        CipherSuite::negotiate(&[Aes, ChaCha], &[ChaCha, Aes]),
+        Some(ChaCha)
+    );
+    assert_eq!(
+        CipherSuite::negotiate(&[ChaCha, Aes], &[Aes, ChaCha]),
+        Some(ChaCha)
+    );
+
+    // Only what both support, or nothing
+    assert_eq!(CipherSuite::negotiate(&[ChaCha, Aes], &[Aes]), Some(Aes));
+    assert_eq!(CipherSuite::negotiate(&[ChaCha], &[Aes]), None);
+
+    // --cipher is the only suite offered: it wins whatever the peer prefers...
+    assert_eq!(CipherSuite::preferred(Some(ChaCha)), vec![ChaCha]);
+    assert_eq!(CipherSuite::preferred(Some(Aes)), vec![Aes]);
+    let default = CipherSuite::preferred(None);
+    assert_eq!(default.len(), 2);
+    for suite in CipherSuite::ALL {
+        let required = CipherSuite::preferred(Some(suite));
+        assert_eq!(
+            CipherSuite::negotiate(&required, &[ChaCha, Aes]),
+            Some(suite)
+        );
+        assert_eq!(
+            CipherSuite::negotiate(&required, &[Aes, ChaCha]),
+            Some(suite)
+        );
+        assert_eq!(CipherSuite::negotiate(&default, &required), Some(suite));
+    }
+
+    // ...and fails when the peer can't use it or requires another one
+    let aes = CipherSuite::preferred(Some(Aes));
+    assert_eq!(CipherSuite::negotiate(&aes, &[ChaCha]), None);
+    assert_eq!(
+        CipherSuite::negotiate(&aes, &CipherSuite::preferred(Some(ChaCha))),
+        None
+    );
+
+    for suite in CipherSuite::ALL {
+        assert_eq!(CipherSuite::from_name(suite.as_str()), Some(suite));
+        assert_eq!(CipherSuite::from_id(suite.id()), Some(suite));
+    }
+}
@@ -1,6 +1,12 @@
+use rshare::config::{HANDSHAKE_NOISE_IK, KEM_ALGORITHM, KEY_SCHEDULE_VERSIONS};
+use rshare::crypto::encryption::CipherSuite;
 use rshare::crypto::key_exchange::{
-    EphemeralKeyPair, compute_shared_secret, derive_aes_key, parse_public_key, perform_key_exchange,
+    EphemeralKeyPair, KemKeyPair, compute_shared_secret, derive_aes_key, kem_encapsulate,
+    parse_public_key, perform_key_exchange, sign_ephemeral_key, verify_ephemeral_key,
 };
+use rshare::crypto::noise;
+use rshare::server::TransferRole;
+use rshare::server::protocol::{self, NoiseInit, NoiseReply};
 
 #[test]
 fn test_ephemeral_keypair_generation() {
@@ -74,3 +80,147 @@ fn test_full_key_exchange() {
     // Both should have the same AES key
     assert_eq!(alice_aes, bob_aes);
 }
+
+#[test]
+fn test_signed_ephemeral_key() {
+    let identity = ed25519_dalek::SigningKey::from_bytes(&[3u8; 32]);
+    let ephemeral = EphemeralKeyPair::generate().public_key_hex();
+    let message = |role, key: &str| {
+        protocol::ephemeral_key_message(role, "session", "alice", "bob", key, &[])
+    };
+
+    let signature =
+        sign_ephemeral_key(&identity, &message(TransferRole::Receiver, &ephemeral)).unwrap();
+    let trusted = identity.verifying_key();
+
+    assert!(
+        verify_ephemeral_key(
+            &trusted,
+            &message(TransferRole::Receiver, &ephemeral),
+            &signature
+        )
+        .is_ok()
+    );
+
+    // A key swapped in by the relay, or the signature reused for the other role, fails
+    let swapped = EphemeralKeyPair::generate().public_key_hex();
+    assert!(
+        verify_ephemeral_key(
+            &trusted,
+            &message(TransferRole::Receiver, &swapped),
+            &signature
+        )
+        .is_err()
+    );
+    assert!(
+        verify_ephemeral_key(
+            &trusted,
+            &message(TransferRole::Sender, &ephemeral),
+            &signature
+        )
+        .is_err()
+    );
+
+    // Signed by someone else, or not signed at all
+    let stranger = ed25519_dalek::SigningKey::from_bytes(&[4u8; 32]).verifying_key();
+    assert!(
+        verify_ephemeral_key(
+            &stranger,
+            &message(TransferRole::Receiver, &ephemeral),
+            &signature
+        )
+        .is_err()
+    );
+    assert!(
+        verify_ephemeral_key(&trusted, &message(TransferRole::Receiver, &ephemeral), "").is_err()
+    );
+}
+
+#[test]
+fn test_stripped_handshakes_break_the_key_signature() {
+    let identity = ed25519_dalek::SigningKey::from_bytes(&[3u8; 32]);
+    let ephemeral = EphemeralKeyPair::generate().public_key_hex();
+    let message = |handshakes: &[String]| {
+        protocol::ephemeral_key_message(
+            TransferRole::Receiver,
+            "session",
+            "alice",
+            "bob",
+            &ephemeral,
+            handshakes,
+        )
+    };
+
+    // The receiver signs the handshakes it offers in HELLO
+    let offered = vec![HANDSHAKE_NOISE_IK.to_string()];
+    let signature = sign_ephemeral_key(&identity, &message(&offered)).unwrap();
+    let trusted = identity.verifying_key();
+    assert!(verify_ephemeral_key(&trusted, &message(&offered), &signature).is_ok());
+
+    // A relay removing them to force the legacy setup leaves a signature that no longer fits
+    assert!(verify_ephemeral_key(&trusted, &message(&[]), &signature).is_err());
+}
+
+#[test]
+fn test_kem_encapsulation_roundtrip() {
+    let keypair = KemKeyPair::generate();
+    let (ciphertext, secret) = kem_encapsulate(&keypair.public_key_hex()).unwrap();
+
+    assert_eq!(keypair.decapsulate(&ciphertext).unwrap(), secret);
+
+    // A tampered ciphertext yields an unrelated secret, a truncated one is refused
+    let mut tampered = ciphertext.clone();
+    tampered[0] ^= 1;
+    assert_ne!(keypair.decapsulate(&tampered).unwrap(), secret);
+    assert!(keypair.decapsulate(&ciphertext[1..]).is_err());
+    assert!(kem_encapsulate("abcd").is_err());
+}
+
+#[test]
+fn test_hybrid_session_keys_mix_in_the_kem_secret() {
+    let split_secret = [5u8; 64];
+    let classical = noise::derive_session_keys(
+        &split_secret,
+        None,
+        b"hash",
+        KEY_SCHEDULE_VERSIONS[0],
+        CipherSuite::Aes256Gcm,
+    )
+    .unwrap();
+    let hybrid = noise::derive_session_keys(
+        &split_secret,
+        Some(&[6u8; 32]),
+        b"hash",
+        KEY_SCHEDULE_VERSIONS[0],
+        CipherSuite::Aes256Gcm,
+    )
+    .unwrap();
+
+    assert!(hybrid.post_quantum);
+    assert!(!classical.post_quantum);
+    assert_ne!(hybrid.sender_to_receiver, classical.sender_to_receiver);
+}
+
+#[test]
+fn test_hybrid_negotiation_payloads() {
+    let keypair = KemKeyPair::generate();
+    let init = NoiseInit::new(keypair.public_key_hex(), true, &CipherSuite::ALL);
+    let init = NoiseInit::from_bytes(&init.to_bytes().unwrap()).unwrap();
+
+    let offer = init.kem_offer().unwrap();
+    assert_eq!(offer.algorithm, KEM_ALGORITHM);
+    assert!(offer.required);
+
+    // Unknown KEMs are not used
+    let mut unknown = init.clone();
+    unknown.kem.as_mut().unwrap().algorithm = "Classic-McEliece".to_string();
+    assert!(unknown.kem_offer().is_none());
+
+    let (ciphertext, _secret) = kem_encapsulate(&offer.public_key).unwrap();
+    let reply = NoiseReply {
+        key_schedule: KEY_SCHEDULE_VERSIONS[0],
+        cipher_suite: CipherSuite::ChaCha20Poly1305,
+        kem_ciphertext: Some(ciphertext),
+    };
+    assert_eq!(NoiseReply::from_bytes(&reply.to_bytes()).unwrap(), reply);
+}
@@ -0,0 +1,98 @@
+use rshare::crypto::key_file::{self, KdfParams};
+use rshare::dirs::keys;
+
+/// Cheap parameters, the format is the same as with the defaults
+const TEST_PARAMS: KdfParams = KdfParams {
+    memory_kib: 256,
+    iterations: 1,
+    parallelism: 1,
+};
+
+#[test]
+fn test_key_file_roundtrip() {
+    let secret = [7u8; 32];
+    let sealed = key_file::seal(&secret, "correct horse", &TEST_PARAMS).unwrap();
+
+    assert!(key_file::is_encrypted(&sealed));
+    assert!(!key_file::is_encrypted(&secret));
+    assert_eq!(key_file::open(&sealed, "correct horse").unwrap(), secret);
+
+    // Fresh salt and nonce every time
+    let again = key_file::seal(&secret, "correct horse", &TEST_PARAMS).unwrap();
+    assert_ne!(sealed, again);
+}
+
+#[test]
+fn test_key_file_rejects_wrong_passphrase_and_tampering() {
+    let sealed = key_file::seal(&[7u8; 32], "correct horse", &TEST_PARAMS).unwrap();
+    assert!(key_file::open(&sealed, "battery staple").is_err());
+
+    // The header is authenticated, lowering the cost parameters breaks the file
+    let magic_len = sealed.len() - (1 + 12 + 16 + 12 + 32 + 16);
+    let mut weakened = sealed.clone();
+    weakened[magic_len + 3] = 0;
+    weakened[magic_len + 4] = 128;
+    assert!(key_file::open(&weakened, "correct horse").is_err());
+
+    let mut flipped = sealed.clone();
+    *flipped.last_mut().unwrap() ^= 1;
+    assert!(key_file::open(&flipped, "correct horse").is_err());
+
+    // Unknown versions and truncated files are refused before any key derivation
+    let mut future = sealed.clone();
+    future[magic_len] = 2;
+    assert!(key_file::open(&future, "correct horse").is_err());
+    assert!(key_file::open(&sealed[..sealed.len() - 1], "correct horse").is_err());
+}
+
+#[test]
+fn test_key_file_rejects_excessive_parameters() {
+    let params = KdfParams {
+        memory_kib: u32::MAX,
+        ..TEST_PARAMS
+    };
+    assert!(key_file::seal(&[7u8; 32], "correct horse", &params).is_err());
+}
+
+#[test]
+fn test_protected_keys_on_disk() {
+    let dir = std::env::temp_dir().join(format!("rshare-key-file-{}", std::process::id()));
+    let (signing_key, verifying_key) = keys::generate_keys().unwrap();
+
+    keys::save_keys_to(
+        &signing_key,
+        &verifying_key,
+        dir.clone(),
+        Some("passphrase"),
+    )
+    .unwrap();
+    assert!(keys::is_encrypted_at(&dir).unwrap());
+    assert_eq!(keys::load_public_key_from(&dir).unwrap(), verifying_key);
+
+    let (loaded, _) = keys::load_keys_with_passphrase(&dir, "passphrase").unwrap();
+    assert_eq!(loaded.to_bytes(), signing_key.to_bytes());
+    assert!(keys::load_keys_with_passphrase(&dir, "wrong").is_err());
+
+    // Raw keys from before the format still load, without asking for a passphrase
+    keys::save_keys_to(&signing_key, &verifying_key, dir.clone(), None).unwrap();
+    assert!(!keys::is_encrypted_at(&dir).unwrap());
+    let (loaded, _) = keys::load_keys_from(&dir).unwrap();
+    assert_eq!(loaded.to_bytes(), signing_key.to_bytes());
+
+    // The key is written owner-only, even over a stale staged file left by a crash
+    let staged = dir.join("private.key.tmp");
+    std::fs::write(&staged, b"stale").unwrap();
+    keys::save_keys_to(&signing_key, &verifying_key, dir.clone(), None).unwrap();
+    assert!(!staged.exists());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mode = std::fs::metadata(dir.join("private.key"))
+            .unwrap()
+            .permissions()
+            .mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,155 @@
+use rshare::config::KEY_SCHEDULE_VERSIONS;
+use rshare::crypto::encryption::CipherSuite;
+use rshare::crypto::key_schedule::SessionKeys;
+use rshare::server::TransferRole;
+use rshare::server::protocol::{NoiseInit, NoiseReply};
+
+#[test]
+fn test_key_schedule_outputs_are_separate() {
+    let keys = SessionKeys::derive(
+        KEY_SCHEDULE_VERSIONS[0],
+        CipherSuite::Aes256Gcm,
+        &[1u8; 32],
+        b"transcript",
+    )
+    .unwrap();
+    let again = SessionKeys::derive(
+        KEY_SCHEDULE_VERSIONS[0],
+        CipherSuite::Aes256Gcm,
+        &[1u8; 32],
+        b"transcript",
+    )
+    .unwrap();
+
+    // Both peers derive the same keys, but no two outputs are alike
+    assert_eq!(keys.sender_to_receiver, again.sender_to_receiver);
+    assert_eq!(keys.confirmation_code(), again.confirmation_code());
+    assert_ne!(keys.sender_to_receiver, keys.receiver_to_sender);
+    assert_ne!(Some(keys.sender_to_receiver), keys.confirmation);
+
+    // A different transcript gives different keys
+    let other = SessionKeys::derive(
+        KEY_SCHEDULE_VERSIONS[0],
+        CipherSuite::Aes256Gcm,
+        &[1u8; 32],
+        b"tampered",
+    )
+    .unwrap();
+    assert_ne!(keys.sender_to_receiver, other.sender_to_receiver);
+    assert_ne!(keys.confirmation_code(), other.confirmation_code());
+}
+
+#[test]
+fn test_unknown_key_schedule_version_rejected() {
+    assert!(SessionKeys::derive(0, CipherSuite::Aes256Gcm, &[1u8; 32], b"transcript").is_err());
+    assert!(
+        SessionKeys::derive(
+            KEY_SCHEDULE_VERSIONS[0] + 1,
+            CipherSuite::Aes256Gcm,
+            &[1u8; 32],
+            b"transcript"
+        )
+        .is_err()
+    );
+}
+
+#[test]
+fn test_legacy_keys_leave_control_unprotected() {
+    let keys = SessionKeys::legacy([9u8; 32]);
+
+    assert_eq!(keys.version, 0);
+    assert_eq!(keys.sender_to_receiver, keys.receiver_to_sender);
+    assert!(keys.control.is_none());
+    assert!(keys.confirmation_code().is_none());
+}
+
+#[test]
+fn test_control_messages_are_authenticated() {
+    let keys = SessionKeys::derive(
+        KEY_SCHEDULE_VERSIONS[0],
+        CipherSuite::Aes256Gcm,
+        &[1u8; 32],
+        b"transcript",
+    )
+    .unwrap();
+    let control = keys.control.unwrap();
+
+    let done = control.seal(TransferRole::Receiver, "DONE");
+    assert_eq!(control.open(TransferRole::Receiver, &done).unwrap(), "DONE");
+
+    // A forged, swapped or reflected message does not pass
+    assert!(control.open(TransferRole::Receiver, "DONE").is_err());
+    let (_, tag) = done.split_once('|').unwrap();
+    let swapped = format!("ERROR:hash_mismatch|{}", tag);
+    assert!(control.open(TransferRole::Receiver, &swapped).is_err());
+    assert!(control.open(TransferRole::Sender, &done).is_err());
+
+    let other = SessionKeys::derive(
+        KEY_SCHEDULE_VERSIONS[0],
+        CipherSuite::Aes256Gcm,
+        &[2u8; 32],
+        b"transcript",
+    )
+    .unwrap();
+    assert!(
+        other
+            .control
+            .unwrap()
+            .open(TransferRole::Receiver, &done)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_key_schedule_negotiation() {
+    let init = NoiseInit::new("ab".repeat(4), false, &[CipherSuite::ChaCha20Poly1305]);
+    let init = NoiseInit::from_bytes(&init.to_bytes().unwrap()).unwrap();
+    assert!(init.supports_key_schedule(KEY_SCHEDULE_VERSIONS[0]));
+    assert_eq!(init.key_schedule(), Some(KEY_SCHEDULE_VERSIONS[0]));
+    assert_eq!(init.cipher_suites(), vec![CipherSuite::ChaCha20Poly1305]);
+
+    // Older receivers send an empty payload, there is no key schedule to agree on
+    let init = NoiseInit::from_bytes(&[]).unwrap();
+    assert!(!init.supports_key_schedule(KEY_SCHEDULE_VERSIONS[0]));
+    assert_eq!(init.key_schedule(), None);
+    assert_eq!(init.cipher_suites(), vec![CipherSuite::Aes256Gcm]);
+
+    // The reply only names the choices, no key material travels in it
+    let reply = NoiseReply {
+        key_schedule: KEY_SCHEDULE_VERSIONS[0],
+        cipher_suite: CipherSuite::ChaCha20Poly1305,
+        kem_ciphertext: None,
+    };
+    let bytes = reply.to_bytes();
+    assert_eq!(bytes.len(), 5);
+    assert_eq!(NoiseReply::from_bytes(&bytes).unwrap(), reply);
+    assert!(NoiseReply::from_bytes(&bytes[..4]).is_err());
+    assert!(NoiseReply::from_bytes(&bytes[..2]).is_err());
+    assert!(NoiseReply::from_bytes(&[]).is_err());
+
+    // Key schedule v1 carries no suite byte and always means AES-256-GCM
+    let reply = NoiseReply {
+        key_schedule: 1,
+        cipher_suite: CipherSuite::Aes256Gcm,
+        ..reply
+    };
+    assert_eq!(reply.to_bytes().len(), 4);
+    assert_eq!(NoiseReply::from_bytes(&reply.to_bytes()).unwrap(), reply);
+}
+
+#[test]
+fn test_cipher_suite_is_bound_into_the_keys() {
+    let aes = SessionKeys::derive(2, CipherSuite::Aes256Gcm, &[1u8; 32], b"transcript").unwrap();
+    let chacha =
+        SessionKeys::derive(2, CipherSuite::ChaCha20Poly1305, &[1u8; 32], b"transcript").unwrap();
+
+    assert_eq!(chacha.suite, CipherSuite::ChaCha20Poly1305);
+    assert_ne!(aes.sender_to_receiver, chacha.sender_to_receiver);
+    assert_ne!(aes.confirmation_code(), chacha.confirmation_code());
+
+    // Key schedule v1 predates suite negotiation
+    assert!(SessionKeys::derive(1, CipherSuite::Aes256Gcm, &[1u8; 32], b"transcript").is_ok());
+    assert!(
+        SessionKeys::derive(1, CipherSuite::ChaCha20Poly1305, &[1u8; 32], b"transcript").is_err()
+    );
+}
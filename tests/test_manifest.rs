@@ -0,0 +1,73 @@
+use rshare::utils::manifest::{Manifest, resolve_entry_path};
+use std::path::{Path, PathBuf};
+
+fn temp_tree(name: &str) -> PathBuf {
+    let root = std::env::temp_dir().join(format!("rshare-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&root);
+    std::fs::create_dir_all(root.join("dist").join("assets")).unwrap();
+    std::fs::write(root.join("dist").join("index.html"), b"<html></html>").unwrap();
+    std::fs::write(root.join("dist").join("assets").join("app.js"), b"main()").unwrap();
+    std::fs::write(root.join("notes.txt"), b"").unwrap();
+    root
+}
+
+#[tokio::test]
+async fn test_build_manifest_from_directory_and_file() {
+    let root = temp_tree("build");
+
+    let manifest = Manifest::build(&[root.join("dist"), root.join("notes.txt")])
+        .await
+        .unwrap();
+
+    let paths: Vec<_> = manifest.entries.iter().map(|e| e.path.as_str()).collect();
+    assert_eq!(
+        paths,
+        ["dist/assets/app.js", "dist/index.html", "notes.txt"]
+    );
+    assert_eq!(manifest.total_size(), 6 + 13);
+
+    // Source paths are local only and never serialized
+    let bytes = manifest.to_bytes().unwrap();
+    let parsed = Manifest::from_bytes(&bytes).unwrap();
+    assert_eq!(parsed.entries.len(), 3);
+    assert_eq!(parsed.entries[0].hash, manifest.entries[0].hash);
+    assert_eq!(parsed.entries[0].source, PathBuf::new());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[tokio::test]
+async fn test_build_manifest_rejects_duplicates() {
+    let root = temp_tree("dupes");
+
+    let result = Manifest::build(&[root.join("notes.txt"), root.join("notes.txt")]).await;
+    assert!(result.is_err());
+
+    std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn test_manifest_digest_changes_with_content() {
+    let a = Manifest::digest(b"{\"entries\":[]}");
+    let b = Manifest::digest(b"{\"entries\":[ ]}");
+
+    assert_eq!(a.len(), 64);
+    assert_ne!(a, b);
+}
+
+#[test]
+fn test_resolve_entry_path() {
+    let root = Path::new("downloads");
+
+    assert_eq!(
+        resolve_entry_path(root, "dist/assets/app.js").unwrap().path,
+        root.join("dist").join("assets").join("app.js")
+    );
+
+    // Traversal, absolute and empty components are rejected
+    assert!(resolve_entry_path(root, "../.bashrc").is_err());
+    assert!(resolve_entry_path(root, "dist/../../etc/passwd").is_err());
+    assert!(resolve_entry_path(root, "/etc/passwd").is_err());
+    assert!(resolve_entry_path(root, "dist//app.js").is_err());
+    assert!(resolve_entry_path(root, "./app.js").is_err());
+}
@@ -0,0 +1,89 @@
+use rshare::config::FILE_CHUNK_SIZE;
+use rshare::utils::merkle::{self, MerkleTree, leaf_hash, node_hash};
+
+fn tree_of(blocks: &[&[u8]]) -> MerkleTree {
+    let mut tree = MerkleTree::new();
+    for block in blocks {
+        tree.push(block);
+    }
+    tree
+}
+
+#[test]
+fn test_merkle_root_shapes() {
+    // Empty file
+    assert_eq!(MerkleTree::new().root(), leaf_hash(&[]));
+
+    // Single block is its own root
+    assert_eq!(tree_of(&[b"a"]).root(), leaf_hash(b"a"));
+
+    // Unpaired node is promoted unchanged
+    let (a, b, c) = (leaf_hash(b"a"), leaf_hash(b"b"), leaf_hash(b"c"));
+    let expected = node_hash(&node_hash(&a, &b), &c);
+    assert_eq!(tree_of(&[b"a", b"b", b"c"]).root(), expected);
+
+    // Leaves and nodes are domain separated
+    assert_ne!(leaf_hash(&[a, b].concat()), node_hash(&a, &b));
+}
+
+#[test]
+fn test_merkle_verify_block_and_bytes_roundtrip() {
+    let tree = tree_of(&[b"block zero", b"block one", b"block two"]);
+    let parsed = MerkleTree::from_bytes(&tree.to_bytes()).unwrap();
+
+    assert_eq!(parsed.len(), 3);
+    assert_eq!(parsed.root(), tree.root());
+    assert!(parsed.verify_block(1, b"block one"));
+    assert!(!parsed.verify_block(1, b"block ONE"));
+    assert!(!parsed.verify_block(3, b"block three"));
+
+    assert!(MerkleTree::from_bytes(&[0u8; 33]).is_err());
+}
+
+#[tokio::test]
+async fn test_merkle_from_file_and_prefix() {
+    let dir = std::env::temp_dir().join(format!("rshare-merkle-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file_path = dir.join("data.bin");
+
+    // Two full blocks and a short one
+    let data: Vec<u8> = (0..FILE_CHUNK_SIZE * 2 + 100)
+        .map(|i| (i % 251) as u8)
+        .collect();
+    std::fs::write(&file_path, &data).unwrap();
+
+    let tree = MerkleTree::from_file(&file_path).await.unwrap();
+    let expected = tree_of(&data.chunks(FILE_CHUNK_SIZE).collect::<Vec<_>>());
+    assert_eq!(tree.len() as u64, merkle::block_count(data.len() as u64));
+    assert_eq!(tree.root(), expected.root());
+
+    let first_block = FILE_CHUNK_SIZE as u64;
+    assert!(
+        tree.verify_file_prefix(&file_path, first_block)
+            .await
+            .unwrap()
+    );
+    assert!(
+        tree.verify_file_prefix(&file_path, data.len() as u64)
+            .await
+            .unwrap()
+    );
+
+    // Corrupt the second block
+    let mut corrupted = data.clone();
+    corrupted[FILE_CHUNK_SIZE + 7] ^= 0xff;
+    std::fs::write(&file_path, &corrupted).unwrap();
+    assert!(
+        tree.verify_file_prefix(&file_path, first_block)
+            .await
+            .unwrap()
+    );
+    assert!(
+        !tree
+            .verify_file_prefix(&file_path, first_block * 2)
+            .await
+            .unwrap()
+    );
+
+    std::fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,109 @@
+use ed25519_dalek::SigningKey;
+use rshare::config::KEY_SCHEDULE_VERSIONS;
+use rshare::crypto::encryption::CipherSuite;
+use rshare::crypto::identity::Identity;
+use rshare::crypto::noise::{self, NoiseHandshake};
+use x25519_dalek::{X25519_BASEPOINT_BYTES, x25519};
+
+fn identities() -> (SigningKey, SigningKey) {
+    (
+        SigningKey::from_bytes(&[1u8; 32]),
+        SigningKey::from_bytes(&[2u8; 32]),
+    )
+}
+
+#[test]
+fn test_identity_keys_convert_to_matching_x25519_keys() {
+    let (alice, bob) = identities();
+
+    let alice_secret = noise::x25519_secret(&alice);
+    let bob_secret = noise::x25519_secret(&bob);
+    assert_eq!(
+        x25519(alice_secret, X25519_BASEPOINT_BYTES),
+        noise::x25519_public(&alice.verifying_key())
+    );
+
+    let alice_public = noise::x25519_public(&alice.verifying_key());
+    let bob_public = noise::x25519_public(&bob.verifying_key());
+    assert_eq!(
+        x25519(alice_secret, bob_public),
+        x25519(bob_secret, alice_public)
+    );
+}
+
+#[test]
+fn test_handshake_agrees_on_session_keys() {
+    let (receiver, sender) = identities();
+    let prologue = b"rshare-noise-v1|session";
+
+    let mut initiator = NoiseHandshake::initiator(
+        &Identity::from(receiver.clone()),
+        &sender.verifying_key(),
+        prologue,
+    )
+    .unwrap();
+    let mut responder =
+        NoiseHandshake::responder(&Identity::from(sender.clone()), prologue).unwrap();
+
+    let first = initiator.write_message(&[]).unwrap();
+    responder.read_message(&first).unwrap();
+    assert!(responder.remote_is(&receiver.verifying_key()));
+    assert!(!responder.remote_is(&sender.verifying_key()));
+
+    // The keys can't be taken before the handshake is done
+    assert!(responder.split_secret().is_err());
+
+    let reply = responder.write_message(b"reply").unwrap();
+    assert_eq!(initiator.read_message(&reply).unwrap(), b"reply");
+
+    assert!(initiator.is_finished() && responder.is_finished());
+    assert_eq!(initiator.handshake_hash(), responder.handshake_hash());
+
+    // Both sides derive the same keys from the handshake itself, nothing secret is sent
+    let derive = |handshake: &mut NoiseHandshake| {
+        let secret = handshake.split_secret().unwrap();
+        noise::derive_session_keys(
+            &secret,
+            None,
+            handshake.handshake_hash(),
+            KEY_SCHEDULE_VERSIONS[0],
+            CipherSuite::ChaCha20Poly1305,
+        )
+        .unwrap()
+    };
+    let (ours, theirs) = (derive(&mut initiator), derive(&mut responder));
+    assert_eq!(ours.sender_to_receiver, theirs.sender_to_receiver);
+    assert_eq!(ours.receiver_to_sender, theirs.receiver_to_sender);
+    assert_eq!(ours.confirmation_code(), theirs.confirmation_code());
+    assert_ne!(ours.sender_to_receiver, ours.receiver_to_sender);
+}
+
+#[test]
+fn test_handshake_fails_for_wrong_peer_or_prologue() {
+    let (receiver, sender) = identities();
+    let impostor = SigningKey::from_bytes(&[9u8; 32]);
+
+    // The receiver expects `sender`, an impostor cannot read the first message
+    let mut initiator = NoiseHandshake::initiator(
+        &Identity::from(receiver.clone()),
+        &sender.verifying_key(),
+        b"prologue",
+    )
+    .unwrap();
+    let mut responder =
+        NoiseHandshake::responder(&Identity::from(impostor.clone()), b"prologue").unwrap();
+    let first = initiator.write_message(&[]).unwrap();
+    assert!(responder.read_message(&first).is_err());
+
+    // Tampered negotiation (different prologue) breaks the handshake
+    let mut initiator = NoiseHandshake::initiator(
+        &Identity::from(receiver.clone()),
+        &sender.verifying_key(),
+        b"prologue",
+    )
+    .unwrap();
+    let mut responder =
+        NoiseHandshake::responder(&Identity::from(sender.clone()), b"downgraded").unwrap();
+    let first = initiator.write_message(&[]).unwrap();
+    assert!(responder.read_message(&first).is_err());
+}
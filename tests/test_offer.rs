@@ -0,0 +1,131 @@
+use ed25519_dalek::SigningKey;
+use rshare::config::{MAX_SEEN_OFFERS, OFFER_CLOCK_SKEW_SECS, OFFER_LIFETIME_SECS};
+use rshare::dirs::offers::SeenOffers;
+use rshare::server::offer::{Offer, SignedOffer};
+
+const RECIPIENT: &str = "bob-fingerprint";
+const EPHEMERAL: &str = "sender-ephemeral-key";
+
+fn signed_offer(signing_key: &SigningKey) -> (Offer, SignedOffer) {
+    let sender_fp = hex::encode(signing_key.verifying_key().to_bytes());
+    let offer = Offer::new(&sender_fp, RECIPIENT, EPHEMERAL, "report.pdf", 4096, "abcd");
+    let signed = offer.sign(signing_key).unwrap();
+    (offer, signed)
+}
+
+#[test]
+fn test_offer_sign_verify_roundtrip() {
+    let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+    let (offer, signed) = signed_offer(&signing_key);
+
+    let verified = signed.verify(&signing_key.verifying_key()).unwrap();
+    assert_eq!(verified, offer);
+    assert_eq!(Offer::from_bytes(&offer.to_bytes()).unwrap(), offer);
+    assert!(
+        verified
+            .validate(RECIPIENT, EPHEMERAL, offer.created_at)
+            .is_ok()
+    );
+
+    // Each offer gets its own id
+    let (other, _) = signed_offer(&signing_key);
+    assert_ne!(other.offer_id, offer.offer_id);
+}
+
+#[test]
+fn test_offer_tampered_or_wrong_signer_rejected() {
+    let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+    let (offer, signed) = signed_offer(&signing_key);
+
+    let mut tampered = offer.clone();
+    tampered.file_size = 1;
+    let forged = SignedOffer {
+        offer: hex::encode(tampered.to_bytes()),
+        signature: signed.signature.clone(),
+    };
+    assert!(forged.verify(&signing_key.verifying_key()).is_err());
+
+    let mallory = SigningKey::from_bytes(&[9u8; 32]);
+    assert!(signed.verify(&mallory.verifying_key()).is_err());
+
+    // Strict decoding: trailing bytes and unknown versions are refused
+    let mut bytes = offer.to_bytes();
+    bytes.push(0);
+    assert!(Offer::from_bytes(&bytes).is_err());
+    let mut future = offer.clone();
+    future.version = 2;
+    assert!(Offer::from_bytes(&future.to_bytes()).is_err());
+}
+
+#[test]
+fn test_offer_misaddressed_or_other_session_rejected() {
+    let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+    let (offer, _) = signed_offer(&signing_key);
+    let now = offer.created_at;
+
+    assert!(offer.validate("carol-fingerprint", EPHEMERAL, now).is_err());
+    assert!(
+        offer
+            .validate(RECIPIENT, "other-ephemeral-key", now)
+            .is_err()
+    );
+}
+
+#[test]
+fn test_offer_validity_window() {
+    let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+    let (offer, _) = signed_offer(&signing_key);
+    let created = offer.created_at;
+
+    // Expiry and creation time both tolerate the clock skew, no more
+    let last_valid = created + OFFER_LIFETIME_SECS + OFFER_CLOCK_SKEW_SECS;
+    assert!(offer.validate(RECIPIENT, EPHEMERAL, last_valid).is_ok());
+    assert!(
+        offer
+            .validate(RECIPIENT, EPHEMERAL, last_valid + 1)
+            .is_err()
+    );
+    assert!(
+        offer
+            .validate(RECIPIENT, EPHEMERAL, created - OFFER_CLOCK_SKEW_SECS)
+            .is_ok()
+    );
+    assert!(
+        offer
+            .validate(RECIPIENT, EPHEMERAL, created - OFFER_CLOCK_SKEW_SECS - 1)
+            .is_err()
+    );
+
+    // A sender cannot hand out an offer that stays valid for days
+    let mut long_lived = offer.clone();
+    long_lived.expires_at = created + 7 * 24 * 3600;
+    assert!(long_lived.validate(RECIPIENT, EPHEMERAL, created).is_err());
+}
+
+#[test]
+fn test_seen_offers_prunes_expired_and_caps_size() {
+    let mut seen = SeenOffers::default();
+    seen.insert("first".to_string(), 100, 50).unwrap();
+    assert!(seen.contains("first"));
+
+    // Expired entries are dropped on the next insert
+    seen.insert("second".to_string(), 300, 200).unwrap();
+    assert!(!seen.contains("first"));
+    assert!(seen.contains("second"));
+
+    for i in 1..MAX_SEEN_OFFERS {
+        seen.insert(format!("offer-{}", i), 1000 + i as u64, 200)
+            .unwrap();
+    }
+    assert_eq!(seen.offers.len(), MAX_SEEN_OFFERS);
+
+    // Full of live ids: none is forgotten, the new offer is refused instead
+    assert!(seen.insert("new".to_string(), 5000, 200).is_err());
+    assert!(!seen.contains("new"));
+    assert!(seen.contains("second") && seen.contains("offer-1"));
+
+    // Once some expire there is room again
+    seen.insert("new".to_string(), 5000, 500).unwrap();
+    assert!(seen.contains("new"));
+    assert!(!seen.contains("second"));
+}
@@ -0,0 +1,26 @@
+use rshare::config::{DEFAULT_PROFILE, MAX_PROFILE_NAME_LEN};
+use rshare::dirs::profile;
+
+#[test]
+fn test_profile_names_are_safe_directory_names() {
+    for name in ["work", "personal-2", "old_key", DEFAULT_PROFILE] {
+        assert!(profile::validate_name(name).is_ok(), "{}", name);
+    }
+
+    let too_long = "a".repeat(MAX_PROFILE_NAME_LEN + 1);
+    for name in ["", "..", "a/b", "work space", "é", too_long.as_str()] {
+        assert!(profile::validate_name(name).is_err(), "{}", name);
+    }
+}
+
+#[test]
+fn test_profiles_live_under_the_default_one() {
+    let default = profile::dir_of(DEFAULT_PROFILE).unwrap();
+    let work = profile::dir_of("work").unwrap();
+    assert!(work.starts_with(&default));
+    assert_ne!(work, default);
+    assert!(profile::exists(DEFAULT_PROFILE).unwrap());
+
+    // The default profile is always listed, first
+    assert_eq!(profile::list().unwrap()[0], DEFAULT_PROFILE);
+}
@@ -0,0 +1,39 @@
+use ed25519_dalek::SigningKey;
+use rshare::config::STDIN_FILENAME;
+use rshare::crypto::signing;
+use rshare::server::protocol::{Accept, Hello, trailer_message};
+
+#[test]
+fn test_older_peers_use_a_single_stream() {
+    let hello: Hello = serde_json::from_str(r#"{"version":3,"resume_offset":0}"#).unwrap();
+    assert_eq!(hello.streams, 1);
+
+    let accept: Accept = serde_json::from_str(r#"{"version":3,"resume_offset":0}"#).unwrap();
+    assert_eq!(accept.streams, 1);
+}
+
+#[test]
+fn test_stream_count_roundtrip() {
+    let hello = serde_json::to_string(&Hello::new(0, 4, String::new())).unwrap();
+    let hello: Hello = serde_json::from_str(&hello).unwrap();
+    assert_eq!(hello.streams, 4);
+
+    let accept = serde_json::to_string(&Accept::new(0, None, 2, String::new(), None)).unwrap();
+    let accept: Accept = serde_json::from_str(&accept).unwrap();
+    assert_eq!(accept.streams, 2);
+}
+
+#[test]
+fn test_piped_trailer_binds_the_streamed_size() {
+    let signing_key = SigningKey::from_bytes(&[7u8; 32]);
+    let verifying_key = signing_key.verifying_key();
+
+    // Piped input announces size 0, the trailer signs the size actually sent
+    let message = trailer_message("session", STDIN_FILENAME, 4096, "merkle:abcd");
+    let signature = signing::sign_data(&signing_key, &message).unwrap();
+
+    assert!(signing::verify_signature(&verifying_key, &message, &signature).is_ok());
+
+    let truncated = trailer_message("session", STDIN_FILENAME, 2048, "merkle:abcd");
+    assert!(signing::verify_signature(&verifying_key, &truncated, &signature).is_err());
+}
@@ -0,0 +1,101 @@
+use ed25519_dalek::SigningKey;
+use rshare::config::MAX_KEY_ROTATIONS;
+use rshare::crypto::rotation::{self, KeyRotation};
+use rshare::dirs::contacts::{AuditEvent, ContactList};
+
+fn key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32])
+}
+
+fn fingerprint(key: &SigningKey) -> String {
+    hex::encode(key.verifying_key().to_bytes())
+}
+
+#[test]
+fn test_rotation_needs_both_signatures() {
+    let rotation = KeyRotation::new(&key(1), &key(2), 1_700_000_000).unwrap();
+    assert!(rotation.verify().is_ok());
+    assert_eq!(rotation.old_key, fingerprint(&key(1)));
+    assert_eq!(rotation.new_key, fingerprint(&key(2)));
+
+    // Redirecting the statement to another key breaks the old key's signature
+    let mut redirected = rotation.clone();
+    redirected.new_key = fingerprint(&key(3));
+    assert!(redirected.verify().is_err());
+
+    // A statement signed by the old key alone is not enough
+    let mut unilateral = rotation.clone();
+    unilateral.new_signature = rotation.old_signature.clone();
+    assert!(unilateral.verify().is_err());
+
+    let mut future = rotation.clone();
+    future.version += 1;
+    assert!(future.verify().is_err());
+}
+
+#[test]
+fn test_follow_walks_the_chain_from_the_trusted_key() {
+    let first = KeyRotation::new(&key(1), &key(2), 100).unwrap();
+    let second = KeyRotation::new(&key(2), &key(3), 200).unwrap();
+    let chain = vec![first.clone(), second.clone()];
+
+    let path = rotation::follow(&chain, &fingerprint(&key(1)), &fingerprint(&key(3))).unwrap();
+    assert_eq!(path, vec![first, second.clone()]);
+
+    let path = rotation::follow(&chain, &fingerprint(&key(2)), &fingerprint(&key(3))).unwrap();
+    assert_eq!(path, vec![second]);
+
+    // Nothing leads from a key we never trusted, or to a key nobody rotated to
+    assert!(rotation::follow(&chain, &fingerprint(&key(9)), &fingerprint(&key(3))).is_err());
+    assert!(rotation::follow(&chain, &fingerprint(&key(1)), &fingerprint(&key(9))).is_err());
+
+    // A forged link is refused even when the rest of the chain is valid
+    let mut forged = KeyRotation::new(&key(2), &key(9), 300).unwrap();
+    forged.old_key = fingerprint(&key(1));
+    let chain = vec![forged, chain[1].clone()];
+    assert!(rotation::follow(&chain, &fingerprint(&key(1)), &fingerprint(&key(9))).is_err());
+
+    // Cycles end instead of looping
+    let back = KeyRotation::new(&key(2), &key(1), 300).unwrap();
+    let cycle = vec![KeyRotation::new(&key(1), &key(2), 100).unwrap(), back];
+    assert!(rotation::follow(&cycle, &fingerprint(&key(1)), &fingerprint(&key(3))).is_err());
+
+    let too_long = vec![chain[1].clone(); MAX_KEY_ROTATIONS + 1];
+    assert!(rotation::follow(&too_long, &fingerprint(&key(2)), &fingerprint(&key(3))).is_err());
+}
+
+#[test]
+fn test_contact_follows_rotation_with_audit_entry() {
+    let mut contacts = ContactList::default();
+    contacts
+        .add("alice".to_string(), fingerprint(&key(1)))
+        .unwrap();
+
+    let stale = KeyRotation::new(&key(2), &key(3), 100).unwrap();
+    assert!(contacts.apply_rotation("alice", &[stale]).is_err());
+    assert!(contacts.audit.is_empty());
+
+    let rotation = KeyRotation::new(&key(1), &key(2), 100).unwrap();
+    contacts.apply_rotation("alice", &[rotation]).unwrap();
+    assert_eq!(
+        contacts.get("alice").unwrap().public_key,
+        fingerprint(&key(2))
+    );
+
+    let audit = contacts.audit_of("alice");
+    assert_eq!(audit.len(), 1);
+    let AuditEvent::KeyRotated {
+        old_key,
+        new_key,
+        rotated_at,
+    } = &audit[0].event;
+    assert_eq!(old_key, &fingerprint(&key(1)));
+    assert_eq!(new_key, &fingerprint(&key(2)));
+    assert_eq!(*rotated_at, 100);
+
+    // The audit survives a save and load
+    let json = serde_json::to_string(&contacts).unwrap();
+    let loaded: ContactList = serde_json::from_str(&json).unwrap();
+    assert_eq!(loaded.audit_of("alice").len(), 1);
+    assert!(json.contains("\"event\":\"key_rotated\""));
+}
@@ -0,0 +1,107 @@
+use rshare::config::MAX_FILENAME_LEN;
+use rshare::utils::manifest::resolve_entry_path;
+use rshare::utils::sanitize::sanitize_filename;
+use std::path::Path;
+
+#[test]
+fn test_plain_names_are_unchanged() {
+    for name in [
+        "report.pdf",
+        "backup 2024.tar.gz",
+        ".bashrc",
+        "Über café.txt",
+    ] {
+        let safe = sanitize_filename(name).unwrap();
+        assert_eq!(safe.name, name);
+        assert!(safe.changes.is_empty());
+    }
+}
+
+#[test]
+fn test_traversal_and_absolute_paths_keep_the_last_component() {
+    for raw in [
+        "../../.bashrc",
+        "/home/alice/.bashrc",
+        "..\\..\\.bashrc",
+        "C:\\Users\\alice\\.bashrc",
+    ] {
+        let safe = sanitize_filename(raw).unwrap();
+        assert_eq!(safe.name, ".bashrc", "{}", raw);
+        assert!(!safe.changes.is_empty());
+    }
+}
+
+#[test]
+fn test_unusable_names_are_rejected() {
+    for raw in ["", ".", "..", "../", "/", "dir/..", " . . "] {
+        assert!(sanitize_filename(raw).is_err(), "{:?}", raw);
+    }
+}
+
+#[test]
+fn test_control_invisible_and_forbidden_characters_are_replaced() {
+    let safe = sanitize_filename("evil\u{0}name\n.txt").unwrap();
+    assert_eq!(safe.name, "evil_name_.txt");
+
+    // Right-to-left override disguising an executable as a text file
+    let safe = sanitize_filename("invoice\u{202E}txt.exe").unwrap();
+    assert_eq!(safe.name, "invoice_txt.exe");
+
+    let safe = sanitize_filename("what?<now>:\"|*.txt").unwrap();
+    assert_eq!(safe.name, "what__now_____.txt");
+
+    let safe = sanitize_filename("broken\u{FFFD}.bin").unwrap();
+    assert_eq!(safe.name, "broken_.bin");
+    assert_eq!(safe.changes.len(), 1);
+}
+
+#[test]
+fn test_reserved_device_names_are_prefixed() {
+    assert_eq!(sanitize_filename("CON").unwrap().name, "_CON");
+    assert_eq!(sanitize_filename("nul.txt").unwrap().name, "_nul.txt");
+    assert_eq!(
+        sanitize_filename("com1.tar.gz").unwrap().name,
+        "_com1.tar.gz"
+    );
+    assert_eq!(
+        sanitize_filename("console.txt").unwrap().name,
+        "console.txt"
+    );
+}
+
+#[test]
+fn test_trailing_dots_and_long_names() {
+    assert_eq!(
+        sanitize_filename("notes.txt. . ").unwrap().name,
+        "notes.txt"
+    );
+
+    let long = format!("{}.tar.gz", "é".repeat(300));
+    let safe = sanitize_filename(&long).unwrap();
+    assert!(safe.name.len() <= MAX_FILENAME_LEN);
+    assert!(safe.name.ends_with(".gz"));
+    assert!(!safe.changes.is_empty());
+}
+
+#[test]
+fn test_tree_paths_are_sanitized_per_component() {
+    let root = Path::new("downloads");
+
+    let plain = resolve_entry_path(root, "dist/assets/app.js").unwrap();
+    assert_eq!(plain.path, root.join("dist/assets/app.js"));
+    assert!(plain.changes.is_empty());
+
+    // Every directory and file name is cleaned like a single-file name
+    let resolved = resolve_entry_path(root, "docs\u{202E}fdp.exe/CON/notes\n.txt. . ").unwrap();
+    assert_eq!(resolved.path, root.join("docs_fdp.exe/_CON/notes_.txt"));
+    assert_eq!(resolved.changes.len(), 4);
+
+    // Backslashes cannot smuggle traversal into a component either
+    let resolved = resolve_entry_path(root, "dist/..\\..\\.bashrc").unwrap();
+    assert_eq!(resolved.path, root.join("dist/.bashrc"));
+    assert!(!resolved.changes.is_empty());
+
+    for raw in ["dist/ . . ", "dist/../x", "/etc/passwd"] {
+        assert!(resolve_entry_path(root, raw).is_err(), "{:?}", raw);
+    }
+}
@@ -0,0 +1,59 @@
+use ed25519_dalek::SigningKey;
+use rshare::config::{SAS_GROUP_DIGITS, SAS_GROUPS};
+use rshare::crypto::sas;
+use rshare::dirs::contacts::ContactList;
+
+fn key(seed: u8) -> ed25519_dalek::VerifyingKey {
+    SigningKey::from_bytes(&[seed; 32]).verifying_key()
+}
+
+#[test]
+fn test_both_sides_get_the_same_code() {
+    let code = sas::short_auth_string(&key(1), &key(2));
+    assert_eq!(code, sas::short_auth_string(&key(2), &key(1)));
+
+    let groups: Vec<&str> = code.split(' ').collect();
+    assert_eq!(groups.len(), 2 * SAS_GROUPS);
+    assert!(
+        groups
+            .iter()
+            .all(|group| group.len() == SAS_GROUP_DIGITS
+                && group.chars().all(|c| c.is_ascii_digit()))
+    );
+
+    // A different key on either side changes the code
+    assert_ne!(code, sas::short_auth_string(&key(1), &key(3)));
+    assert_ne!(code, sas::short_auth_string(&key(3), &key(2)));
+}
+
+#[test]
+fn test_each_half_depends_on_one_key() {
+    // Each key contributes its own half, so a substitute key has to match that half alone
+    let code = sas::short_auth_string(&key(1), &key(2));
+    let (one, two) = (sas::key_code(&key(1)), sas::key_code(&key(2)));
+    assert!(code == format!("{} {}", one, two) || code == format!("{} {}", two, one));
+
+    let other = sas::short_auth_string(&key(1), &key(3));
+    assert!(other.contains(&one));
+    assert!(!other.contains(&two));
+}
+
+#[test]
+fn test_contact_verification_is_saved() {
+    // Contacts saved before verification existed load as unverified
+    let old = r#"{"contacts":{"bob":{"name":"bob","public_key":"00","added_at":"2025-01-01T00:00:00Z"}}}"#;
+    let mut contacts: ContactList = serde_json::from_str(old).unwrap();
+    assert!(!contacts.get("bob").unwrap().is_verified());
+    assert!(
+        !serde_json::to_string(&contacts)
+            .unwrap()
+            .contains("verified_at")
+    );
+
+    contacts.mark_verified("bob").unwrap();
+    assert!(contacts.mark_verified("carol").is_err());
+
+    let json = serde_json::to_string(&contacts).unwrap();
+    let loaded: ContactList = serde_json::from_str(&json).unwrap();
+    assert!(loaded.get("bob").unwrap().is_verified());
+}
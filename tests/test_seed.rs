@@ -0,0 +1,46 @@
+use ed25519_dalek::SigningKey;
+use rshare::config::SEED_PHRASE_WORDS;
+use rshare::crypto::seed;
+
+#[test]
+fn test_seed_phrase_restores_the_same_key() {
+    let key = SigningKey::from_bytes(&[7u8; 32]);
+    let phrase = seed::to_phrase(&key).unwrap();
+    assert_eq!(phrase.split(' ').count(), SEED_PHRASE_WORDS);
+
+    let restored = seed::from_phrase(&phrase).unwrap();
+    assert_eq!(restored.to_bytes(), key.to_bytes());
+    assert_eq!(restored.verifying_key(), key.verifying_key());
+
+    // Typed back with other casing and spacing
+    let retyped = format!("  {}\n", phrase.to_uppercase().replace(' ', "   "));
+    assert_eq!(
+        seed::from_phrase(&retyped).unwrap().to_bytes(),
+        key.to_bytes()
+    );
+}
+
+#[test]
+fn test_seed_phrase_is_standard_bip39() {
+    // BIP39 test vector for 256 bits of zero entropy
+    let zero = SigningKey::from_bytes(&[0u8; 32]);
+    let expected = format!("{}art", "abandon ".repeat(23));
+    assert_eq!(seed::to_phrase(&zero).unwrap(), expected);
+}
+
+#[test]
+fn test_seed_phrase_rejects_mistakes() {
+    let phrase = seed::to_phrase(&SigningKey::from_bytes(&[7u8; 32])).unwrap();
+    let words: Vec<&str> = phrase.split(' ').collect();
+
+    // Two words swapped fail the checksum
+    let mut swapped = words.clone();
+    swapped.swap(0, 1);
+    if swapped != words {
+        assert!(seed::from_phrase(&swapped.join(" ")).is_err());
+    }
+
+    assert!(seed::from_phrase(&words[..12].join(" ")).is_err());
+    assert!(seed::from_phrase(&format!("{} notaword", words[..23].join(" "))).is_err());
+    assert!(seed::from_phrase("").is_err());
+}
@@ -0,0 +1,109 @@
+use rshare::server::{StripedReads, StripedWrites};
+use std::time::Duration;
+use tokio::io::{AsyncWriteExt, DuplexStream, duplex};
+use tokio::time::timeout;
+
+const WAIT: Duration = Duration::from_secs(5);
+
+/// `count` connections, as (our write ends, our read ends)
+fn lanes(count: usize) -> (Vec<DuplexStream>, Vec<DuplexStream>) {
+    (0..count).map(|_| duplex(1024 * 1024)).unzip()
+}
+
+/// Write one striped frame by hand: [8B index][4B size][data]
+async fn write_tagged(lane: &mut DuplexStream, index: u64, data: &[u8]) {
+    lane.write_all(&index.to_be_bytes()).await.unwrap();
+    lane.write_all(&(data.len() as u32).to_be_bytes())
+        .await
+        .unwrap();
+    lane.write_all(data).await.unwrap();
+}
+
+#[tokio::test]
+async fn test_frames_are_reassembled_in_index_order() {
+    let (mut writers, readers) = lanes(2);
+    let mut reads = StripedReads::start(readers);
+
+    // Lane 0 carries the odd frames and arrives first, lane 1 the even ones
+    write_tagged(&mut writers[0], 1, b"one").await;
+    write_tagged(&mut writers[0], 3, b"three").await;
+    write_tagged(&mut writers[1], 0, b"zero").await;
+    write_tagged(&mut writers[1], 2, b"two").await;
+
+    for (index, expected) in [&b"zero"[..], b"one", b"two", b"three"]
+        .into_iter()
+        .enumerate()
+    {
+        let frame = timeout(WAIT, reads.read_frame(index as u64))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, expected);
+    }
+}
+
+#[tokio::test]
+async fn test_writer_tasks_roundtrip_through_reader_tasks() {
+    let (writers, readers) = lanes(3);
+    let writes = StripedWrites::start(writers);
+    let mut reads = StripedReads::start(readers);
+
+    let frames: Vec<Vec<u8>> = (0..32u8).map(|i| vec![i; 1000 + i as usize]).collect();
+    let sending = tokio::spawn(async move {
+        for (index, frame) in frames.into_iter().enumerate() {
+            writes.send(index as u64, frame).await.unwrap();
+        }
+        writes.finish().await.unwrap().len()
+    });
+
+    for i in 0..32u8 {
+        let frame = timeout(WAIT, reads.read_frame(i as u64))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, vec![i; 1000 + i as usize]);
+    }
+    assert_eq!(sending.await.unwrap(), 3);
+}
+
+#[tokio::test]
+async fn test_missing_frame_fails_instead_of_hanging() {
+    let (mut writers, readers) = lanes(2);
+    let mut reads = StripedReads::start(readers);
+
+    // Frame 1 never comes, both connections already moved past it
+    write_tagged(&mut writers[0], 0, b"zero").await;
+    write_tagged(&mut writers[0], 2, b"two").await;
+    write_tagged(&mut writers[1], 3, b"three").await;
+
+    assert_eq!(
+        timeout(WAIT, reads.read_frame(0)).await.unwrap().unwrap(),
+        b"zero"
+    );
+    assert!(timeout(WAIT, reads.read_frame(1)).await.unwrap().is_err());
+}
+
+#[tokio::test]
+async fn test_closed_lane_fails_instead_of_truncating() {
+    let (mut writers, readers) = lanes(2);
+    let mut reads = StripedReads::start(readers);
+
+    write_tagged(&mut writers[0], 0, b"zero").await;
+    write_tagged(&mut writers[1], 1, b"one").await;
+    write_tagged(&mut writers[0], 2, b"two").await;
+
+    // Lane 1 goes away in the middle of frame 3
+    writers[1].write_all(&3u64.to_be_bytes()).await.unwrap();
+    writers[1].write_all(&100u32.to_be_bytes()).await.unwrap();
+    writers[1].write_all(b"thr").await.unwrap();
+    writers.remove(1);
+
+    for (index, expected) in [&b"zero"[..], b"one", b"two"].into_iter().enumerate() {
+        let frame = timeout(WAIT, reads.read_frame(index as u64))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(frame, expected);
+    }
+    assert!(timeout(WAIT, reads.read_frame(3)).await.unwrap().is_err());
+}